@@ -0,0 +1,255 @@
+//! Compares looking up the next level and the subtree of comments on MongoDB by matching
+//! materialized paths with `$regex`, as commenter used to, with equality on `parent_comment_id`
+//! and `ancestor_comment_ids`, as it does now.
+//!
+//! Seeds a scratch database with one large thread and times both approaches on it:
+//!
+//! ```text
+//! MONGODB_CONNECTION_STRING=mongodb://... cargo bench --bench subtree_queries
+//! ```
+//!
+//! `BENCH_COMMENT_COUNT` sets the size of the thread, 100000 comments by default, and
+//! `BENCH_DATABASE` the scratch database, which is dropped first, `commenter_bench` by default.
+
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    options::{FindOptions, IndexOptions},
+    Client, Collection, IndexModel,
+};
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+// comments whose next level and subtree are looked up, spread over the thread
+const SAMPLE_COUNT: usize = 50;
+
+// regex of a uuid, as the next-level query used to end its path pattern with
+const UUID_PATTERN: &str =
+    "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+
+struct SeededComment {
+    comment_id: Uuid,
+    materialized_path: String,
+    ancestor_comment_ids: Vec<Uuid>,
+}
+
+// deterministic pseudo-random numbers, so that every run seeds the same thread
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound
+    }
+}
+
+fn indexes() -> Vec<IndexModel> {
+    let index = |keys: Document, name: &str| {
+        IndexModel::builder()
+            .keys(keys)
+            .options(IndexOptions::builder().name(name.to_string()).build())
+            .build()
+    };
+
+    vec![
+        // what the regex queries ran against
+        index(
+            doc! { "materialized_path": 1, "commented_timestamp": -1 },
+            "materialized_path_commented_timestamp",
+        ),
+        // what the equality queries run against
+        index(
+            doc! { "resource_id": 1, "depth": 1, "commented_timestamp": -1 },
+            "resource_id_depth_commented_timestamp",
+        ),
+        index(
+            doc! { "parent_comment_id": 1, "commented_timestamp": -1 },
+            "parent_comment_id_commented_timestamp",
+        ),
+        index(doc! { "ancestor_comment_ids": 1 }, "ancestor_comment_ids"),
+    ]
+}
+
+async fn seed(
+    comments_collection: &Collection<Document>,
+    comment_count: usize,
+) -> Vec<SeededComment> {
+    let resource_id = Uuid::new_v4();
+    let mut random = Lcg(42);
+    let mut comments: Vec<SeededComment> = Vec::with_capacity(comment_count);
+    let mut documents: Vec<Document> = Vec::new();
+
+    for index in 0..comment_count {
+        let comment_id = Uuid::new_v4();
+
+        // about one comment in twenty starts a thread, the others reply to an earlier comment
+        let parent = if index == 0 || random.next(20) == 0 {
+            None
+        } else {
+            Some(&comments[random.next(index)])
+        };
+
+        let (materialized_path, ancestor_comment_ids) = match parent {
+            Some(parent) => (
+                format!("{}->{}", parent.materialized_path, comment_id),
+                [parent.ancestor_comment_ids.as_slice(), &[parent.comment_id]].concat(),
+            ),
+            None => (format!("{}->{}", resource_id, comment_id), vec![]),
+        };
+
+        documents.push(doc! {
+            "comment_id": comment_id.to_string(),
+            "materialized_path": &materialized_path,
+            "resource_id": resource_id.to_string(),
+            "parent_comment_id": parent.map(|parent| parent.comment_id.to_string()),
+            "depth": ancestor_comment_ids.len() as i64 + 1,
+            "ancestor_comment_ids": ancestor_comment_ids
+                .iter()
+                .map(|ancestor_comment_id| ancestor_comment_id.to_string())
+                .collect::<Vec<String>>(),
+            "commented_timestamp": index as i64,
+        });
+
+        comments.push(SeededComment {
+            comment_id,
+            materialized_path,
+            ancestor_comment_ids,
+        });
+
+        if documents.len() == 10_000 {
+            comments_collection
+                .insert_many(std::mem::take(&mut documents), None)
+                .await
+                .unwrap();
+        }
+    }
+
+    if !documents.is_empty() {
+        comments_collection
+            .insert_many(documents, None)
+            .await
+            .unwrap();
+    }
+
+    comments
+}
+
+// runs the query of every sample and returns how long they took together and how many
+// comments they found
+async fn time_queries(
+    comments_collection: &Collection<Document>,
+    filters: &[Document],
+) -> (Duration, usize) {
+    let find_options = FindOptions::builder()
+        .sort(doc! { "commented_timestamp": -1 })
+        .build();
+
+    let started = Instant::now();
+    let mut found_count = 0;
+    for filter in filters {
+        let found: Vec<Document> = comments_collection
+            .find(filter.clone(), find_options.clone())
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        found_count += found.len();
+    }
+
+    (started.elapsed(), found_count)
+}
+
+fn report(name: &str, (elapsed, found_count): (Duration, usize)) {
+    println!(
+        "{:<24} {:>10.2} ms per query {:>10} comments found",
+        name,
+        elapsed.as_secs_f64() * 1000.0 / SAMPLE_COUNT as f64,
+        found_count
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let Ok(connection_string) = env::var("MONGODB_CONNECTION_STRING") else {
+        println!("MONGODB_CONNECTION_STRING is not set, skipping the subtree query benchmarks");
+        return;
+    };
+    let comment_count: usize = env::var("BENCH_COMMENT_COUNT")
+        .unwrap_or_else(|_| "100000".to_string())
+        .parse()
+        .expect("BENCH_COMMENT_COUNT must be a number");
+    let database_name =
+        env::var("BENCH_DATABASE").unwrap_or_else(|_| "commenter_bench".to_string());
+
+    let client = Client::with_uri_str(&connection_string).await.unwrap();
+    let db = client.database(&database_name);
+    db.drop(None).await.unwrap();
+
+    let comments_collection: Collection<Document> = db.collection("comments");
+    comments_collection
+        .create_indexes(indexes(), None)
+        .await
+        .unwrap();
+
+    println!("seeding a thread of {} comments", comment_count);
+    let comments = seed(&comments_collection, comment_count).await;
+
+    // shallow comments have the largest subtrees, deep ones the smallest
+    let samples: Vec<&SeededComment> = (0..SAMPLE_COUNT)
+        .map(|sample| &comments[sample * comments.len() / SAMPLE_COUNT])
+        .collect();
+
+    let regex_next_level_filters: Vec<Document> = samples
+        .iter()
+        .map(|comment| {
+            doc! { "materialized_path": {
+                "$regex": format!("^{}->{}$", comment.materialized_path, UUID_PATTERN)
+            } }
+        })
+        .collect();
+    let parent_next_level_filters: Vec<Document> = samples
+        .iter()
+        .map(|comment| doc! { "parent_comment_id": comment.comment_id.to_string() })
+        .collect();
+    let regex_subtree_filters: Vec<Document> = samples
+        .iter()
+        .map(|comment| {
+            doc! { "materialized_path": { "$regex": format!("^{}", comment.materialized_path) } }
+        })
+        .collect();
+    let ancestor_subtree_filters: Vec<Document> = samples
+        .iter()
+        .map(|comment| {
+            doc! { "$or": [
+                { "comment_id": comment.comment_id.to_string() },
+                { "ancestor_comment_ids": comment.comment_id.to_string() },
+            ] }
+        })
+        .collect();
+
+    report(
+        "next level, $regex",
+        time_queries(&comments_collection, &regex_next_level_filters).await,
+    );
+    report(
+        "next level, parent",
+        time_queries(&comments_collection, &parent_next_level_filters).await,
+    );
+    report(
+        "subtree, $regex",
+        time_queries(&comments_collection, &regex_subtree_filters).await,
+    );
+    report(
+        "subtree, ancestors",
+        time_queries(&comments_collection, &ancestor_subtree_filters).await,
+    );
+
+    db.drop(None).await.unwrap();
+}
@@ -0,0 +1,26 @@
+-- comments keep both the materialized path string served to clients and the same path as an
+-- array of uuids, which is what the subtree and next-level queries run against
+CREATE TABLE comments (
+    comment_id UUID PRIMARY KEY,
+    comment_type TEXT NOT NULL,
+    commenter_account_id UUID NOT NULL,
+    commenter_username TEXT NOT NULL,
+    commented_timestamp TIMESTAMPTZ NOT NULL,
+    comment_text TEXT NOT NULL,
+    branch_comment_ids UUID[] NOT NULL DEFAULT '{}',
+    materialized_path TEXT NOT NULL,
+    path UUID[] NOT NULL
+);
+
+CREATE INDEX comments_path_idx ON comments USING GIN (path);
+CREATE INDEX comments_commented_timestamp_idx ON comments (commented_timestamp DESC);
+
+CREATE TABLE comment_reactions (
+    reaction_id BIGSERIAL PRIMARY KEY,
+    comment_id UUID NOT NULL REFERENCES comments (comment_id) ON DELETE CASCADE,
+    reactor_account_id UUID NOT NULL,
+    reactor_username TEXT NOT NULL,
+    emoji_unified_code TEXT NOT NULL
+);
+
+CREATE INDEX comment_reactions_comment_id_idx ON comment_reactions (comment_id);
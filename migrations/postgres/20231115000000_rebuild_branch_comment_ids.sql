@@ -0,0 +1,7 @@
+-- branch_comment_ids used to be written empty, rebuild them from the comment paths
+UPDATE comments AS parent SET branch_comment_ids = COALESCE((
+    SELECT array_agg(child.comment_id ORDER BY child.commented_timestamp)
+    FROM comments AS child
+    WHERE child.path[1:cardinality(parent.path)] = parent.path
+    AND cardinality(child.path) = cardinality(parent.path) + 1
+), '{}');
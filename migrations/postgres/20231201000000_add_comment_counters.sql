@@ -0,0 +1,41 @@
+-- reply and reaction counters kept on each comment, so that clients don't have to load whole
+-- subtrees or reaction lists to show them
+ALTER TABLE comments
+    ADD COLUMN direct_reply_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN total_descendant_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN reaction_counts JSONB NOT NULL DEFAULT '{}';
+
+-- a reactor reacts with a given emoji at most once, keep the first of any duplicates
+DELETE FROM comment_reactions AS duplicate
+USING comment_reactions AS kept
+WHERE duplicate.comment_id = kept.comment_id
+AND duplicate.reactor_account_id = kept.reactor_account_id
+AND duplicate.emoji_unified_code = kept.emoji_unified_code
+AND duplicate.reaction_id > kept.reaction_id;
+
+CREATE UNIQUE INDEX comment_reactions_reactor_emoji_idx
+    ON comment_reactions (comment_id, reactor_account_id, emoji_unified_code);
+
+UPDATE comments AS parent SET
+    direct_reply_count = (
+        SELECT count(*) FROM comments AS child
+        WHERE child.path @> parent.path
+        AND child.path[1:cardinality(parent.path)] = parent.path
+        AND cardinality(child.path) = cardinality(parent.path) + 1
+    ),
+    total_descendant_count = (
+        SELECT count(*) FROM comments AS child
+        WHERE child.path @> parent.path
+        AND child.path[1:cardinality(parent.path)] = parent.path
+        AND cardinality(child.path) > cardinality(parent.path)
+    );
+
+UPDATE comments SET reaction_counts = COALESCE((
+    SELECT jsonb_object_agg(emoji_unified_code, reaction_count)
+    FROM (
+        SELECT emoji_unified_code, count(*) AS reaction_count
+        FROM comment_reactions
+        WHERE comment_reactions.comment_id = comments.comment_id
+        GROUP BY emoji_unified_code
+    ) AS counts
+), '{}');
@@ -0,0 +1,12 @@
+-- resources get a record of their own in the registry, next to the settings of their comment
+-- sections. resources that only had settings are registered without a title
+ALTER TABLE resources
+    ADD COLUMN title TEXT NOT NULL DEFAULT '',
+    ADD COLUMN canonical_url TEXT,
+    ADD COLUMN owner_account_id UUID,
+    ADD COLUMN created_timestamp TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN comments_open BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN max_depth INTEGER,
+    ADD COLUMN moderation_mode TEXT NOT NULL DEFAULT 'unmoderated';
+
+CREATE INDEX resources_owner_account_id_idx ON resources (owner_account_id);
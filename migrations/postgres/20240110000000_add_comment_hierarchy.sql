@@ -0,0 +1,19 @@
+-- the resource, parent and depth of every comment as columns of their own, so that they can be
+-- queried without taking the path apart
+ALTER TABLE comments
+    ADD COLUMN resource_id UUID,
+    ADD COLUMN parent_comment_id UUID,
+    ADD COLUMN depth INTEGER;
+
+-- paths start with the resource id, followed by the ids from the root comment down
+UPDATE comments SET
+    resource_id = path[1],
+    parent_comment_id = CASE WHEN cardinality(path) > 2 THEN path[cardinality(path) - 1] END,
+    depth = cardinality(path) - 1;
+
+ALTER TABLE comments
+    ALTER COLUMN resource_id SET NOT NULL,
+    ALTER COLUMN depth SET NOT NULL;
+
+CREATE INDEX comments_resource_id_idx ON comments (resource_id);
+CREATE INDEX comments_parent_comment_id_idx ON comments (parent_comment_id);
@@ -0,0 +1,23 @@
+-- the keys the top and controversial orders sort comments by, kept up to date whenever a
+-- reaction is appended or removed
+ALTER TABLE comments
+    ADD COLUMN total_reaction_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN controversy_score DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+UPDATE comments SET total_reaction_count = (
+    SELECT COALESCE(SUM(value::INTEGER), 0) FROM jsonb_each_text(reaction_counts)
+);
+
+-- (approving + disapproving) * min / max, so that comments with many reactions split evenly
+-- between thumbs up and thumbs down rank highest
+UPDATE comments SET controversy_score = CASE
+    WHEN COALESCE((reaction_counts->>'1f44d')::INTEGER, 0) > 0
+        AND COALESCE((reaction_counts->>'1f44e')::INTEGER, 0) > 0
+    THEN ((reaction_counts->>'1f44d')::DOUBLE PRECISION
+        + (reaction_counts->>'1f44e')::DOUBLE PRECISION)
+        * LEAST((reaction_counts->>'1f44d')::DOUBLE PRECISION,
+            (reaction_counts->>'1f44e')::DOUBLE PRECISION)
+        / GREATEST((reaction_counts->>'1f44d')::DOUBLE PRECISION,
+            (reaction_counts->>'1f44e')::DOUBLE PRECISION)
+    ELSE 0
+END;
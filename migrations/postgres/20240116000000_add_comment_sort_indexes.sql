@@ -0,0 +1,23 @@
+-- root comments and replies are listed in every order straight from an index: newest and oldest
+-- by timestamp, top by reaction count and controversial by score, ties broken by timestamp and id.
+-- the indexes start with the columns of the single-column ones, which they take the place of
+DROP INDEX comments_resource_id_idx;
+DROP INDEX comments_parent_comment_id_idx;
+
+CREATE INDEX comments_root_timestamp_idx
+    ON comments (resource_id, depth, commented_timestamp DESC, comment_id DESC);
+CREATE INDEX comments_root_reaction_count_idx
+    ON comments (resource_id, depth, total_reaction_count DESC, commented_timestamp DESC,
+        comment_id DESC);
+CREATE INDEX comments_root_controversy_score_idx
+    ON comments (resource_id, depth, controversy_score DESC, commented_timestamp DESC,
+        comment_id DESC);
+
+CREATE INDEX comments_reply_timestamp_idx
+    ON comments (parent_comment_id, commented_timestamp DESC, comment_id DESC);
+CREATE INDEX comments_reply_reaction_count_idx
+    ON comments (parent_comment_id, total_reaction_count DESC, commented_timestamp DESC,
+        comment_id DESC);
+CREATE INDEX comments_reply_controversy_score_idx
+    ON comments (parent_comment_id, controversy_score DESC, commented_timestamp DESC,
+        comment_id DESC);
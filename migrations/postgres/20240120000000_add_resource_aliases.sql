@@ -0,0 +1,9 @@
+-- ids that stand for another resource, such as the ids of resources merged into it. lookups
+-- on an alias are answered with the comment thread of the resource it stands for
+CREATE TABLE resource_aliases (
+    alias_resource_id UUID PRIMARY KEY,
+    resource_id UUID NOT NULL,
+    created_timestamp TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX resource_aliases_resource_id_idx ON resource_aliases (resource_id);
@@ -0,0 +1,14 @@
+-- comments made on pre-moderated resources, held apart from the threads until a moderator
+-- approves or rejects them
+CREATE TABLE pending_comments (
+    comment_id UUID PRIMARY KEY,
+    resource_id UUID NOT NULL,
+    parent_comment_id UUID,
+    commenter_account_id UUID NOT NULL,
+    commenter_username TEXT NOT NULL,
+    commented_timestamp TIMESTAMPTZ NOT NULL,
+    comment_text TEXT NOT NULL
+);
+
+CREATE INDEX pending_comments_resource_id_idx
+    ON pending_comments (resource_id, commented_timestamp, comment_id);
@@ -0,0 +1,26 @@
+-- uuids are stored as hyphenated text so that materialized path prefixes can be matched with
+-- GLOB, which sqlite serves from the materialized_path index
+CREATE TABLE comments (
+    comment_id TEXT PRIMARY KEY NOT NULL,
+    comment_type TEXT NOT NULL,
+    commenter_account_id TEXT NOT NULL,
+    commenter_username TEXT NOT NULL,
+    commented_timestamp TEXT NOT NULL,
+    comment_text TEXT NOT NULL,
+    branch_comment_ids TEXT NOT NULL DEFAULT '[]',
+    materialized_path TEXT NOT NULL,
+    path_depth INTEGER NOT NULL
+);
+
+CREATE INDEX comments_materialized_path_idx ON comments (materialized_path);
+CREATE INDEX comments_commented_timestamp_idx ON comments (commented_timestamp DESC);
+
+CREATE TABLE comment_reactions (
+    reaction_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    comment_id TEXT NOT NULL REFERENCES comments (comment_id) ON DELETE CASCADE,
+    reactor_account_id TEXT NOT NULL,
+    reactor_username TEXT NOT NULL,
+    emoji_unified_code TEXT NOT NULL
+);
+
+CREATE INDEX comment_reactions_comment_id_idx ON comment_reactions (comment_id);
@@ -0,0 +1,9 @@
+-- branch_comment_ids used to be written empty, rebuild them from the comment paths
+UPDATE comments SET branch_comment_ids = (
+    SELECT json_group_array(comment_id) FROM (
+        SELECT child.comment_id FROM comments AS child
+        WHERE child.materialized_path GLOB comments.materialized_path || '->*'
+        AND child.path_depth = comments.path_depth + 1
+        ORDER BY child.commented_timestamp
+    )
+);
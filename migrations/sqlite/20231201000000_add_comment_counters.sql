@@ -0,0 +1,33 @@
+-- reply and reaction counters kept on each comment, so that clients don't have to load whole
+-- subtrees or reaction lists to show them
+ALTER TABLE comments ADD COLUMN direct_reply_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE comments ADD COLUMN total_descendant_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE comments ADD COLUMN reaction_counts TEXT NOT NULL DEFAULT '{}';
+
+-- a reactor reacts with a given emoji at most once, keep the first of any duplicates
+DELETE FROM comment_reactions WHERE reaction_id NOT IN (
+    SELECT min(reaction_id) FROM comment_reactions
+    GROUP BY comment_id, reactor_account_id, emoji_unified_code
+);
+
+CREATE UNIQUE INDEX comment_reactions_reactor_emoji_idx
+    ON comment_reactions (comment_id, reactor_account_id, emoji_unified_code);
+
+UPDATE comments SET
+    direct_reply_count = (
+        SELECT count(*) FROM comments AS child
+        WHERE child.materialized_path GLOB comments.materialized_path || '->*'
+        AND child.path_depth = comments.path_depth + 1
+    ),
+    total_descendant_count = (
+        SELECT count(*) FROM comments AS child
+        WHERE child.materialized_path GLOB comments.materialized_path || '->*'
+    ),
+    reaction_counts = (
+        SELECT json_group_object(emoji_unified_code, reaction_count) FROM (
+            SELECT emoji_unified_code, count(*) AS reaction_count
+            FROM comment_reactions
+            WHERE comment_reactions.comment_id = comments.comment_id
+            GROUP BY emoji_unified_code
+        )
+    );
@@ -0,0 +1,8 @@
+-- deleted comments can be left in their threads as tombstones, which keep their replies in place
+ALTER TABLE comments ADD COLUMN deleted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- settings of the comment sections of resources, resources without a row use the defaults
+CREATE TABLE resources (
+    resource_id TEXT PRIMARY KEY NOT NULL,
+    delete_mode TEXT NOT NULL
+);
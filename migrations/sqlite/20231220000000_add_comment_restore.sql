@@ -0,0 +1,14 @@
+-- tombstones can be restored for a while after they were deleted
+ALTER TABLE comments ADD COLUMN deleted_timestamp TEXT;
+
+-- text and author of tombstones that can still be restored
+CREATE TABLE deleted_comment_contents (
+    comment_id TEXT PRIMARY KEY NOT NULL REFERENCES comments (comment_id) ON DELETE CASCADE,
+    commenter_account_id TEXT NOT NULL,
+    commenter_username TEXT NOT NULL,
+    comment_text TEXT NOT NULL,
+    deleted_timestamp TEXT NOT NULL
+);
+
+-- tombstones are purged once their restore window expires
+CREATE INDEX comments_deleted_timestamp_idx ON comments (deleted_timestamp) WHERE deleted;
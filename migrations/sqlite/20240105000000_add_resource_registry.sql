@@ -0,0 +1,14 @@
+-- resources get a record of their own in the registry, next to the settings of their comment
+-- sections. resources that only had settings are registered without a title
+ALTER TABLE resources ADD COLUMN title TEXT NOT NULL DEFAULT '';
+ALTER TABLE resources ADD COLUMN canonical_url TEXT;
+ALTER TABLE resources ADD COLUMN owner_account_id TEXT;
+-- sqlite only adds columns with constant defaults, the resources so far were registered now
+ALTER TABLE resources ADD COLUMN created_timestamp TEXT NOT NULL DEFAULT '';
+ALTER TABLE resources ADD COLUMN comments_open BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE resources ADD COLUMN max_depth INTEGER;
+ALTER TABLE resources ADD COLUMN moderation_mode TEXT NOT NULL DEFAULT 'unmoderated';
+
+UPDATE resources SET created_timestamp = strftime('%Y-%m-%dT%H:%M:%fZ', 'now');
+
+CREATE INDEX resources_owner_account_id_idx ON resources (owner_account_id);
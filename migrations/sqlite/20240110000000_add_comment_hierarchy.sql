@@ -0,0 +1,17 @@
+-- the resource, parent and depth of every comment as columns of their own, so that they can be
+-- queried without taking the path apart
+ALTER TABLE comments ADD COLUMN resource_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE comments ADD COLUMN parent_comment_id TEXT;
+ALTER TABLE comments ADD COLUMN depth INTEGER NOT NULL DEFAULT 0;
+
+-- paths start with the resource id, followed by the ids from the root comment down. every
+-- hyphenated uuid is 36 characters long and followed by the 2 characters of '->'
+UPDATE comments SET
+    resource_id = substr(materialized_path, 1, 36),
+    parent_comment_id = CASE
+        WHEN path_depth > 2 THEN substr(materialized_path, (path_depth - 2) * 38 + 1, 36)
+    END,
+    depth = path_depth - 1;
+
+CREATE INDEX comments_resource_id_idx ON comments (resource_id);
+CREATE INDEX comments_parent_comment_id_idx ON comments (parent_comment_id);
@@ -0,0 +1,21 @@
+-- the keys the top and controversial orders sort comments by, kept up to date whenever a
+-- reaction is appended or removed
+ALTER TABLE comments ADD COLUMN total_reaction_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE comments ADD COLUMN controversy_score REAL NOT NULL DEFAULT 0;
+
+UPDATE comments SET total_reaction_count = (
+    SELECT COALESCE(SUM(value), 0) FROM json_each(reaction_counts)
+);
+
+-- (approving + disapproving) * min / max, so that comments with many reactions split evenly
+-- between thumbs up and thumbs down rank highest
+UPDATE comments SET controversy_score = CASE
+    WHEN COALESCE(json_extract(reaction_counts, '$."1f44d"'), 0) > 0
+        AND COALESCE(json_extract(reaction_counts, '$."1f44e"'), 0) > 0
+    THEN (json_extract(reaction_counts, '$."1f44d"') + json_extract(reaction_counts, '$."1f44e"'))
+        * CAST(min(json_extract(reaction_counts, '$."1f44d"'),
+            json_extract(reaction_counts, '$."1f44e"')) AS REAL)
+        / max(json_extract(reaction_counts, '$."1f44d"'),
+            json_extract(reaction_counts, '$."1f44e"'))
+    ELSE 0
+END;
@@ -0,0 +1,9 @@
+-- ids that stand for another resource, such as the ids of resources merged into it. lookups
+-- on an alias are answered with the comment thread of the resource it stands for
+CREATE TABLE resource_aliases (
+    alias_resource_id TEXT PRIMARY KEY NOT NULL,
+    resource_id TEXT NOT NULL,
+    created_timestamp TEXT NOT NULL
+);
+
+CREATE INDEX resource_aliases_resource_id_idx ON resource_aliases (resource_id);
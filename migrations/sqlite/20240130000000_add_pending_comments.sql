@@ -0,0 +1,14 @@
+-- comments made on pre-moderated resources, held apart from the threads until a moderator
+-- approves or rejects them
+CREATE TABLE pending_comments (
+    comment_id TEXT PRIMARY KEY NOT NULL,
+    resource_id TEXT NOT NULL,
+    parent_comment_id TEXT,
+    commenter_account_id TEXT NOT NULL,
+    commenter_username TEXT NOT NULL,
+    commented_timestamp TEXT NOT NULL,
+    comment_text TEXT NOT NULL
+);
+
+CREATE INDEX pending_comments_resource_id_idx
+    ON pending_comments (resource_id, commented_timestamp, comment_id);
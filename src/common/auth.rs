@@ -0,0 +1,97 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+    Extension,
+};
+
+use crate::common::errors::ServerError;
+
+/// Key that callers of privileged endpoints present as a bearer token. Privileged endpoints are
+/// disabled when no key is configured.
+#[derive(Clone, Debug)]
+pub struct AdminApiKey(pub Option<String>);
+
+/// Extractor that only lets requests carrying the admin API key through, for endpoints that
+/// moderators and operators use but commenters must not.
+#[derive(Debug)]
+pub struct Admin;
+
+// compares in time independent of where the inputs differ, so that the key can't be guessed
+// byte by byte from response times
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(AdminApiKey(admin_api_key)) =
+            Extension::<AdminApiKey>::from_request_parts(parts, state)
+                .await
+                .map_err(|_| ServerError::internal_server_error())?;
+
+        let presented_key = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match (admin_api_key, presented_key) {
+            (Some(admin_api_key), Some(presented_key))
+                if constant_time_eq(admin_api_key.as_bytes(), presented_key.as_bytes()) =>
+            {
+                Ok(Admin)
+            }
+            _ => Err(ServerError::forbidden_error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Request, StatusCode};
+
+    use super::*;
+
+    async fn extract(
+        admin_api_key: Option<&str>,
+        authorization: Option<&str>,
+    ) -> Option<StatusCode> {
+        let mut request =
+            Request::builder().extension(AdminApiKey(admin_api_key.map(String::from)));
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+
+        Admin::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .map(|rejection| rejection.status_code)
+    }
+
+    #[tokio::test]
+    async fn only_the_admin_api_key_is_let_through() {
+        assert_eq!(extract(Some("secret"), Some("Bearer secret")).await, None);
+        assert_eq!(
+            extract(Some("secret"), Some("Bearer secrets")).await,
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            extract(Some("secret"), Some("secret")).await,
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            extract(Some("secret"), None).await,
+            Some(StatusCode::FORBIDDEN)
+        );
+        // privileged endpoints are disabled without a configured key
+        assert_eq!(
+            extract(None, Some("Bearer secret")).await,
+            Some(StatusCode::FORBIDDEN)
+        );
+    }
+}
@@ -19,6 +19,13 @@ impl ServerError {
         }
     }
 
+    pub fn bad_request_error(message: &str) -> ServerError {
+        ServerError {
+            message: Some(message.to_string()),
+            status_code: StatusCode::BAD_REQUEST,
+        }
+    }
+
     pub fn forbidden_error() -> ServerError {
         ServerError {
             message: None,
@@ -15,3 +15,146 @@ pub fn append_uuid_to_materialized_path(existing_path: &str, new_uuid: &Uuid) ->
         format!("{}->{}", existing_path, new_uuid)
     }
 }
+
+pub fn materialized_path_to_uuid_list(materialized_path: &str) -> anyhow::Result<Vec<Uuid>> {
+    materialized_path
+        .split("->")
+        .map(|uuid| Ok(Uuid::parse_str(uuid)?))
+        .collect()
+}
+
+/// Returns the id of the resource a comment belongs to, which heads its materialized path.
+pub fn resource_id_from_materialized_path(materialized_path: &str) -> Option<Uuid> {
+    materialized_path
+        .split("->")
+        .next()
+        .and_then(|uuid| Uuid::parse_str(uuid).ok())
+}
+
+/// Returns how deep the comment at the given path is in its thread, root comments being at depth
+/// 1.
+pub fn depth_from_materialized_path(materialized_path: &str) -> u32 {
+    materialized_path.split("->").count().saturating_sub(1) as u32
+}
+
+/// Returns the id of the comment a branch comment was branched from, or `None` for a root
+/// comment, whose path starts with the resource id followed by its own id.
+pub fn parent_comment_id_from_materialized_path(materialized_path: &str) -> Option<Uuid> {
+    let uuids: Vec<&str> = materialized_path.split("->").collect();
+
+    if uuids.len() < 3 {
+        return None;
+    }
+
+    Uuid::parse_str(uuids[uuids.len() - 2]).ok()
+}
+
+pub fn comment_id_from_materialized_path(materialized_path: &str) -> Option<Uuid> {
+    materialized_path
+        .rsplit("->")
+        .next()
+        .and_then(|uuid| Uuid::parse_str(uuid).ok())
+}
+
+/// Returns the ids of every comment above the comment at the given path, root comment first.
+pub fn ancestor_comment_ids_from_materialized_path(materialized_path: &str) -> Vec<Uuid> {
+    let uuids: Vec<&str> = materialized_path.split("->").collect();
+
+    if uuids.len() < 3 {
+        return vec![];
+    }
+
+    uuids[1..uuids.len() - 1]
+        .iter()
+        .filter_map(|uuid| Uuid::parse_str(uuid).ok())
+        .collect()
+}
+
+/// Returns the path of a comment at or below the comment at `old_path` once that comment has
+/// moved to `new_path`, or `None` if the comment isn't at or below it.
+pub fn rebase_materialized_path(
+    materialized_path: &str,
+    old_path: &str,
+    new_path: &str,
+) -> Option<String> {
+    let rest = materialized_path.strip_prefix(old_path)?;
+
+    (rest.is_empty() || rest.starts_with("->")).then(|| format!("{}{}", new_path, rest))
+}
+
+/// Emoji unified codes are hex code points joined by dashes, e.g. `1f44d` or `1f468-200d-1f469`.
+/// Checking the format keeps them safe to use as keys of the per-emoji reaction counts.
+pub fn is_valid_emoji_unified_code(emoji_unified_code: &str) -> bool {
+    !emoji_unified_code.is_empty()
+        && emoji_unified_code.split('-').all(|code_point| {
+            !code_point.is_empty() && code_point.chars().all(|c| c.is_ascii_hexdigit())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchy_is_read_from_materialized_paths() {
+        let uuids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let root_path = uuid_list_to_materialized_path(&uuids[..2]);
+        let nested_path = uuid_list_to_materialized_path(&uuids);
+
+        assert_eq!(
+            append_uuid_to_materialized_path(&root_path, &uuids[2]),
+            uuid_list_to_materialized_path(&uuids[..3])
+        );
+        assert_eq!(materialized_path_to_uuid_list(&nested_path).unwrap(), uuids);
+
+        assert_eq!(
+            resource_id_from_materialized_path(&nested_path),
+            Some(uuids[0])
+        );
+        assert_eq!(
+            comment_id_from_materialized_path(&nested_path),
+            Some(uuids[3])
+        );
+
+        assert_eq!(depth_from_materialized_path(&root_path), 1);
+        assert_eq!(depth_from_materialized_path(&nested_path), 3);
+
+        assert_eq!(parent_comment_id_from_materialized_path(&root_path), None);
+        assert_eq!(
+            parent_comment_id_from_materialized_path(&nested_path),
+            Some(uuids[2])
+        );
+
+        assert!(ancestor_comment_ids_from_materialized_path(&root_path).is_empty());
+        assert_eq!(
+            ancestor_comment_ids_from_materialized_path(&nested_path),
+            uuids[1..3]
+        );
+    }
+
+    #[test]
+    fn paths_are_rebased_only_at_or_below_the_moved_comment() {
+        let uuids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let old_path = uuid_list_to_materialized_path(&uuids[..2]);
+        let new_path = uuid_list_to_materialized_path(&[uuids[0], uuids[3], uuids[1]]);
+        let nested_path = uuid_list_to_materialized_path(&uuids[..3]);
+
+        assert_eq!(
+            rebase_materialized_path(&old_path, &old_path, &new_path),
+            Some(new_path.clone())
+        );
+        assert_eq!(
+            rebase_materialized_path(&nested_path, &old_path, &new_path),
+            Some(append_uuid_to_materialized_path(&new_path, &uuids[2]))
+        );
+        assert_eq!(
+            rebase_materialized_path(&uuids[0].to_string(), &old_path, &new_path),
+            None
+        );
+        // a path merely starting with the same characters isn't below it
+        assert_eq!(
+            rebase_materialized_path(&format!("{}0", old_path), &old_path, &new_path),
+            None
+        );
+    }
+}
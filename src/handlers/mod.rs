@@ -1,20 +1,49 @@
-use axum::{Extension, Json};
+use axum::{
+    body::StreamBody,
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
 use chrono::Utc;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use serde_json::json;
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
 use tracing::{debug, instrument};
 use uuid::Uuid;
 
 use crate::{
     common::{
+        auth::Admin,
         errors::ServerError,
-        utils::{append_uuid_to_materialized_path, uuid_list_to_materialized_path},
+        utils::{
+            append_uuid_to_materialized_path, is_valid_emoji_unified_code,
+            uuid_list_to_materialized_path,
+        },
+    },
+    models::{
+        Comment, CommentCursor, CommentNode, CommentReaction, CommentReactor, CommentSort,
+        CommentType, Commenter, DeleteMode, ListingFormat, ModerationMode, MoreReplies,
+        PendingComment, Resource, ResourceAlias, ResourceSettings, CURRENT_COMMENT_SCHEMA_VERSION,
+    },
+    persistent::CommentStore,
+    service::{
+        export::{export_thread, ExportFormat},
+        purge::RestoreWindow,
     },
-    models::{Comment, CommentReaction, CommentReactor, CommentType, Commenter},
-    persistent::PersistentLayer,
 };
 
+/// The most levels of replies `/branch-comments/levels` fetches, whatever `depth` asks for.
+pub const MAX_BRANCH_COMMENTS_LEVELS: u32 = 10;
+
+/// How many replies per comment `/branch-comments/levels` fetches if no `limit` is given.
+pub const DEFAULT_BRANCH_COMMENTS_LEVELS_LIMIT: u32 = 10;
+
+/// The most replies per comment `/branch-comments/levels` fetches, whatever `limit` asks for.
+pub const MAX_BRANCH_COMMENTS_LEVELS_LIMIT: u32 = 100;
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct CreateRootCommentRequest {
     pub resource_id: Uuid,
@@ -24,33 +53,46 @@ pub struct CreateRootCommentRequest {
 }
 
 #[instrument(level = "trace")]
-pub async fn create_root_comment(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn create_root_comment<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<CreateRootCommentRequest>,
 ) -> Result<String, ServerError> {
     debug!(payload = ?payload);
 
-    let comment_id = Uuid::new_v4();
+    // comments made under an alias go to the thread of the resource it stands for
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
 
-    let comment = Comment {
-        comment_id,
-        comment_type: CommentType::Root,
+    let resource_settings = persistent_layer
+        .find_resource_settings(resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if !resource_settings.comments_open {
+        return Err(ServerError::forbidden_error());
+    }
+
+    let pending_comment = PendingComment {
+        comment_id: Uuid::new_v4(),
+        resource_id,
+        parent_comment_id: None,
         commenter: Commenter {
             account_id: payload.commenter_account_id,
             username: payload.commenter_username,
         },
         commented_timestamp: Utc::now(),
         comment_text: payload.comment_text,
-        reactions: vec![],
-        branch_comment_ids: vec![],
-        materialized_path: uuid_list_to_materialized_path(&[payload.resource_id, comment_id]),
     };
 
-    if (persistent_layer.insert_comment_mongo(comment).await).is_err() {
-        return Err(ServerError::internal_server_error());
-    }
-
-    Ok(json!({ "comment_id": comment_id }).to_string())
+    publish_or_hold(
+        &*persistent_layer,
+        pending_comment,
+        None,
+        resource_settings.moderation_mode,
+    )
+    .await
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -62,8 +104,8 @@ pub struct CreateBranchCommentRequest {
 }
 
 #[instrument(level = "trace")]
-pub async fn create_branch_comment(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn create_branch_comment<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<CreateBranchCommentRequest>,
 ) -> Result<String, ServerError> {
     debug!(payload = ?payload);
@@ -73,30 +115,126 @@ pub async fn create_branch_comment(
         .await
         .map_err(|_| ServerError::forbidden_error())?;
 
-    let comment_id = Uuid::new_v4();
+    // tombstones keep the replies they already have, but take no new ones
+    if branched_from_comment.deleted {
+        return Err(ServerError::forbidden_error());
+    }
 
-    let comment = Comment {
-        comment_id,
-        comment_type: CommentType::Branch,
+    let resource_settings = persistent_layer
+        .find_resource_settings(branched_from_comment.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if !resource_settings.comments_open {
+        return Err(ServerError::forbidden_error());
+    }
+
+    if let Some(max_depth) = resource_settings.max_depth {
+        if branched_from_comment.depth + 1 > max_depth {
+            return Err(ServerError::bad_request_error(
+                "maximum threading depth reached",
+            ));
+        }
+    }
+
+    let pending_comment = PendingComment {
+        comment_id: Uuid::new_v4(),
+        resource_id: branched_from_comment.resource_id,
+        parent_comment_id: Some(branched_from_comment.comment_id),
         commenter: Commenter {
             account_id: payload.commenter_account_id,
             username: payload.commenter_username,
         },
         commented_timestamp: Utc::now(),
         comment_text: payload.comment_text,
-        reactions: vec![],
-        branch_comment_ids: vec![],
-        materialized_path: append_uuid_to_materialized_path(
-            &branched_from_comment.materialized_path,
-            &comment_id,
+    };
+
+    publish_or_hold(
+        &*persistent_layer,
+        pending_comment,
+        Some(&branched_from_comment),
+        resource_settings.moderation_mode,
+    )
+    .await
+}
+
+// the comment a new or approved comment is published as: a root comment of its resource, or a
+// reply to the comment it was branched from
+fn new_comment(
+    pending_comment: PendingComment,
+    branched_from_comment: Option<&Comment>,
+) -> Comment {
+    let comment_id = pending_comment.comment_id;
+
+    let (comment_type, materialized_path, depth, ancestor_comment_ids) = match branched_from_comment
+    {
+        Some(branched_from_comment) => (
+            CommentType::Branch,
+            append_uuid_to_materialized_path(&branched_from_comment.materialized_path, &comment_id),
+            branched_from_comment.depth + 1,
+            [
+                branched_from_comment.ancestor_comment_ids.as_slice(),
+                &[branched_from_comment.comment_id],
+            ]
+            .concat(),
         ),
+        None => (
+            CommentType::Root,
+            uuid_list_to_materialized_path(&[pending_comment.resource_id, comment_id]),
+            1,
+            vec![],
+        ),
+    };
+
+    Comment {
+        comment_id,
+        comment_type,
+        commenter: pending_comment.commenter,
+        commented_timestamp: pending_comment.commented_timestamp,
+        comment_text: pending_comment.comment_text,
+        branch_comment_ids: vec![],
+        materialized_path,
+        resource_id: pending_comment.resource_id,
+        parent_comment_id: branched_from_comment.map(|comment| comment.comment_id),
+        depth,
+        ancestor_comment_ids,
+        direct_reply_count: 0,
+        total_descendant_count: 0,
+        reaction_counts: BTreeMap::new(),
+        total_reaction_count: 0,
+        controversy_score: 0.0,
+        deleted: false,
+        deleted_timestamp: None,
+        schema_version: CURRENT_COMMENT_SCHEMA_VERSION,
+    }
+}
+
+// publishes a new comment right away, or holds it until a moderator approves it if the resource
+// is pre-moderated
+async fn publish_or_hold<S: CommentStore + ?Sized>(
+    persistent_layer: &S,
+    pending_comment: PendingComment,
+    branched_from_comment: Option<&Comment>,
+    moderation_mode: ModerationMode,
+) -> Result<String, ServerError> {
+    let comment_id = pending_comment.comment_id;
+    let pending = moderation_mode == ModerationMode::PreModeration;
+
+    let insert_result = if pending {
+        persistent_layer
+            .insert_pending_comment(pending_comment)
+            .await
+    } else {
+        persistent_layer
+            .insert_comment(new_comment(pending_comment, branched_from_comment))
+            .await
     };
 
-    if (persistent_layer.insert_comment_mongo(comment).await).is_err() {
+    if insert_result.is_err() {
         return Err(ServerError::internal_server_error());
     }
 
-    Ok(json!({ "comment_id": comment_id }).to_string())
+    Ok(json!({ "comment_id": comment_id, "pending": pending }).to_string())
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -108,12 +246,28 @@ pub struct ReactToCommentRequest {
 }
 
 #[instrument(level = "trace")]
-pub async fn react_to_comment(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn react_to_comment<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<ReactToCommentRequest>,
 ) -> Result<(), ServerError> {
     debug!(payload = ?payload);
 
+    if !is_valid_emoji_unified_code(&payload.emoji_unicode) {
+        return Err(ServerError::bad_request_error(
+            "emoji_unicode must be an emoji unified code",
+        ));
+    }
+
+    // make sure the reacted comment exists and isn't a tombstone
+    let reacted_comment = persistent_layer
+        .find_comment(payload.reacted_comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    if reacted_comment.deleted {
+        return Err(ServerError::forbidden_error());
+    }
+
     let comment_reaction = CommentReaction {
         reactor: CommentReactor {
             account_id: payload.reactor_account_id,
@@ -123,7 +277,7 @@ pub async fn react_to_comment(
     };
 
     if (persistent_layer
-        .append_reaction_to_comment_mongo(payload.reacted_comment_id, comment_reaction)
+        .append_reaction_to_comment(payload.reacted_comment_id, comment_reaction)
         .await)
         .is_err()
     {
@@ -142,12 +296,18 @@ pub struct UndoReactToCommentRequest {
 }
 
 #[instrument(level = "trace")]
-pub async fn undo_react_to_comment(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn undo_react_to_comment<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<UndoReactToCommentRequest>,
 ) -> Result<(), ServerError> {
     debug!(payload = ?payload);
 
+    if !is_valid_emoji_unified_code(&payload.emoji_unicode) {
+        return Err(ServerError::bad_request_error(
+            "emoji_unicode must be an emoji unified code",
+        ));
+    }
+
     let comment_reaction = CommentReaction {
         reactor: CommentReactor {
             account_id: payload.reactor_account_id,
@@ -157,7 +317,7 @@ pub async fn undo_react_to_comment(
     };
 
     if (persistent_layer
-        .remove_reaction_from_comment_mongo(payload.reacted_comment_id, comment_reaction)
+        .remove_reaction_from_comment(payload.reacted_comment_id, comment_reaction)
         .await)
         .is_err()
     {
@@ -167,6 +327,33 @@ pub async fn undo_react_to_comment(
     Ok(())
 }
 
+#[derive(Deserialize, Clone, Debug)]
+pub struct GetReactionsRequest {
+    pub reacted_comment_id: Uuid,
+    pub limit: Option<u32>,
+}
+
+#[instrument(level = "trace")]
+pub async fn get_reactions<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<GetReactionsRequest>,
+) -> Result<String, ServerError> {
+    debug!(payload = ?payload);
+
+    // make sure the reacted comment exists
+    persistent_layer
+        .find_comment(payload.reacted_comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    let reactions = persistent_layer
+        .find_comment_reactions(payload.reacted_comment_id, payload.limit)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(json!({ "reactions": reactions }).to_string())
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct UpdateCommentTextRequest {
     pub comment_id: Uuid,
@@ -174,24 +361,25 @@ pub struct UpdateCommentTextRequest {
 }
 
 #[instrument(level = "trace")]
-pub async fn update_comment_text(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn update_comment_text<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<UpdateCommentTextRequest>,
 ) -> Result<(), ServerError> {
     debug!(payload = ?payload);
 
-    // find the comment to be updated
-    let mut comment = persistent_layer
+    // make sure the comment to be updated exists and isn't a tombstone
+    let comment = persistent_layer
         .find_comment(payload.comment_id)
         .await
         .map_err(|_| ServerError::forbidden_error())?;
 
-    // new comment text
-    comment.comment_text = payload.new_comment_text;
+    if comment.deleted {
+        return Err(ServerError::forbidden_error());
+    }
 
-    // put it back
+    // set the new comment text, leaving the fields maintained by the store untouched
     if (persistent_layer
-        .update_comment_mongo(payload.comment_id, comment)
+        .update_comment_text(payload.comment_id, payload.new_comment_text)
         .await)
         .is_err()
     {
@@ -207,8 +395,8 @@ pub struct DeleteCommentRequest {
 }
 
 #[instrument(level = "trace")]
-pub async fn delete_comment(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn delete_comment<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<DeleteCommentRequest>,
 ) -> Result<(), ServerError> {
     debug!(payload = ?payload);
@@ -219,9 +407,381 @@ pub async fn delete_comment(
         .await
         .map_err(|_| ServerError::forbidden_error())?;
 
+    if comment.deleted {
+        return Ok(());
+    }
+
+    // the resource decides whether the comment's replies go with it
+    let resource_settings = persistent_layer
+        .find_resource_settings(comment.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    let delete_result = match resource_settings.delete_mode {
+        DeleteMode::Tombstone => {
+            persistent_layer
+                .tombstone_comment(comment.comment_id, Utc::now())
+                .await
+        }
+        DeleteMode::Prune => {
+            persistent_layer
+                .prune_comments(comment.materialized_path)
+                .await
+        }
+    };
+
+    if delete_result.is_err() {
+        return Err(ServerError::internal_server_error());
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RestoreCommentRequest {
+    pub comment_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn restore_comment<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Extension(RestoreWindow(restore_window)): Extension<RestoreWindow>,
+    Json(payload): Json<RestoreCommentRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    // find the comment to be restored
+    let comment = persistent_layer
+        .find_comment(payload.comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    if !comment.deleted {
+        return Err(ServerError::bad_request_error("comment is not deleted"));
+    }
+
+    // tombstones deleted before they could be restored have no deleted_timestamp
+    match comment.deleted_timestamp {
+        Some(deleted_timestamp) if deleted_timestamp + restore_window > Utc::now() => {}
+        _ => return Err(ServerError::forbidden_error()),
+    }
+
+    // the purge job may have discarded the text and the author in the meantime
+    persistent_layer
+        .restore_comment(payload.comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PruneCommentRequest {
+    pub comment_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn prune_comment<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<PruneCommentRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    // find the comment to be pruned
+    let comment = persistent_layer
+        .find_comment(payload.comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
     // prune comments by the materialized path
     if (persistent_layer
-        .prune_comments_mongo(comment.materialized_path)
+        .prune_comments(comment.materialized_path)
+        .await)
+        .is_err()
+    {
+        return Err(ServerError::internal_server_error());
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MoveCommentRequest {
+    pub comment_id: Uuid,
+    /// The comment to move it below, or `None` to make it a root comment of `resource_id`.
+    pub new_parent_comment_id: Option<Uuid>,
+    pub resource_id: Option<Uuid>,
+}
+
+#[instrument(level = "trace")]
+pub async fn move_comment<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<MoveCommentRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    // find the comment to be moved
+    let comment = persistent_layer
+        .find_comment(payload.comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    let resource_id = match payload.resource_id {
+        Some(resource_id) => Some(
+            persistent_layer
+                .resolve_resource_id(resource_id)
+                .await
+                .map_err(|_| ServerError::internal_server_error())?,
+        ),
+        None => None,
+    };
+
+    let (new_parent_materialized_path, new_resource_id, new_depth) =
+        match (payload.new_parent_comment_id, resource_id) {
+            (Some(new_parent_comment_id), resource_id) => {
+                let new_parent_comment = persistent_layer
+                    .find_comment(new_parent_comment_id)
+                    .await
+                    .map_err(|_| ServerError::forbidden_error())?;
+
+                if resource_id
+                    .is_some_and(|resource_id| resource_id != new_parent_comment.resource_id)
+                {
+                    return Err(ServerError::bad_request_error(
+                        "new parent comment belongs to another resource",
+                    ));
+                }
+
+                // a comment can't end up among its own replies
+                if new_parent_comment.comment_id == comment.comment_id
+                    || new_parent_comment
+                        .ancestor_comment_ids
+                        .contains(&comment.comment_id)
+                {
+                    return Err(ServerError::bad_request_error(
+                        "cannot move a comment below itself",
+                    ));
+                }
+
+                // tombstones keep the replies they already have, but take no new ones
+                if new_parent_comment.deleted {
+                    return Err(ServerError::forbidden_error());
+                }
+
+                (
+                    new_parent_comment.materialized_path,
+                    new_parent_comment.resource_id,
+                    new_parent_comment.depth + 1,
+                )
+            }
+            (None, Some(resource_id)) => {
+                // a mistyped id would otherwise start a thread nobody looks at
+                if persistent_layer.find_resource(resource_id).await.is_err() {
+                    return Err(ServerError::bad_request_error(
+                        "destination resource not registered",
+                    ));
+                }
+
+                (resource_id.to_string(), resource_id, 1)
+            }
+            (None, None) => {
+                return Err(ServerError::bad_request_error(
+                    "new parent comment or resource required",
+                ))
+            }
+        };
+
+    let resource_settings = persistent_layer
+        .find_resource_settings(new_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if !resource_settings.comments_open {
+        return Err(ServerError::forbidden_error());
+    }
+
+    // the deepest reply of the moved comment has to fit below the new parent as well
+    if let Some(max_depth) = resource_settings.max_depth {
+        let descendant_depth = persistent_layer
+            .find_all_comments(comment.materialized_path.clone(), CommentSort::Oldest, None)
+            .await
+            .map_err(|_| ServerError::internal_server_error())?
+            .iter()
+            .map(|descendant| descendant.depth - comment.depth)
+            .max()
+            .unwrap_or(0);
+
+        if new_depth + descendant_depth > max_depth {
+            return Err(ServerError::bad_request_error(
+                "maximum threading depth reached",
+            ));
+        }
+    }
+
+    // move comments by the materialized path
+    if (persistent_layer
+        .move_comments(comment.materialized_path, new_parent_materialized_path)
+        .await)
+        .is_err()
+    {
+        return Err(ServerError::internal_server_error());
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GetPendingCommentsRequest {
+    pub resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn get_pending_comments<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<GetPendingCommentsRequest>,
+) -> Result<String, ServerError> {
+    debug!(payload = ?payload);
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    let resource_aliases = persistent_layer
+        .find_resource_aliases(resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    // comments held before their resource was merged into this one are still held under the
+    // id it had then
+    let mut pending_comments = vec![];
+    for held_resource_id in std::iter::once(resource_id).chain(
+        resource_aliases
+            .iter()
+            .map(|resource_alias| resource_alias.alias_resource_id),
+    ) {
+        pending_comments.extend(
+            persistent_layer
+                .find_pending_comments(held_resource_id)
+                .await
+                .map_err(|_| ServerError::internal_server_error())?,
+        );
+    }
+    pending_comments.sort_by_key(|pending_comment| {
+        (
+            pending_comment.commented_timestamp,
+            pending_comment.comment_id,
+        )
+    });
+
+    Ok(json!({
+        "resource_id": resource_id,
+        "pending_comments": pending_comments,
+    })
+    .to_string())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ApproveCommentRequest {
+    pub comment_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn approve_comment<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<ApproveCommentRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    let pending_comment = persistent_layer
+        .find_pending_comment(payload.comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    // an approval that failed after publishing the comment is finished by approving it again
+    if persistent_layer
+        .find_comment(payload.comment_id)
+        .await
+        .is_err()
+    {
+        let comment = match pending_comment.parent_comment_id {
+            Some(parent_comment_id) => {
+                // the comment it replies to may have been deleted or moved in the meantime
+                let branched_from_comment = persistent_layer
+                    .find_comment(parent_comment_id)
+                    .await
+                    .map_err(|_| ServerError::forbidden_error())?;
+
+                if branched_from_comment.deleted {
+                    return Err(ServerError::forbidden_error());
+                }
+
+                new_comment(
+                    PendingComment {
+                        resource_id: branched_from_comment.resource_id,
+                        ..pending_comment
+                    },
+                    Some(&branched_from_comment),
+                )
+            }
+            None => {
+                // its resource may have been merged into another one in the meantime
+                let resource_id = persistent_layer
+                    .resolve_resource_id(pending_comment.resource_id)
+                    .await
+                    .map_err(|_| ServerError::internal_server_error())?;
+
+                new_comment(
+                    PendingComment {
+                        resource_id,
+                        ..pending_comment
+                    },
+                    None,
+                )
+            }
+        };
+
+        if (persistent_layer.insert_comment(comment).await).is_err() {
+            return Err(ServerError::internal_server_error());
+        }
+    }
+
+    if (persistent_layer
+        .delete_pending_comment(payload.comment_id)
+        .await)
+        .is_err()
+    {
+        return Err(ServerError::internal_server_error());
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RejectCommentRequest {
+    pub comment_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn reject_comment<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<RejectCommentRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    // only held comments can be rejected, published ones are deleted instead
+    persistent_layer
+        .find_pending_comment(payload.comment_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    if (persistent_layer
+        .delete_pending_comment(payload.comment_id)
         .await)
         .is_err()
     {
@@ -231,40 +791,110 @@ pub async fn delete_comment(
     Ok(())
 }
 
+// parses the cursor a client got with the previous page of a listing. a cursor only points into
+// a listing in the order it was handed out for
+fn parse_cursor(
+    cursor: Option<&str>,
+    sort: CommentSort,
+) -> Result<Option<CommentCursor>, ServerError> {
+    cursor
+        .map(str::parse::<CommentCursor>)
+        .transpose()
+        .ok()
+        .filter(|after| match after {
+            Some(after) => after.sort == sort && after.score.is_some() == sort.has_score(),
+            None => true,
+        })
+        .ok_or_else(|| ServerError::bad_request_error("invalid cursor"))
+}
+
+// pages are fetched with one comment more than the limit, which tells whether more follow and
+// is dropped again. returns the page, whether more follow and the cursor to fetch them with
+fn into_page(
+    mut comments: Vec<Comment>,
+    sort: CommentSort,
+    limit: Option<u32>,
+) -> (Vec<Comment>, bool, Option<String>) {
+    let has_more = match limit {
+        Some(limit) if comments.len() > limit as usize => {
+            comments.truncate(limit as usize);
+            true
+        }
+        _ => false,
+    };
+
+    let next_cursor = comments
+        .last()
+        .filter(|_| has_more)
+        .map(|comment| CommentCursor::of(comment, sort).encode());
+
+    (comments, has_more, next_cursor)
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct GetRootCommentsRequest {
     pub resource_id: Uuid,
+    #[serde(default)]
+    pub sort: CommentSort,
     pub limit: Option<u32>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[instrument(level = "trace")]
-pub async fn get_root_comments(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn get_root_comments<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<GetRootCommentsRequest>,
 ) -> Result<String, ServerError> {
     debug!(payload = ?payload);
 
+    let after = parse_cursor(payload.cursor.as_deref(), payload.sort)?;
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
     let root_comments = persistent_layer
-        .find_next_level_comments(payload.resource_id.to_string(), payload.limit)
+        .find_next_level_comments(
+            resource_id.to_string(),
+            payload.sort,
+            payload.limit.map(|limit| limit.saturating_add(1)),
+            after,
+        )
         .await
         .map_err(|_| ServerError::internal_server_error())?;
 
-    Ok(json!({ "root_comments": root_comments }).to_string())
+    let (root_comments, has_more, next_cursor) =
+        into_page(root_comments, payload.sort, payload.limit);
+
+    Ok(json!({
+        "root_comments": root_comments,
+        "has_more": has_more,
+        "next_cursor": next_cursor,
+    })
+    .to_string())
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct GetBranchCommentsNextRequest {
     pub branched_from: Uuid,
+    #[serde(default)]
+    pub sort: CommentSort,
     pub limit: Option<u32>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[instrument(level = "trace")]
-pub async fn get_branch_comments_next(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn get_branch_comments_next<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<GetBranchCommentsNextRequest>,
 ) -> Result<String, ServerError> {
     debug!(payload = ?payload);
 
+    let after = parse_cursor(payload.cursor.as_deref(), payload.sort)?;
+
     // find its root comment
     let root_comment = persistent_layer
         .find_comment(payload.branched_from)
@@ -273,21 +903,38 @@ pub async fn get_branch_comments_next(
 
     // find branch comments by materialized path
     let branch_comments = persistent_layer
-        .find_next_level_comments(root_comment.materialized_path, payload.limit)
+        .find_next_level_comments(
+            root_comment.materialized_path,
+            payload.sort,
+            payload.limit.map(|limit| limit.saturating_add(1)),
+            after,
+        )
         .await
         .map_err(|_| ServerError::internal_server_error())?;
 
-    Ok(json!({ "branch_comments": branch_comments }).to_string())
+    let (branch_comments, has_more, next_cursor) =
+        into_page(branch_comments, payload.sort, payload.limit);
+
+    Ok(json!({
+        "branch_comments": branch_comments,
+        "has_more": has_more,
+        "next_cursor": next_cursor,
+    })
+    .to_string())
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct GetRestBranchCommentsRequest {
     pub branched_from: Uuid,
+    #[serde(default)]
+    pub sort: CommentSort,
+    #[serde(default)]
+    pub format: ListingFormat,
 }
 
 #[instrument(level = "trace")]
-pub async fn get_branch_comments_rest(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn get_branch_comments_rest<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<GetRestBranchCommentsRequest>,
 ) -> Result<String, ServerError> {
     debug!(payload = ?payload);
@@ -300,29 +947,608 @@ pub async fn get_branch_comments_rest(
 
     // find branch comments by materialized path
     let branch_comments = persistent_layer
-        .find_all_comments(branched_from_comment.materialized_path)
+        .find_all_comments(branched_from_comment.materialized_path, payload.sort, None)
         .await
         .map_err(|_| ServerError::internal_server_error())?;
 
-    Ok(json!({ "branch_comments": branch_comments }).to_string())
+    Ok(json!({ "branch_comments": format_listing(branch_comments, payload.format) }).to_string())
+}
+
+// nests every comment in the `children` of the comment it replies to, keeping the order the
+// comments come in among siblings. comments whose parent isn't among them become the top nodes.
+// comments come shallowest first, so walking them backwards assembles every node's replies
+// before the node itself, without recursing as deep as the thread goes
+fn into_tree(comments: Vec<Comment>) -> Vec<CommentNode> {
+    let comment_ids: HashSet<Uuid> = comments.iter().map(|comment| comment.comment_id).collect();
+
+    let mut children: HashMap<Uuid, Vec<CommentNode>> = HashMap::new();
+    let mut top_nodes: Vec<CommentNode> = Vec::new();
+
+    for comment in comments.into_iter().rev() {
+        let mut node_children = children.remove(&comment.comment_id).unwrap_or_default();
+        node_children.reverse();
+
+        let parent_comment_id = comment
+            .parent_comment_id
+            .filter(|parent_comment_id| comment_ids.contains(parent_comment_id));
+        let node = CommentNode {
+            comment,
+            children: node_children,
+        };
+
+        match parent_comment_id {
+            Some(parent_comment_id) => children.entry(parent_comment_id).or_default().push(node),
+            None => top_nodes.push(node),
+        }
+    }
+
+    top_nodes.reverse();
+    top_nodes
+}
+
+// lays out the comments of a thread in the requested format
+fn format_listing(comments: Vec<Comment>, format: ListingFormat) -> serde_json::Value {
+    match format {
+        ListingFormat::Flat => json!(comments),
+        ListingFormat::Tree => json!(into_tree(comments)),
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GetBranchCommentsLevelsRequest {
+    pub branched_from: Uuid,
+    /// How many levels of replies below the comment to fetch, at most
+    /// [`MAX_BRANCH_COMMENTS_LEVELS`].
+    pub depth: u32,
+    #[serde(default)]
+    pub sort: CommentSort,
+    /// The most replies to fetch per comment, at most [`MAX_BRANCH_COMMENTS_LEVELS_LIMIT`] and
+    /// [`DEFAULT_BRANCH_COMMENTS_LEVELS_LIMIT`] if not given.
+    pub limit: Option<u32>,
+}
+
+#[instrument(level = "trace")]
+pub async fn get_branch_comments_levels<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<GetBranchCommentsLevelsRequest>,
+) -> Result<String, ServerError> {
+    debug!(payload = ?payload);
+
+    let branched_from_comment = persistent_layer
+        .find_comment(payload.branched_from)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    // every level is fetched in one go, at most `limit` replies per comment. the counters tell
+    // how many were left out, by the limit or below the last level
+    let depth = payload.depth.min(MAX_BRANCH_COMMENTS_LEVELS);
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_BRANCH_COMMENTS_LEVELS_LIMIT)
+        .min(MAX_BRANCH_COMMENTS_LEVELS_LIMIT);
+    let mut branch_comments: Vec<Comment> = Vec::new();
+    let mut more_replies: Vec<MoreReplies> = Vec::new();
+    let mut level = vec![branched_from_comment];
+
+    for level_depth in 0..=depth {
+        let parent_comment_ids: Vec<Uuid> = level
+            .iter()
+            .filter(|comment| level_depth < depth && comment.direct_reply_count > 0)
+            .map(|comment| comment.comment_id)
+            .collect();
+
+        let mut replies = if parent_comment_ids.is_empty() {
+            HashMap::new()
+        } else {
+            persistent_layer
+                .find_replies_of_comments(parent_comment_ids, payload.sort, limit)
+                .await
+                .map_err(|_| ServerError::internal_server_error())?
+        };
+
+        let mut next_level: Vec<Comment> = Vec::new();
+
+        for comment in &level {
+            let replies = replies.remove(&comment.comment_id).unwrap_or_default();
+
+            let hidden_reply_count = comment
+                .direct_reply_count
+                .saturating_sub(replies.len() as u32);
+            if hidden_reply_count > 0 {
+                more_replies.push(MoreReplies {
+                    parent_comment_id: comment.comment_id,
+                    hidden_reply_count,
+                    cursor: replies
+                        .last()
+                        .map(|reply| CommentCursor::of(reply, payload.sort).encode()),
+                });
+            }
+
+            next_level.extend(replies);
+        }
+
+        branch_comments.append(&mut level);
+        level = next_level;
+    }
+
+    Ok(json!({
+        "branch_comments": branch_comments,
+        "more_replies": more_replies,
+    })
+    .to_string())
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct GetAllCommentsRequest {
     pub resource_id: Uuid,
+    #[serde(default)]
+    pub sort: CommentSort,
+    #[serde(default)]
+    pub format: ListingFormat,
 }
 
 #[instrument(level = "trace")]
-pub async fn get_all_comments(
-    Extension(persistent_layer): Extension<Arc<PersistentLayer>>,
+pub async fn get_all_comments<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
     Json(payload): Json<GetAllCommentsRequest>,
 ) -> Result<String, ServerError> {
     debug!(payload = ?payload);
 
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
     let all_comments = persistent_layer
-        .find_all_comments(payload.resource_id.to_string())
+        .find_all_comments(resource_id.to_string(), payload.sort, None)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(json!({ "comments": format_listing(all_comments, payload.format) }).to_string())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RebuildBranchCommentIdsRequest {
+    pub resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn rebuild_branch_comment_ids<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<RebuildBranchCommentIdsRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    persistent_layer
+        .rebuild_branch_comment_ids(resource_id.to_string())
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+// tells a field that was left out, and stays as it is, from one explicitly set to null
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CreateResourceRequest {
+    pub resource_id: Uuid,
+    pub title: String,
+    pub canonical_url: Option<String>,
+    pub owner_account_id: Option<Uuid>,
+    #[serde(default)]
+    pub settings: ResourceSettings,
+}
+
+#[instrument(level = "trace")]
+pub async fn create_resource<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<CreateResourceRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    if persistent_layer
+        .find_resource(payload.resource_id)
+        .await
+        .is_ok()
+    {
+        return Err(ServerError::bad_request_error(
+            "resource already registered",
+        ));
+    }
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    // the registration would never be read, the alias is resolved first
+    if resource_id != payload.resource_id {
+        return Err(ServerError::bad_request_error("resource id is an alias"));
+    }
+
+    let resource = Resource {
+        resource_id: payload.resource_id,
+        title: payload.title,
+        canonical_url: payload.canonical_url,
+        owner_account_id: payload.owner_account_id,
+        created_timestamp: Utc::now(),
+        settings: payload.settings,
+    };
+
+    persistent_layer
+        .insert_resource(resource)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GetResourceRequest {
+    pub resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn get_resource<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<GetResourceRequest>,
+) -> Result<String, ServerError> {
+    debug!(payload = ?payload);
+
+    let resource = persistent_layer
+        .find_resource(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    Ok(json!({ "resource": resource }).to_string())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UpdateResourceRequest {
+    pub resource_id: Uuid,
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub canonical_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub owner_account_id: Option<Option<Uuid>>,
+}
+
+#[instrument(level = "trace")]
+pub async fn update_resource<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<UpdateResourceRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    let mut resource = persistent_layer
+        .find_resource(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    if let Some(title) = payload.title {
+        resource.title = title;
+    }
+    if let Some(canonical_url) = payload.canonical_url {
+        resource.canonical_url = canonical_url;
+    }
+    if let Some(owner_account_id) = payload.owner_account_id {
+        resource.owner_account_id = owner_account_id;
+    }
+
+    persistent_layer
+        .update_resource(resource)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GetResourceSettingsRequest {
+    pub resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn get_resource_settings<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<GetResourceSettingsRequest>,
+) -> Result<String, ServerError> {
+    debug!(payload = ?payload);
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    let resource_settings = persistent_layer
+        .find_resource_settings(resource_id)
         .await
         .map_err(|_| ServerError::internal_server_error())?;
 
-    Ok(json!({ "comments": all_comments }).to_string())
+    Ok(json!({ "settings": resource_settings }).to_string())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UpdateResourceSettingsRequest {
+    pub resource_id: Uuid,
+    pub delete_mode: Option<DeleteMode>,
+    pub comments_open: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub max_depth: Option<Option<u32>>,
+    pub moderation_mode: Option<ModerationMode>,
 }
+
+#[instrument(level = "trace")]
+pub async fn update_resource_settings<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<UpdateResourceSettingsRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    // only registered resources have settings of their own
+    let mut resource = persistent_layer
+        .find_resource(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::forbidden_error())?;
+
+    if let Some(delete_mode) = payload.delete_mode {
+        resource.settings.delete_mode = delete_mode;
+    }
+    if let Some(comments_open) = payload.comments_open {
+        resource.settings.comments_open = comments_open;
+    }
+    if let Some(max_depth) = payload.max_depth {
+        resource.settings.max_depth = max_depth;
+    }
+    if let Some(moderation_mode) = payload.moderation_mode {
+        resource.settings.moderation_mode = moderation_mode;
+    }
+
+    persistent_layer
+        .update_resource(resource)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MergeResourcesRequest {
+    pub source_resource_id: Uuid,
+    pub target_resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn merge_resources<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<MergeResourcesRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    let source_resource_id = persistent_layer
+        .resolve_resource_id(payload.source_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    // an alias has no comments of its own left to merge
+    if source_resource_id != payload.source_resource_id {
+        return Err(ServerError::bad_request_error(
+            "source resource is an alias",
+        ));
+    }
+
+    // merging into an alias merges into the resource it stands for
+    let target_resource_id = persistent_layer
+        .resolve_resource_id(payload.target_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if target_resource_id == source_resource_id {
+        return Err(ServerError::bad_request_error(
+            "cannot merge a resource into itself",
+        ));
+    }
+
+    persistent_layer
+        .merge_resources(ResourceAlias {
+            alias_resource_id: source_resource_id,
+            resource_id: target_resource_id,
+            created_timestamp: Utc::now(),
+        })
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExportResourceCommentsRequest {
+    pub resource_id: Uuid,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[instrument(level = "trace")]
+pub async fn export_resource_comments<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<ExportResourceCommentsRequest>,
+) -> Result<Response, ServerError> {
+    debug!(payload = ?payload);
+
+    let exported_thread = export_thread(persistent_layer, payload.resource_id, payload.format)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok((
+        [(header::CONTENT_TYPE, payload.format.content_type())],
+        StreamBody::new(exported_thread),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CreateResourceAliasRequest {
+    pub alias_resource_id: Uuid,
+    pub resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn create_resource_alias<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<CreateResourceAliasRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    let alias_resource_id = persistent_layer
+        .resolve_resource_id(payload.alias_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if alias_resource_id != payload.alias_resource_id {
+        return Err(ServerError::bad_request_error(
+            "resource alias already registered",
+        ));
+    }
+
+    // aliasing an alias aliases the resource it stands for
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if resource_id == alias_resource_id {
+        return Err(ServerError::bad_request_error(
+            "cannot make a resource an alias of itself",
+        ));
+    }
+
+    // whatever is already under the id would be hidden by the alias, merging moves it over
+    if persistent_layer
+        .find_resource(alias_resource_id)
+        .await
+        .is_ok()
+    {
+        return Err(ServerError::bad_request_error(
+            "resource is registered, merge it instead",
+        ));
+    }
+
+    let root_comments = persistent_layer
+        .find_next_level_comments(
+            alias_resource_id.to_string(),
+            CommentSort::default(),
+            Some(1),
+            None,
+        )
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if !root_comments.is_empty() {
+        return Err(ServerError::bad_request_error(
+            "resource has comments, merge it instead",
+        ));
+    }
+
+    let resource_aliases = persistent_layer
+        .find_resource_aliases(alias_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if !resource_aliases.is_empty() {
+        return Err(ServerError::bad_request_error(
+            "resource has aliases, merge it instead",
+        ));
+    }
+
+    persistent_layer
+        .insert_resource_alias(ResourceAlias {
+            alias_resource_id,
+            resource_id,
+            created_timestamp: Utc::now(),
+        })
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DeleteResourceAliasRequest {
+    pub alias_resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn delete_resource_alias<S: CommentStore + ?Sized>(
+    _: Admin,
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<DeleteResourceAliasRequest>,
+) -> Result<(), ServerError> {
+    debug!(payload = ?payload);
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.alias_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    if resource_id == payload.alias_resource_id {
+        return Err(ServerError::forbidden_error());
+    }
+
+    persistent_layer
+        .delete_resource_alias(payload.alias_resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GetResourceAliasesRequest {
+    pub resource_id: Uuid,
+}
+
+#[instrument(level = "trace")]
+pub async fn get_resource_aliases<S: CommentStore + ?Sized>(
+    Extension(persistent_layer): Extension<Arc<S>>,
+    Json(payload): Json<GetResourceAliasesRequest>,
+) -> Result<String, ServerError> {
+    debug!(payload = ?payload);
+
+    let resource_id = persistent_layer
+        .resolve_resource_id(payload.resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    let resource_aliases = persistent_layer
+        .find_resource_aliases(resource_id)
+        .await
+        .map_err(|_| ServerError::internal_server_error())?;
+
+    Ok(json!({
+        "resource_id": resource_id,
+        "resource_aliases": resource_aliases,
+    })
+    .to_string())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1626 @@
+use axum::{body::HttpBody, http::StatusCode, Extension, Json};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::*;
+use crate::{
+    models::DELETED_COMMENT_TEXT,
+    persistent::{
+        init_mongo_connection, init_postgres_connection, init_sqlite_connection,
+        InMemoryCommentStore, MongoCommentStore, MongoDbConfig, PostgresCommentStore,
+        SqliteCommentStore,
+    },
+    service::purge::purge_expired_comments,
+};
+
+fn new_store() -> Arc<InMemoryCommentStore> {
+    Arc::new(InMemoryCommentStore::new())
+}
+
+// a store on a schema of its own, so that purging tombstones doesn't reach into other tests
+async fn new_postgres_store() -> Arc<PostgresCommentStore> {
+    let connection_string = std::env::var("POSTGRES_TEST_URL").unwrap();
+    let schema_name = format!("commenter_test_{}", Uuid::new_v4().simple());
+
+    let pool = init_postgres_connection(&connection_string, None)
+        .await
+        .unwrap();
+    sqlx::query(&format!("CREATE SCHEMA {}", schema_name))
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool.close().await;
+
+    let separator = if connection_string.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+    let pool = init_postgres_connection(
+        &format!(
+            "{}{}options=-c%20search_path%3D{}",
+            connection_string, separator, schema_name
+        ),
+        None,
+    )
+    .await
+    .unwrap();
+    let store = Arc::new(PostgresCommentStore { pool });
+    store.migrate().await.unwrap();
+
+    store
+}
+
+async fn drop_postgres_store(store: &PostgresCommentStore) {
+    let (schema_name,): (String,) = sqlx::query_as("SELECT current_schema()")
+        .fetch_one(&store.pool)
+        .await
+        .unwrap();
+    sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema_name))
+        .execute(&store.pool)
+        .await
+        .unwrap();
+    store.pool.close().await;
+}
+
+// a store on a database of its own, which transactions need to be a replica set
+async fn new_mongo_store() -> Arc<MongoCommentStore> {
+    let connection_string = std::env::var("MONGODB_TEST_URL").unwrap();
+    let mongo_client = init_mongo_connection(&connection_string, None)
+        .await
+        .unwrap();
+
+    let store = Arc::new(MongoCommentStore {
+        mongo_client,
+        mongo_config: MongoDbConfig {
+            mongo_db_name: format!("commenter_test_{}", Uuid::new_v4().simple()),
+        },
+    });
+    store.ensure_indexes().await.unwrap();
+    store.migrate().await.unwrap();
+
+    store
+}
+
+async fn drop_mongo_store(store: &MongoCommentStore) {
+    store
+        .mongo_client
+        .database(&store.mongo_config.mongo_db_name)
+        .drop(None)
+        .await
+        .unwrap();
+}
+
+// builds a request body the way the router deserializes it
+fn request<T: DeserializeOwned>(payload: Value) -> Json<T> {
+    Json(serde_json::from_value(payload).unwrap())
+}
+
+fn parse(response: Result<String, ServerError>) -> Value {
+    serde_json::from_str(&response.unwrap()).unwrap()
+}
+
+fn comment_ids(comments: &[Comment]) -> Vec<Uuid> {
+    comments.iter().map(|comment| comment.comment_id).collect()
+}
+
+async fn create_root<S: CommentStore>(store: &Arc<S>, resource_id: Uuid, text: &str) -> Uuid {
+    let response = create_root_comment(
+        Extension(store.clone()),
+        request(json!({
+            "resource_id": resource_id,
+            "commenter_account_id": Uuid::new_v4(),
+            "commenter_username": "commenter",
+            "comment_text": text,
+        })),
+    )
+    .await;
+
+    serde_json::from_value(parse(response)["comment_id"].clone()).unwrap()
+}
+
+async fn create_branch<S: CommentStore>(store: &Arc<S>, branched_from: Uuid, text: &str) -> Uuid {
+    let response = create_branch_comment(
+        Extension(store.clone()),
+        request(json!({
+            "branched_from": branched_from,
+            "commenter_account_id": Uuid::new_v4(),
+            "commenter_username": "commenter",
+            "comment_text": text,
+        })),
+    )
+    .await;
+
+    serde_json::from_value(parse(response)["comment_id"].clone()).unwrap()
+}
+
+// every comment of the resource, shallowest and then oldest first
+async fn find_comments<S: CommentStore>(store: &Arc<S>, resource_id: Uuid) -> Vec<Comment> {
+    let response = get_all_comments(
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id, "sort": "oldest" })),
+    )
+    .await;
+
+    serde_json::from_value(parse(response)["comments"].clone()).unwrap()
+}
+
+#[tokio::test]
+async fn comments_are_kept_in_memory() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+
+    let comments = find_comments(&store, resource_id).await;
+    assert_eq!(comment_ids(&comments), [root_comment_id, branch_comment_id]);
+    assert_eq!(
+        comments[1].materialized_path,
+        format!(
+            "{}->{}->{}",
+            resource_id, root_comment_id, branch_comment_id
+        )
+    );
+    assert_eq!(comments[1].comment_text, "branch");
+    assert!(find_comments(&store, Uuid::new_v4()).await.is_empty());
+}
+
+// builds a thread, reads it back through the listings and prunes part of it, which every store
+// has to answer alike
+async fn exercise_comment_tree<S: CommentStore>(store: Arc<S>) {
+    let resource_id = Uuid::new_v4();
+
+    let first_root_comment_id = create_root(&store, resource_id, "first").await;
+    let second_root_comment_id = create_root(&store, resource_id, "second").await;
+    let branch_comment_id = create_branch(&store, first_root_comment_id, "branch").await;
+    let nested_comment_id = create_branch(&store, branch_comment_id, "nested").await;
+
+    assert_eq!(
+        comment_ids(&find_comments(&store, resource_id).await),
+        [
+            first_root_comment_id,
+            second_root_comment_id,
+            branch_comment_id,
+            nested_comment_id
+        ]
+    );
+
+    let response = get_root_comments(
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id })),
+    )
+    .await;
+    let root_comments: Vec<Comment> =
+        serde_json::from_value(parse(response)["root_comments"].clone()).unwrap();
+    assert_eq!(
+        comment_ids(&root_comments),
+        [second_root_comment_id, first_root_comment_id]
+    );
+
+    let response = get_branch_comments_rest(
+        Extension(store.clone()),
+        request(json!({ "branched_from": first_root_comment_id, "sort": "oldest" })),
+    )
+    .await;
+    let branch_comments: Vec<Comment> =
+        serde_json::from_value(parse(response)["branch_comments"].clone()).unwrap();
+    assert_eq!(
+        comment_ids(&branch_comments),
+        [first_root_comment_id, branch_comment_id, nested_comment_id]
+    );
+    assert_eq!(branch_comments[2].depth, 3);
+    assert_eq!(
+        branch_comments[2].ancestor_comment_ids,
+        [first_root_comment_id, branch_comment_id]
+    );
+
+    prune_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "comment_id": branch_comment_id })),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        comment_ids(&find_comments(&store, resource_id).await),
+        [first_root_comment_id, second_root_comment_id]
+    );
+    let first_root_comment = store.find_comment(first_root_comment_id).await.unwrap();
+    assert!(first_root_comment.branch_comment_ids.is_empty());
+    assert_eq!(first_root_comment.total_descendant_count, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database at POSTGRES_TEST_URL"]
+async fn postgres_store_keeps_the_comment_tree() {
+    let store = new_postgres_store().await;
+
+    exercise_comment_tree(store.clone()).await;
+
+    drop_postgres_store(&store).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_keeps_the_comment_tree() {
+    let database_path = std::env::temp_dir().join(format!("commenter-{}.db", Uuid::new_v4()));
+    let pool = init_sqlite_connection(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let store = Arc::new(SqliteCommentStore { pool });
+    store.migrate().await.unwrap();
+
+    exercise_comment_tree(store.clone()).await;
+
+    store.pool.close().await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", database_path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_keeps_the_comment_tree() {
+    let store = new_mongo_store().await;
+
+    exercise_comment_tree(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+#[tokio::test]
+async fn rebuild_recovers_branch_comment_ids() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let first_branch_comment_id = create_branch(&store, root_comment_id, "first").await;
+    let second_branch_comment_id = create_branch(&store, root_comment_id, "second").await;
+
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert_eq!(
+        root_comment.branch_comment_ids,
+        [first_branch_comment_id, second_branch_comment_id]
+    );
+
+    // a comment written before the ids were maintained, pointing to a reply that never existed
+    let stale_comment_id = Uuid::new_v4();
+    store
+        .insert_comment(Comment {
+            comment_id: stale_comment_id,
+            materialized_path: format!("{}->{}", resource_id, stale_comment_id),
+            branch_comment_ids: vec![Uuid::new_v4()],
+            ..root_comment
+        })
+        .await
+        .unwrap();
+
+    rebuild_branch_comment_ids(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id })),
+    )
+    .await
+    .unwrap();
+
+    assert!(store
+        .find_comment(stale_comment_id)
+        .await
+        .unwrap()
+        .branch_comment_ids
+        .is_empty());
+    assert_eq!(
+        store
+            .find_comment(root_comment_id)
+            .await
+            .unwrap()
+            .branch_comment_ids,
+        [first_branch_comment_id, second_branch_comment_id]
+    );
+}
+
+async fn react<S: CommentStore>(store: &Arc<S>, comment_id: Uuid, reactor: Uuid, emoji: &str) {
+    react_to_comment(
+        Extension(store.clone()),
+        request(json!({
+            "reactor_account_id": reactor,
+            "reactor_username": "reactor",
+            "emoji_unicode": emoji,
+            "reacted_comment_id": comment_id,
+        })),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn counters_follow_replies_and_reactions() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+    create_branch(&store, branch_comment_id, "nested").await;
+
+    let reactor = Uuid::new_v4();
+    react(&store, root_comment_id, reactor, "1f44d").await;
+    // reacting again with the same emoji doesn't count twice
+    react(&store, root_comment_id, reactor, "1f44d").await;
+    react(&store, root_comment_id, Uuid::new_v4(), "1f44d").await;
+    react(&store, root_comment_id, reactor, "2764-fe0f").await;
+
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert_eq!(root_comment.direct_reply_count, 1);
+    assert_eq!(root_comment.total_descendant_count, 2);
+    assert_eq!(
+        root_comment.reaction_counts,
+        BTreeMap::from([("1f44d".to_string(), 2), ("2764-fe0f".to_string(), 1)])
+    );
+
+    undo_react_to_comment(
+        Extension(store.clone()),
+        request(json!({
+            "reactor_account_id": reactor,
+            "reactor_username": "reactor",
+            "emoji_unicode": "2764-fe0f",
+            "reacted_comment_id": root_comment_id,
+        })),
+    )
+    .await
+    .unwrap();
+
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert_eq!(
+        root_comment.reaction_counts,
+        BTreeMap::from([("1f44d".to_string(), 2)])
+    );
+
+    let response = react_to_comment(
+        Extension(store.clone()),
+        request(json!({
+            "reactor_account_id": reactor,
+            "reactor_username": "reactor",
+            "emoji_unicode": "thumbs up",
+            "reacted_comment_id": root_comment_id,
+        })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn reactions_are_listed_apart_from_the_comment() {
+    let store = new_store();
+    let comment_id = create_root(&store, Uuid::new_v4(), "root").await;
+
+    let first_reactor = Uuid::new_v4();
+    let second_reactor = Uuid::new_v4();
+    react(&store, comment_id, first_reactor, "1f44d").await;
+    react(&store, comment_id, second_reactor, "1f44e").await;
+
+    let response = get_reactions(
+        Extension(store.clone()),
+        request(json!({ "reacted_comment_id": comment_id })),
+    )
+    .await;
+    let reactions: Vec<CommentReaction> =
+        serde_json::from_value(parse(response)["reactions"].clone()).unwrap();
+    assert_eq!(
+        reactions
+            .iter()
+            .map(|reaction| (reaction.reactor.account_id, &*reaction.emoji_unified_code))
+            .collect::<Vec<_>>(),
+        [(first_reactor, "1f44d"), (second_reactor, "1f44e")]
+    );
+
+    let response = get_reactions(
+        Extension(store.clone()),
+        request(json!({ "reacted_comment_id": comment_id, "limit": 1 })),
+    )
+    .await;
+    assert_eq!(parse(response)["reactions"].as_array().unwrap().len(), 1);
+
+    let response = get_reactions(
+        Extension(store.clone()),
+        request(json!({ "reacted_comment_id": Uuid::new_v4() })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn replies_to_pruned_comments_leave_nothing_behind() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+
+    prune_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "comment_id": root_comment_id })),
+    )
+    .await
+    .unwrap();
+
+    let response = create_branch_comment(
+        Extension(store.clone()),
+        request(json!({
+            "branched_from": root_comment_id,
+            "commenter_account_id": Uuid::new_v4(),
+            "commenter_username": "commenter",
+            "comment_text": "too late",
+        })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+
+    // the parent may also go between the handler finding it and the reply being written
+    let branch_comment_id = Uuid::new_v4();
+    let insert_result = store
+        .insert_comment(Comment {
+            comment_id: branch_comment_id,
+            comment_type: CommentType::Branch,
+            materialized_path: format!("{}->{}", root_comment.materialized_path, branch_comment_id),
+            parent_comment_id: Some(root_comment_id),
+            depth: 2,
+            ancestor_comment_ids: vec![root_comment_id],
+            ..root_comment
+        })
+        .await;
+    assert!(insert_result.is_err());
+    assert!(store.find_comment(branch_comment_id).await.is_err());
+    assert!(find_comments(&store, resource_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn deleted_comments_become_tombstones() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+
+    delete_comment(
+        Extension(store.clone()),
+        request(json!({ "comment_id": root_comment_id })),
+    )
+    .await
+    .unwrap();
+
+    let comments = find_comments(&store, resource_id).await;
+    assert_eq!(comment_ids(&comments), [root_comment_id, branch_comment_id]);
+    assert!(comments[0].deleted);
+    assert_eq!(comments[0].comment_text, DELETED_COMMENT_TEXT);
+    assert_eq!(comments[0].branch_comment_ids, [branch_comment_id]);
+    assert!(!comments[1].deleted);
+
+    // tombstones can't be edited or reacted to
+    let response = update_comment_text(
+        Extension(store.clone()),
+        request(json!({ "comment_id": root_comment_id, "new_comment_text": "back" })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+
+    let response = react_to_comment(
+        Extension(store.clone()),
+        request(json!({
+            "reactor_account_id": Uuid::new_v4(),
+            "reactor_username": "reactor",
+            "emoji_unicode": "1f44d",
+            "reacted_comment_id": root_comment_id,
+        })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+}
+
+async fn delete<S: CommentStore>(store: &Arc<S>, comment_id: Uuid) {
+    delete_comment(
+        Extension(store.clone()),
+        request(json!({ "comment_id": comment_id })),
+    )
+    .await
+    .unwrap();
+}
+
+async fn restore<S: CommentStore>(
+    store: &Arc<S>,
+    restore_window: chrono::Duration,
+    comment_id: Uuid,
+) -> Result<(), ServerError> {
+    restore_comment(
+        Extension(store.clone()),
+        Extension(RestoreWindow(restore_window)),
+        request(json!({ "comment_id": comment_id })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn tombstones_are_restored_within_the_window() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+    let restore_window = chrono::Duration::days(1);
+
+    let comment_id = create_root(&store, resource_id, "root").await;
+
+    let response = restore(&store, restore_window, comment_id).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    delete(&store, comment_id).await;
+    restore(&store, restore_window, comment_id).await.unwrap();
+
+    let comment = store.find_comment(comment_id).await.unwrap();
+    assert!(!comment.deleted);
+    assert_eq!(comment.deleted_timestamp, None);
+    assert_eq!(comment.comment_text, "root");
+
+    // once the window has passed, the purge discards the tombstone for good
+    delete(&store, comment_id).await;
+    let response = restore(&store, chrono::Duration::zero(), comment_id).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+
+    let purged_count =
+        purge_expired_comments(store.as_ref(), RestoreWindow(chrono::Duration::zero()))
+            .await
+            .unwrap();
+    assert_eq!(purged_count, 1);
+    assert!(find_comments(&store, resource_id).await.is_empty());
+}
+
+async fn purge<S: CommentStore>(store: &Arc<S>) -> usize {
+    purge_expired_comments(store.as_ref(), RestoreWindow(chrono::Duration::zero()))
+        .await
+        .unwrap()
+}
+
+// purges tombstones with and without replies, and ones nothing was kept for, which every store
+// has to answer alike
+async fn exercise_purge<S: CommentStore>(store: Arc<S>) {
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+    let nested_comment_id = create_branch(&store, branch_comment_id, "nested").await;
+
+    // a tombstone with replies stays, but can't be restored anymore
+    delete(&store, branch_comment_id).await;
+    assert_eq!(purge(&store).await, 0);
+    assert!(store.restore_comment(branch_comment_id).await.is_err());
+    assert_eq!(
+        comment_ids(&find_comments(&store, resource_id).await),
+        [root_comment_id, branch_comment_id, nested_comment_id]
+    );
+
+    // once its last reply is purged, it goes in the same run
+    delete(&store, nested_comment_id).await;
+    assert_eq!(purge(&store).await, 2);
+    assert_eq!(
+        comment_ids(&find_comments(&store, resource_id).await),
+        [root_comment_id]
+    );
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert!(root_comment.branch_comment_ids.is_empty());
+    assert_eq!(root_comment.total_descendant_count, 0);
+
+    // imported tombstones come without the text and author they replaced
+    let mut imported_comment = new_comment(
+        PendingComment {
+            comment_id: Uuid::new_v4(),
+            resource_id,
+            parent_comment_id: None,
+            commenter: Commenter::deleted(),
+            commented_timestamp: Utc::now() - chrono::Duration::days(2),
+            comment_text: DELETED_COMMENT_TEXT.to_string(),
+        },
+        None,
+    );
+    imported_comment.deleted = true;
+    imported_comment.deleted_timestamp = Some(Utc::now() - chrono::Duration::days(1));
+    store.insert_comment(imported_comment).await.unwrap();
+
+    assert_eq!(purge(&store).await, 1);
+    assert_eq!(
+        comment_ids(&find_comments(&store, resource_id).await),
+        [root_comment_id]
+    );
+    assert_eq!(purge(&store).await, 0);
+}
+
+#[tokio::test]
+async fn expired_tombstones_are_purged() {
+    exercise_purge(new_store()).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_purges_expired_tombstones() {
+    let database_path = std::env::temp_dir().join(format!("commenter-{}.db", Uuid::new_v4()));
+    let pool = init_sqlite_connection(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let store = Arc::new(SqliteCommentStore { pool });
+    store.migrate().await.unwrap();
+
+    exercise_purge(store.clone()).await;
+
+    store.pool.close().await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", database_path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_purges_expired_tombstones() {
+    let store = new_mongo_store().await;
+
+    exercise_purge(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database at POSTGRES_TEST_URL"]
+async fn postgres_store_purges_expired_tombstones() {
+    let store = new_postgres_store().await;
+
+    exercise_purge(store.clone()).await;
+
+    drop_postgres_store(&store).await;
+}
+
+async fn register<S: CommentStore>(store: &Arc<S>, resource_id: Uuid, settings: Value) {
+    create_resource(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id, "title": "resource", "settings": settings })),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn resource_settings_are_enforced() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+    register(
+        &store,
+        resource_id,
+        json!({ "delete_mode": "prune", "max_depth": 2 }),
+    )
+    .await;
+
+    let response = get_resource_settings(
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id })),
+    )
+    .await;
+    assert_eq!(
+        parse(response)["settings"],
+        json!({
+            "delete_mode": "prune",
+            "comments_open": true,
+            "max_depth": 2,
+            "moderation_mode": "unmoderated",
+        })
+    );
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+
+    let response = create_branch_comment(
+        Extension(store.clone()),
+        request(json!({
+            "branched_from": branch_comment_id,
+            "commenter_account_id": Uuid::new_v4(),
+            "commenter_username": "commenter",
+            "comment_text": "too deep",
+        })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    // pruning takes the replies along instead of leaving a tombstone
+    delete(&store, root_comment_id).await;
+    assert!(find_comments(&store, resource_id).await.is_empty());
+
+    update_resource_settings(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id, "comments_open": false })),
+    )
+    .await
+    .unwrap();
+
+    let response = create_root_comment(
+        Extension(store.clone()),
+        request(json!({
+            "resource_id": resource_id,
+            "commenter_account_id": Uuid::new_v4(),
+            "commenter_username": "commenter",
+            "comment_text": "closed",
+        })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+}
+
+async fn find_pending<S: CommentStore>(store: &Arc<S>, resource_id: Uuid) -> Vec<Uuid> {
+    let response = get_pending_comments(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id })),
+    )
+    .await;
+    let pending_comments: Vec<PendingComment> =
+        serde_json::from_value(parse(response)["pending_comments"].clone()).unwrap();
+
+    pending_comments
+        .iter()
+        .map(|pending_comment| pending_comment.comment_id)
+        .collect()
+}
+
+// holds comments on a pre-moderated resource and publishes or drops them as a moderator
+// decides, which every store has to answer alike
+async fn exercise_pre_moderation<S: CommentStore>(store: Arc<S>) {
+    let resource_id = Uuid::new_v4();
+    register(
+        &store,
+        resource_id,
+        json!({ "moderation_mode": "pre_moderation" }),
+    )
+    .await;
+
+    let response = create_root_comment(
+        Extension(store.clone()),
+        request(json!({
+            "resource_id": resource_id,
+            "commenter_account_id": Uuid::new_v4(),
+            "commenter_username": "commenter",
+            "comment_text": "held",
+        })),
+    )
+    .await;
+    let response = parse(response);
+    assert_eq!(response["pending"], json!(true));
+    let root_comment_id: Uuid = serde_json::from_value(response["comment_id"].clone()).unwrap();
+
+    assert!(find_comments(&store, resource_id).await.is_empty());
+    assert_eq!(find_pending(&store, resource_id).await, [root_comment_id]);
+
+    approve_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "comment_id": root_comment_id })),
+    )
+    .await
+    .unwrap();
+
+    let comments = find_comments(&store, resource_id).await;
+    assert_eq!(comment_ids(&comments), [root_comment_id]);
+    assert_eq!(comments[0].comment_text, "held");
+    assert!(find_pending(&store, resource_id).await.is_empty());
+
+    // replies are held too, and publish below the comment they reply to
+    let approved_comment_id = create_branch(&store, root_comment_id, "approved").await;
+    let rejected_comment_id = create_branch(&store, root_comment_id, "rejected").await;
+    assert_eq!(
+        find_pending(&store, resource_id).await,
+        [approved_comment_id, rejected_comment_id]
+    );
+
+    approve_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "comment_id": approved_comment_id })),
+    )
+    .await
+    .unwrap();
+    reject_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "comment_id": rejected_comment_id })),
+    )
+    .await
+    .unwrap();
+
+    let comments = find_comments(&store, resource_id).await;
+    assert_eq!(
+        comment_ids(&comments),
+        [root_comment_id, approved_comment_id]
+    );
+    assert_eq!(comments[1].parent_comment_id, Some(root_comment_id));
+    assert_eq!(comments[1].depth, 2);
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert_eq!(root_comment.branch_comment_ids, [approved_comment_id]);
+    assert!(find_pending(&store, resource_id).await.is_empty());
+
+    // a rejected comment is gone, and a published one can't be approved again
+    for comment_id in [rejected_comment_id, approved_comment_id] {
+        let response = approve_comment(
+            Admin,
+            Extension(store.clone()),
+            request(json!({ "comment_id": comment_id })),
+        )
+        .await;
+        assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+    }
+
+    // replies to comments deleted while they were held can't be published
+    let orphaned_comment_id = create_branch(&store, approved_comment_id, "orphaned").await;
+    delete(&store, approved_comment_id).await;
+    let response = approve_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "comment_id": orphaned_comment_id })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+    assert_eq!(
+        find_pending(&store, resource_id).await,
+        [orphaned_comment_id]
+    );
+}
+
+#[tokio::test]
+async fn pre_moderated_comments_wait_for_approval() {
+    exercise_pre_moderation(new_store()).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_holds_pre_moderated_comments() {
+    let database_path = std::env::temp_dir().join(format!("commenter-{}.db", Uuid::new_v4()));
+    let pool = init_sqlite_connection(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let store = Arc::new(SqliteCommentStore { pool });
+    store.migrate().await.unwrap();
+
+    exercise_pre_moderation(store.clone()).await;
+
+    store.pool.close().await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", database_path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_holds_pre_moderated_comments() {
+    let store = new_mongo_store().await;
+
+    exercise_pre_moderation(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database at POSTGRES_TEST_URL"]
+async fn postgres_store_holds_pre_moderated_comments() {
+    let store = new_postgres_store().await;
+
+    exercise_pre_moderation(store.clone()).await;
+
+    drop_postgres_store(&store).await;
+}
+
+#[tokio::test]
+async fn comments_carry_their_place_in_the_thread() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+    let nested_comment_id = create_branch(&store, branch_comment_id, "nested").await;
+
+    let response = get_branch_comments_next(
+        Extension(store.clone()),
+        request(json!({ "branched_from": branch_comment_id })),
+    )
+    .await;
+    let branch_comments: Vec<Comment> =
+        serde_json::from_value(parse(response)["branch_comments"].clone()).unwrap();
+    assert_eq!(comment_ids(&branch_comments), [nested_comment_id]);
+    assert_eq!(branch_comments[0].resource_id, resource_id);
+    assert_eq!(
+        branch_comments[0].parent_comment_id,
+        Some(branch_comment_id)
+    );
+    assert_eq!(branch_comments[0].depth, 3);
+
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert_eq!(root_comment.resource_id, resource_id);
+    assert_eq!(root_comment.parent_comment_id, None);
+    assert_eq!(root_comment.depth, 1);
+}
+
+#[tokio::test]
+async fn subtrees_hold_only_the_replies_below_the_comment() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let first_branch_comment_id = create_branch(&store, root_comment_id, "first").await;
+    let second_branch_comment_id = create_branch(&store, root_comment_id, "second").await;
+    let first_nested_comment_id = create_branch(&store, first_branch_comment_id, "nested").await;
+    create_branch(&store, second_branch_comment_id, "elsewhere").await;
+    create_root(&store, resource_id, "other root").await;
+
+    let response = get_branch_comments_rest(
+        Extension(store.clone()),
+        request(json!({ "branched_from": first_branch_comment_id, "sort": "oldest" })),
+    )
+    .await;
+    let branch_comments: Vec<Comment> =
+        serde_json::from_value(parse(response)["branch_comments"].clone()).unwrap();
+    assert_eq!(
+        comment_ids(&branch_comments),
+        [first_branch_comment_id, first_nested_comment_id]
+    );
+    assert!(branch_comments[1]
+        .ancestor_comment_ids
+        .contains(&first_branch_comment_id));
+}
+
+#[test]
+fn cursors_only_parse_for_the_order_they_were_handed_out_for() {
+    let cursor = CommentCursor {
+        sort: CommentSort::Newest,
+        score: None,
+        commented_timestamp: Utc::now(),
+        comment_id: Uuid::new_v4(),
+    };
+    let encoded_cursor = cursor.encode();
+
+    assert_eq!(parse_cursor(None, CommentSort::Newest).unwrap(), None);
+    assert_eq!(
+        parse_cursor(Some(&encoded_cursor), CommentSort::Newest).unwrap(),
+        Some(cursor)
+    );
+    assert!(parse_cursor(Some(&encoded_cursor), CommentSort::Oldest).is_err());
+    assert!(parse_cursor(Some("not a cursor"), CommentSort::Newest).is_err());
+}
+
+// reads root comments a page at a time, which every store has to answer alike
+async fn exercise_paging<S: CommentStore>(store: Arc<S>) {
+    let resource_id = Uuid::new_v4();
+
+    let mut root_comment_ids = Vec::new();
+    for text in ["first", "second", "third", "fourth", "fifth"] {
+        root_comment_ids.push(create_root(&store, resource_id, text).await);
+    }
+    root_comment_ids.reverse();
+
+    let mut paged_comment_ids = Vec::new();
+    let mut cursor = Value::Null;
+    loop {
+        let response = get_root_comments(
+            Extension(store.clone()),
+            request(json!({ "resource_id": resource_id, "limit": 2, "cursor": cursor })),
+        )
+        .await;
+        let page = parse(response);
+
+        let root_comments: Vec<Comment> =
+            serde_json::from_value(page["root_comments"].clone()).unwrap();
+        assert!(root_comments.len() <= 2);
+        paged_comment_ids.extend(comment_ids(&root_comments));
+
+        assert_eq!(page["has_more"], !page["next_cursor"].is_null());
+        if page["next_cursor"].is_null() {
+            break;
+        }
+        cursor = page["next_cursor"].clone();
+    }
+    assert_eq!(paged_comment_ids, root_comment_ids);
+
+    // the page is cut one comment short of what was fetched
+    let comments = store
+        .find_next_level_comments(resource_id.to_string(), CommentSort::Newest, Some(3), None)
+        .await
+        .unwrap();
+    let (page, has_more, next_cursor) = into_page(comments, CommentSort::Newest, Some(2));
+    assert_eq!(comment_ids(&page), root_comment_ids[..2]);
+    assert!(has_more);
+    assert_eq!(
+        next_cursor.unwrap().parse::<CommentCursor>().unwrap(),
+        CommentCursor::of(&page[1], CommentSort::Newest)
+    );
+
+    let response = get_root_comments(
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id, "cursor": "not a cursor" })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn pages_end_where_the_next_cursor_picks_up() {
+    exercise_paging(new_store()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_pages_root_comments() {
+    let store = new_mongo_store().await;
+
+    exercise_paging(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+#[tokio::test]
+async fn listings_follow_the_requested_sort() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let quiet_comment_id = create_root(&store, resource_id, "quiet").await;
+    let liked_comment_id = create_root(&store, resource_id, "liked").await;
+    let disputed_comment_id = create_root(&store, resource_id, "disputed").await;
+
+    for _ in 0..3 {
+        react(&store, liked_comment_id, Uuid::new_v4(), "1f44d").await;
+    }
+    react(&store, disputed_comment_id, Uuid::new_v4(), "1f44d").await;
+    react(&store, disputed_comment_id, Uuid::new_v4(), "1f44e").await;
+
+    for (sort, expected_comment_ids) in [
+        (
+            "newest",
+            [disputed_comment_id, liked_comment_id, quiet_comment_id],
+        ),
+        (
+            "oldest",
+            [quiet_comment_id, liked_comment_id, disputed_comment_id],
+        ),
+        (
+            "top",
+            [liked_comment_id, disputed_comment_id, quiet_comment_id],
+        ),
+        (
+            "controversial",
+            [disputed_comment_id, liked_comment_id, quiet_comment_id],
+        ),
+    ] {
+        let response = get_root_comments(
+            Extension(store.clone()),
+            request(json!({ "resource_id": resource_id, "sort": sort })),
+        )
+        .await;
+        let root_comments: Vec<Comment> =
+            serde_json::from_value(parse(response)["root_comments"].clone()).unwrap();
+        assert_eq!(
+            comment_ids(&root_comments),
+            expected_comment_ids,
+            "{}",
+            sort
+        );
+    }
+}
+
+async fn find_levels<S: CommentStore>(
+    store: &Arc<S>,
+    payload: Value,
+) -> (Vec<Comment>, Vec<MoreReplies>) {
+    let response = get_branch_comments_levels(Extension(store.clone()), request(payload)).await;
+    let levels = parse(response);
+
+    (
+        serde_json::from_value(levels["branch_comments"].clone()).unwrap(),
+        serde_json::from_value(levels["more_replies"].clone()).unwrap(),
+    )
+}
+
+// fetches a thread a few levels at a time, which every store has to answer alike
+async fn exercise_levels<S: CommentStore>(store: Arc<S>) {
+    let resource_id = Uuid::new_v4();
+
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    create_branch(&store, root_comment_id, "first").await;
+    let second_branch_comment_id = create_branch(&store, root_comment_id, "second").await;
+    let third_branch_comment_id = create_branch(&store, root_comment_id, "third").await;
+
+    // a chain of replies deeper than a single fetch goes
+    let mut chain_comment_ids = vec![third_branch_comment_id];
+    for _ in 0..=MAX_BRANCH_COMMENTS_LEVELS {
+        let parent_comment_id = *chain_comment_ids.last().unwrap();
+        chain_comment_ids.push(create_branch(&store, parent_comment_id, "deeper").await);
+    }
+
+    let response = get_branch_comments_levels(
+        Extension(store.clone()),
+        request(json!({ "branched_from": root_comment_id, "depth": 1, "limit": 2 })),
+    )
+    .await;
+    let levels = parse(response);
+    let branch_comments: Vec<Comment> =
+        serde_json::from_value(levels["branch_comments"].clone()).unwrap();
+    assert_eq!(
+        comment_ids(&branch_comments),
+        [
+            root_comment_id,
+            third_branch_comment_id,
+            second_branch_comment_id
+        ]
+    );
+    let more_replies: Vec<MoreReplies> =
+        serde_json::from_value(levels["more_replies"].clone()).unwrap();
+    assert_eq!(more_replies.len(), 2);
+    assert_eq!(more_replies[0].parent_comment_id, root_comment_id);
+    assert_eq!(more_replies[0].hidden_reply_count, 1);
+    assert_eq!(
+        more_replies[0].cursor,
+        Some(CommentCursor::of(&branch_comments[2], CommentSort::Newest).encode())
+    );
+    assert_eq!(more_replies[1].parent_comment_id, third_branch_comment_id);
+    assert_eq!(more_replies[1].hidden_reply_count, 1);
+    assert_eq!(more_replies[1].cursor, None);
+
+    // asking for more levels than allowed fetches the most allowed
+    let response = get_branch_comments_levels(
+        Extension(store.clone()),
+        request(json!({ "branched_from": third_branch_comment_id, "depth": 50 })),
+    )
+    .await;
+    let levels = parse(response);
+    let branch_comments: Vec<Comment> =
+        serde_json::from_value(levels["branch_comments"].clone()).unwrap();
+    let last_level = MAX_BRANCH_COMMENTS_LEVELS as usize;
+    assert_eq!(
+        comment_ids(&branch_comments),
+        chain_comment_ids[..=last_level]
+    );
+    let more_replies: Vec<MoreReplies> =
+        serde_json::from_value(levels["more_replies"].clone()).unwrap();
+    assert_eq!(more_replies.len(), 1);
+    assert_eq!(
+        more_replies[0].parent_comment_id,
+        chain_comment_ids[last_level]
+    );
+
+    // the replies of a level are limited per comment they reply to
+    let mut second_reply_ids = vec![];
+    for _ in 0..3 {
+        second_reply_ids.push(create_branch(&store, second_branch_comment_id, "reply").await);
+    }
+    let (branch_comments, more_replies) = find_levels(
+        &store,
+        json!({ "branched_from": root_comment_id, "depth": 2, "limit": 2 }),
+    )
+    .await;
+    assert_eq!(
+        comment_ids(&branch_comments),
+        [
+            root_comment_id,
+            third_branch_comment_id,
+            second_branch_comment_id,
+            chain_comment_ids[1],
+            second_reply_ids[2],
+            second_reply_ids[1],
+        ]
+    );
+    let stubs: Vec<(Uuid, u32)> = more_replies
+        .iter()
+        .map(|stub| (stub.parent_comment_id, stub.hidden_reply_count))
+        .collect();
+    assert_eq!(
+        stubs,
+        [
+            (root_comment_id, 1),
+            (second_branch_comment_id, 1),
+            (chain_comment_ids[1], 1)
+        ]
+    );
+
+    // without a limit, a default one applies
+    let busy_comment_id = create_root(&store, resource_id, "busy").await;
+    for _ in 0..=DEFAULT_BRANCH_COMMENTS_LEVELS_LIMIT {
+        create_branch(&store, busy_comment_id, "reply").await;
+    }
+    let (branch_comments, more_replies) = find_levels(
+        &store,
+        json!({ "branched_from": busy_comment_id, "depth": 1 }),
+    )
+    .await;
+    assert_eq!(
+        branch_comments.len(),
+        DEFAULT_BRANCH_COMMENTS_LEVELS_LIMIT as usize + 1
+    );
+    assert_eq!(more_replies.len(), 1);
+    assert_eq!(more_replies[0].hidden_reply_count, 1);
+}
+
+#[tokio::test]
+async fn levels_leave_stubs_for_the_replies_left_out() {
+    exercise_levels(new_store()).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_fetches_levels() {
+    let database_path = std::env::temp_dir().join(format!("commenter-{}.db", Uuid::new_v4()));
+    let pool = init_sqlite_connection(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let store = Arc::new(SqliteCommentStore { pool });
+    store.migrate().await.unwrap();
+
+    exercise_levels(store.clone()).await;
+
+    store.pool.close().await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", database_path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database at POSTGRES_TEST_URL"]
+async fn postgres_store_fetches_levels() {
+    let store = new_postgres_store().await;
+
+    exercise_levels(store.clone()).await;
+
+    drop_postgres_store(&store).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_fetches_levels() {
+    let store = new_mongo_store().await;
+
+    exercise_levels(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+#[tokio::test]
+async fn into_tree_nests_replies_below_their_parents() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+
+    let first_root_comment_id = create_root(&store, resource_id, "first").await;
+    let second_root_comment_id = create_root(&store, resource_id, "second").await;
+    let first_branch_comment_id = create_branch(&store, first_root_comment_id, "one").await;
+    let second_branch_comment_id = create_branch(&store, first_root_comment_id, "two").await;
+    let nested_comment_id = create_branch(&store, second_branch_comment_id, "nested").await;
+
+    let comments = find_comments(&store, resource_id).await;
+    let tree = into_tree(comments.clone());
+    assert_eq!(
+        tree.iter()
+            .map(|node| node.comment.comment_id)
+            .collect::<Vec<_>>(),
+        [first_root_comment_id, second_root_comment_id]
+    );
+    assert_eq!(
+        tree[0]
+            .children
+            .iter()
+            .map(|node| node.comment.comment_id)
+            .collect::<Vec<_>>(),
+        [first_branch_comment_id, second_branch_comment_id]
+    );
+    assert_eq!(
+        tree[0].children[1].children[0].comment.comment_id,
+        nested_comment_id
+    );
+    assert!(tree[1].children.is_empty());
+
+    // replies whose parent isn't listed head a tree of their own
+    let tree = into_tree(comments[3..].to_vec());
+    assert_eq!(tree.len(), 1);
+    assert_eq!(tree[0].comment.comment_id, second_branch_comment_id);
+    assert_eq!(tree[0].children[0].comment.comment_id, nested_comment_id);
+
+    let response = get_all_comments(
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id, "sort": "oldest", "format": "tree" })),
+    )
+    .await;
+    let comments = &parse(response)["comments"];
+    assert_eq!(comments.as_array().unwrap().len(), 2);
+    assert_eq!(
+        comments[0]["children"][1]["children"][0]["comment_id"],
+        json!(nested_comment_id)
+    );
+    assert_eq!(comments[1]["children"], json!([]));
+}
+
+async fn move_below<S: CommentStore>(
+    store: &Arc<S>,
+    comment_id: Uuid,
+    new_parent_comment_id: Option<Uuid>,
+    resource_id: Option<Uuid>,
+) -> Result<(), ServerError> {
+    move_comment(
+        Admin,
+        Extension(store.clone()),
+        request(json!({
+            "comment_id": comment_id,
+            "new_parent_comment_id": new_parent_comment_id,
+            "resource_id": resource_id,
+        })),
+    )
+    .await
+}
+
+// moves a subtree within its resource and to another one, which every store has to answer alike
+async fn exercise_moves<S: CommentStore>(store: Arc<S>) {
+    let resource_id = Uuid::new_v4();
+
+    let first_root_comment_id = create_root(&store, resource_id, "first").await;
+    let second_root_comment_id = create_root(&store, resource_id, "second").await;
+    let branch_comment_id = create_branch(&store, first_root_comment_id, "branch").await;
+    let nested_comment_id = create_branch(&store, branch_comment_id, "nested").await;
+
+    move_below(
+        &store,
+        branch_comment_id,
+        Some(second_root_comment_id),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let nested_comment = store.find_comment(nested_comment_id).await.unwrap();
+    assert_eq!(
+        nested_comment.materialized_path,
+        format!(
+            "{}->{}->{}->{}",
+            resource_id, second_root_comment_id, branch_comment_id, nested_comment_id
+        )
+    );
+    assert_eq!(
+        nested_comment.ancestor_comment_ids,
+        [second_root_comment_id, branch_comment_id]
+    );
+    let first_root_comment = store.find_comment(first_root_comment_id).await.unwrap();
+    assert!(first_root_comment.branch_comment_ids.is_empty());
+    assert_eq!(first_root_comment.total_descendant_count, 0);
+    let second_root_comment = store.find_comment(second_root_comment_id).await.unwrap();
+    assert_eq!(second_root_comment.branch_comment_ids, [branch_comment_id]);
+    assert_eq!(second_root_comment.total_descendant_count, 2);
+
+    let response = move_below(&store, branch_comment_id, Some(nested_comment_id), None).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    let response = move_below(&store, branch_comment_id, None, Some(Uuid::new_v4())).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    // the whole subtree has to fit the destination's depth limit
+    let other_resource_id = Uuid::new_v4();
+    register(&store, other_resource_id, json!({ "max_depth": 2 })).await;
+    let other_root_comment_id = create_root(&store, other_resource_id, "other").await;
+
+    let response = move_below(&store, branch_comment_id, Some(other_root_comment_id), None).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    move_below(&store, branch_comment_id, None, Some(other_resource_id))
+        .await
+        .unwrap();
+
+    let branch_comment = store.find_comment(branch_comment_id).await.unwrap();
+    assert_eq!(branch_comment.resource_id, other_resource_id);
+    assert_eq!(branch_comment.parent_comment_id, None);
+    assert_eq!(branch_comment.depth, 1);
+    assert_eq!(
+        store.find_comment(nested_comment_id).await.unwrap().depth,
+        2
+    );
+    assert_eq!(
+        comment_ids(&find_comments(&store, resource_id).await),
+        [first_root_comment_id, second_root_comment_id]
+    );
+}
+
+#[tokio::test]
+async fn moved_subtrees_keep_their_shape() {
+    exercise_moves(new_store()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_moves_subtrees() {
+    let store = new_mongo_store().await;
+
+    exercise_moves(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+async fn merge<S: CommentStore>(
+    store: &Arc<S>,
+    source_resource_id: Uuid,
+    target_resource_id: Uuid,
+) -> Result<(), ServerError> {
+    merge_resources(
+        Admin,
+        Extension(store.clone()),
+        request(json!({
+            "source_resource_id": source_resource_id,
+            "target_resource_id": target_resource_id,
+        })),
+    )
+    .await
+}
+
+// merges one resource's thread into another's, which every store has to answer alike
+async fn exercise_merge<S: CommentStore>(store: Arc<S>) {
+    let source_resource_id = Uuid::new_v4();
+    let target_resource_id = Uuid::new_v4();
+
+    let source_root_comment_id = create_root(&store, source_resource_id, "source").await;
+    let source_branch_comment_id = create_branch(&store, source_root_comment_id, "reply").await;
+    let target_root_comment_id = create_root(&store, target_resource_id, "target").await;
+
+    merge(&store, source_resource_id, target_resource_id)
+        .await
+        .unwrap();
+
+    let comments = find_comments(&store, target_resource_id).await;
+    assert_eq!(
+        comment_ids(&comments),
+        [
+            source_root_comment_id,
+            target_root_comment_id,
+            source_branch_comment_id
+        ]
+    );
+    assert_eq!(
+        comments[2].materialized_path,
+        format!(
+            "{}->{}->{}",
+            target_resource_id, source_root_comment_id, source_branch_comment_id
+        )
+    );
+
+    // the source id is left behind as an alias of the target
+    assert_eq!(
+        comment_ids(&find_comments(&store, source_resource_id).await),
+        comment_ids(&comments)
+    );
+    let response = merge(&store, source_resource_id, target_resource_id).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    let response = merge(&store, target_resource_id, source_resource_id).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn merged_resources_share_one_thread() {
+    exercise_merge(new_store()).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a MongoDB replica set at MONGODB_TEST_URL"]
+async fn mongo_store_merges_resources() {
+    let store = new_mongo_store().await;
+
+    exercise_merge(store.clone()).await;
+
+    drop_mongo_store(&store).await;
+}
+
+async fn alias<S: CommentStore>(
+    store: &Arc<S>,
+    alias_resource_id: Uuid,
+    resource_id: Uuid,
+) -> Result<(), ServerError> {
+    create_resource_alias(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "alias_resource_id": alias_resource_id, "resource_id": resource_id })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn aliases_stand_for_their_resource() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+    let alias_resource_id = Uuid::new_v4();
+    let nested_alias_resource_id = Uuid::new_v4();
+
+    alias(&store, alias_resource_id, resource_id).await.unwrap();
+    // aliasing an alias aliases the resource it stands for
+    alias(&store, nested_alias_resource_id, alias_resource_id)
+        .await
+        .unwrap();
+
+    let root_comment_id = create_root(&store, nested_alias_resource_id, "aliased").await;
+    let root_comment = store.find_comment(root_comment_id).await.unwrap();
+    assert_eq!(root_comment.resource_id, resource_id);
+
+    let response = get_resource_aliases(
+        Extension(store.clone()),
+        request(json!({ "resource_id": alias_resource_id })),
+    )
+    .await;
+    let resource_aliases = parse(response);
+    assert_eq!(resource_aliases["resource_id"], json!(resource_id));
+    let resource_aliases: Vec<ResourceAlias> =
+        serde_json::from_value(resource_aliases["resource_aliases"].clone()).unwrap();
+    assert_eq!(
+        resource_aliases
+            .iter()
+            .map(|resource_alias| resource_alias.alias_resource_id)
+            .collect::<Vec<_>>(),
+        [alias_resource_id, nested_alias_resource_id]
+    );
+
+    let response = alias(&store, alias_resource_id, Uuid::new_v4()).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+    let response = alias(&store, resource_id, alias_resource_id).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    // ids with comments of their own have to be merged instead
+    let commented_resource_id = Uuid::new_v4();
+    create_root(&store, commented_resource_id, "own").await;
+    let response = alias(&store, commented_resource_id, resource_id).await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::BAD_REQUEST);
+
+    delete_resource_alias(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "alias_resource_id": alias_resource_id })),
+    )
+    .await
+    .unwrap();
+    assert!(find_comments(&store, alias_resource_id).await.is_empty());
+
+    let response = delete_resource_alias(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "alias_resource_id": resource_id })),
+    )
+    .await;
+    assert_eq!(response.unwrap_err().status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn exports_stream_the_whole_thread() {
+    let store = new_store();
+    let resource_id = Uuid::new_v4();
+    let root_comment_id = create_root(&store, resource_id, "root").await;
+    let branch_comment_id = create_branch(&store, root_comment_id, "branch").await;
+
+    let response = export_resource_comments(
+        Admin,
+        Extension(store.clone()),
+        request(json!({ "resource_id": resource_id, "format": "ndjson" })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        ExportFormat::Ndjson.content_type()
+    );
+
+    let mut body = response.into_body();
+    let mut export = Vec::new();
+    while let Some(chunk) = body.data().await {
+        export.extend_from_slice(&chunk.unwrap());
+    }
+    let lines: Vec<Value> = String::from_utf8(export)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines[0]["resource_id"], json!(resource_id));
+    assert_eq!(
+        lines[1..]
+            .iter()
+            .map(|line| line["comment_id"].clone())
+            .collect::<Vec<_>>(),
+        [json!(root_comment_id), json!(branch_comment_id)]
+    );
+    assert_eq!(lines[2]["parent_comment_id"], json!(root_comment_id));
+}
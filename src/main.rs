@@ -4,9 +4,13 @@ mod models;
 mod persistent;
 mod service;
 
-use service::server::init_server;
+use clap::Parser;
+
+use service::cli::{run, Cli};
 
 #[tokio::main]
 async fn main() {
-    init_server().await;
+    tracing_subscriber::fmt::init();
+
+    run(Cli::parse()).await;
 }
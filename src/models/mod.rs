@@ -1,15 +1,45 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr};
 use uuid::Uuid;
 
 // ---
 
+/// Version of the shape of stored comments. Bump it together with a migration that brings older
+/// documents up to date.
+pub const CURRENT_COMMENT_SCHEMA_VERSION: u32 = 9;
+
+/// Text a deleted comment is replaced with when it is left in its thread as a tombstone.
+pub const DELETED_COMMENT_TEXT: &str = "[deleted]";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CommentType {
     Root,
     Branch,
 }
 
+impl CommentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentType::Root => "Root",
+            CommentType::Branch => "Branch",
+        }
+    }
+}
+
+impl FromStr for CommentType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Root" => Ok(CommentType::Root),
+            "Branch" => Ok(CommentType::Branch),
+            _ => Err(anyhow::anyhow!("unknown comment type: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment {
     pub comment_id: Uuid,
@@ -17,9 +47,174 @@ pub struct Comment {
     pub commenter: Commenter,
     pub commented_timestamp: DateTime<Utc>,
     pub comment_text: String,
-    pub reactions: Vec<CommentReaction>,
     pub branch_comment_ids: Vec<Uuid>,
     pub materialized_path: String,
+    /// The resource the comment belongs to, which heads its materialized path.
+    pub resource_id: Uuid,
+    /// The comment it was branched from, `None` for a root comment.
+    #[serde(default)]
+    pub parent_comment_id: Option<Uuid>,
+    /// How deep the comment is in its thread, root comments being at depth 1.
+    pub depth: u32,
+    /// Every comment above the comment, root comment first, so that subtrees can be looked up
+    /// by equality on any of them.
+    pub ancestor_comment_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub direct_reply_count: u32,
+    #[serde(default)]
+    pub total_descendant_count: u32,
+    /// Number of reactions per emoji unified code. The reactions themselves are stored apart
+    /// from the comment.
+    #[serde(default)]
+    pub reaction_counts: BTreeMap<String, u32>,
+    /// Number of reactions with any emoji, which the `top` sort order ranks by.
+    #[serde(default)]
+    pub total_reaction_count: u32,
+    /// How evenly the reactions for and against the comment are split, weighted by how many
+    /// there are, which the `controversial` sort order ranks by.
+    #[serde(default)]
+    pub controversy_score: f64,
+    /// Whether the comment was deleted and replaced with a tombstone, which keeps its replies in
+    /// place.
+    #[serde(default)]
+    pub deleted: bool,
+    /// When the comment was replaced with a tombstone, which starts the window it can be
+    /// restored in.
+    #[serde(default)]
+    pub deleted_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// Emoji unified codes of the reactions that count for and against a comment when weighing how
+/// controversial it is, 👍 and 👎.
+pub const APPROVING_EMOJI_UNIFIED_CODE: &str = "1f44d";
+pub const DISAPPROVING_EMOJI_UNIFIED_CODE: &str = "1f44e";
+
+/// Number of reactions with any emoji, from the reaction counts of a comment.
+pub fn total_reaction_count(reaction_counts: &BTreeMap<String, u32>) -> u32 {
+    reaction_counts.values().sum()
+}
+
+/// How controversial a comment is, from its reaction counts: the number of 👍 and 👎 together,
+/// scaled by how close they are to an even split. 0 unless it has both.
+pub fn controversy_score(reaction_counts: &BTreeMap<String, u32>) -> f64 {
+    let count = |emoji_unified_code: &str| {
+        f64::from(
+            reaction_counts
+                .get(emoji_unified_code)
+                .copied()
+                .unwrap_or_default(),
+        )
+    };
+    let approving_count = count(APPROVING_EMOJI_UNIFIED_CODE);
+    let disapproving_count = count(DISAPPROVING_EMOJI_UNIFIED_CODE);
+
+    if approving_count == 0.0 || disapproving_count == 0.0 {
+        return 0.0;
+    }
+
+    (approving_count + disapproving_count) * approving_count.min(disapproving_count)
+        / approving_count.max(disapproving_count)
+}
+
+/// Order comments are listed in. Comments ranked the same are listed latest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentSort {
+    /// Latest first.
+    #[default]
+    Newest,
+    /// Earliest first.
+    Oldest,
+    /// Most reactions first.
+    Top,
+    /// Highest `controversy_score` first.
+    Controversial,
+}
+
+impl CommentSort {
+    /// Whether the order ranks comments by a score before their timestamp.
+    pub fn has_score(&self) -> bool {
+        matches!(self, CommentSort::Top | CommentSort::Controversial)
+    }
+
+    /// The value a comment is ranked by before its timestamp, if the order has one.
+    pub fn score(&self, comment: &Comment) -> Option<f64> {
+        match self {
+            CommentSort::Newest | CommentSort::Oldest => None,
+            CommentSort::Top => Some(f64::from(comment.total_reaction_count)),
+            CommentSort::Controversial => Some(comment.controversy_score),
+        }
+    }
+}
+
+/// Position of a comment in a listing, which the next page of the listing starts after. Handed
+/// to clients as an opaque string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommentCursor {
+    /// The order of the listing, cursors don't carry over to listings in another order.
+    #[serde(default)]
+    pub sort: CommentSort,
+    /// Parsed back exactly as it was encoded, since listings start strictly after it.
+    #[serde(default)]
+    pub score: Option<f64>,
+    pub commented_timestamp: DateTime<Utc>,
+    /// Breaks ties between comments made at the same time.
+    pub comment_id: Uuid,
+}
+
+impl CommentCursor {
+    /// The cursor pointing at the given comment in a listing in the given order.
+    pub fn of(comment: &Comment, sort: CommentSort) -> Self {
+        CommentCursor {
+            sort,
+            score: sort.score(comment),
+            commented_timestamp: comment.commented_timestamp,
+            comment_id: comment.comment_id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+}
+
+impl FromStr for CommentCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_slice(&URL_SAFE_NO_PAD.decode(s)?)?)
+    }
+}
+
+/// How listings of a whole thread are laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListingFormat {
+    /// A flat list, shallowest comments first.
+    #[default]
+    Flat,
+    /// The top comments, each with its replies nested in `children`.
+    Tree,
+}
+
+/// A comment in a nested listing, together with its replies.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentNode {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub children: Vec<CommentNode>,
+}
+
+/// Stands in for the replies to a comment that a depth-limited fetch left out, which
+/// `/branch-comments/next` loads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoreReplies {
+    pub parent_comment_id: Uuid,
+    pub hidden_reply_count: u32,
+    /// Where the left out replies start, `None` if none of the replies were included.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,14 +223,208 @@ pub struct Commenter {
     pub username: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Commenter {
+    /// Stands in for the author of a tombstone, who is removed from the comment.
+    pub fn deleted() -> Self {
+        Commenter {
+            account_id: Uuid::nil(),
+            username: DELETED_COMMENT_TEXT.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CommentReactor {
     pub account_id: Uuid,
     pub username: String,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub struct CommentReaction {
     pub reactor: CommentReactor,
     pub emoji_unified_code: String,
 }
+
+// ---
+
+/// What deleting a comment does to it and its replies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    /// The comment is replaced with a tombstone, its replies are kept.
+    #[default]
+    Tombstone,
+    /// The comment is removed together with all of its replies.
+    Prune,
+}
+
+impl DeleteMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeleteMode::Tombstone => "tombstone",
+            DeleteMode::Prune => "prune",
+        }
+    }
+}
+
+impl FromStr for DeleteMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tombstone" => Ok(DeleteMode::Tombstone),
+            "prune" => Ok(DeleteMode::Prune),
+            _ => Err(anyhow::anyhow!("unknown delete mode: {}", s)),
+        }
+    }
+}
+
+/// How comments on a resource are reviewed. Under pre-moderation new comments are held as
+/// [`PendingComment`]s until a moderator approves them; otherwise they are published right away.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationMode {
+    /// Comments are not reviewed.
+    #[default]
+    Unmoderated,
+    /// Comments are reviewed after they are published.
+    PostModeration,
+    /// Comments are reviewed before they are published.
+    PreModeration,
+}
+
+impl ModerationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationMode::Unmoderated => "unmoderated",
+            ModerationMode::PostModeration => "post_moderation",
+            ModerationMode::PreModeration => "pre_moderation",
+        }
+    }
+}
+
+impl FromStr for ModerationMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unmoderated" => Ok(ModerationMode::Unmoderated),
+            "post_moderation" => Ok(ModerationMode::PostModeration),
+            "pre_moderation" => Ok(ModerationMode::PreModeration),
+            _ => Err(anyhow::anyhow!("unknown moderation mode: {}", s)),
+        }
+    }
+}
+
+fn default_comments_open() -> bool {
+    true
+}
+
+/// Settings of the comment section of a resource. Resources nobody registered use the defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceSettings {
+    #[serde(default)]
+    pub delete_mode: DeleteMode,
+    /// Whether new comments are taken. Existing comments stay readable either way.
+    #[serde(default = "default_comments_open")]
+    pub comments_open: bool,
+    /// How many levels deep threads may go, root comments being the first. Unlimited if not set.
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    #[serde(default)]
+    pub moderation_mode: ModerationMode,
+}
+
+impl Default for ResourceSettings {
+    fn default() -> Self {
+        ResourceSettings {
+            delete_mode: DeleteMode::default(),
+            comments_open: default_comments_open(),
+            max_depth: None,
+            moderation_mode: ModerationMode::default(),
+        }
+    }
+}
+
+/// The digital content comments are associated with, as registered in the resource registry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Resource {
+    pub resource_id: Uuid,
+    pub title: String,
+    pub canonical_url: Option<String>,
+    pub owner_account_id: Option<Uuid>,
+    pub created_timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub settings: ResourceSettings,
+}
+
+/// A comment made on a pre-moderated resource, held apart from its thread until a moderator
+/// approves or rejects it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingComment {
+    /// The id the comment keeps once it is approved.
+    pub comment_id: Uuid,
+    pub resource_id: Uuid,
+    /// The comment it replies to, `None` for a root comment.
+    pub parent_comment_id: Option<Uuid>,
+    pub commenter: Commenter,
+    pub commented_timestamp: DateTime<Utc>,
+    pub comment_text: String,
+}
+
+/// An id that stands for another resource, whose comment thread is served in its place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceAlias {
+    pub alias_resource_id: Uuid,
+    /// The resource the alias stands for, which is never an alias itself.
+    pub resource_id: Uuid,
+    pub created_timestamp: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_scores_survive_encoding_exactly() {
+        // scores the default float parsing of serde_json reads back one bit off
+        let reaction_counts = BTreeMap::from([
+            (APPROVING_EMOJI_UNIFIED_CODE.to_string(), 575),
+            (DISAPPROVING_EMOJI_UNIFIED_CODE.to_string(), 927),
+        ]);
+        for score in [controversy_score(&reaction_counts), 228.56652360515022] {
+            let cursor = CommentCursor {
+                sort: CommentSort::Controversial,
+                score: Some(score),
+                commented_timestamp: Utc::now(),
+                comment_id: Uuid::new_v4(),
+            };
+
+            let parsed_cursor: CommentCursor = cursor.encode().parse().unwrap();
+
+            assert_eq!(parsed_cursor.score.map(f64::to_bits), Some(score.to_bits()));
+            assert_eq!(parsed_cursor, cursor);
+        }
+    }
+
+    #[test]
+    fn controversy_score_favors_many_evenly_split_reactions() {
+        let score = |approving_count: u32, disapproving_count: u32| {
+            controversy_score(&BTreeMap::from([
+                (APPROVING_EMOJI_UNIFIED_CODE.to_string(), approving_count),
+                (
+                    DISAPPROVING_EMOJI_UNIFIED_CODE.to_string(),
+                    disapproving_count,
+                ),
+                ("2764-fe0f".to_string(), 10),
+            ]))
+        };
+
+        assert_eq!(score(0, 5), 0.0);
+        assert_eq!(score(5, 0), 0.0);
+        assert_eq!(score(5, 5), 10.0);
+        assert_eq!(score(2, 8), score(8, 2));
+        assert!(score(10, 10) > score(5, 5));
+        assert!(score(5, 5) > score(2, 8));
+    }
+}
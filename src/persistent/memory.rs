@@ -0,0 +1,840 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{cmp::Ordering, collections::HashMap, sync::RwLock};
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    common::utils::{
+        ancestor_comment_ids_from_materialized_path, append_uuid_to_materialized_path,
+        comment_id_from_materialized_path, depth_from_materialized_path,
+        materialized_path_to_uuid_list, parent_comment_id_from_materialized_path,
+        rebase_materialized_path, resource_id_from_materialized_path,
+    },
+    models::{
+        controversy_score, total_reaction_count, Comment, CommentCursor, CommentReaction,
+        CommentSort, CommentType, Commenter, PendingComment, Resource, ResourceAlias,
+        ResourceSettings, DELETED_COMMENT_TEXT,
+    },
+    persistent::CommentStore,
+};
+
+/// Keeps every comment and reaction in process memory. Nothing survives a restart, which makes it
+/// a good fit for local development and tests but not for production.
+#[derive(Debug, Default)]
+pub struct InMemoryCommentStore {
+    comments: RwLock<HashMap<Uuid, Comment>>,
+    // reactions per comment id, oldest first. always locked after `comments`
+    reactions: RwLock<HashMap<Uuid, Vec<CommentReaction>>>,
+    // text and author of tombstones that can still be restored, per comment id. always locked
+    // after `reactions`
+    deleted_comment_contents: RwLock<HashMap<Uuid, DeletedCommentContent>>,
+    // comments held on pre-moderated resources, per comment id
+    pending_comments: RwLock<HashMap<Uuid, PendingComment>>,
+    resources: RwLock<HashMap<Uuid, Resource>>,
+    // per alias id. always locked after `resources`
+    resource_aliases: RwLock<HashMap<Uuid, ResourceAlias>>,
+}
+
+#[derive(Debug)]
+struct DeletedCommentContent {
+    commenter: Commenter,
+    comment_text: String,
+}
+
+impl InMemoryCommentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// the materialized path of a direct child is its parent's path followed by exactly one uuid
+fn is_next_level_path(current_path: &str, materialized_path: &str) -> bool {
+    materialized_path
+        .strip_prefix(current_path)
+        .and_then(|rest| rest.strip_prefix("->"))
+        .map(|rest| rest.len() == 36 && Uuid::parse_str(rest).is_ok())
+        .unwrap_or(false)
+}
+
+// a reactor reacts with a given emoji at most once, whatever username they had at the time
+fn is_same_reaction(a: &CommentReaction, b: &CommentReaction) -> bool {
+    a.reactor.account_id == b.reactor.account_id && a.emoji_unified_code == b.emoji_unified_code
+}
+
+// what listings in the given order sort a comment by, most significant first
+type SortKey = (f64, DateTime<Utc>, Uuid);
+
+fn sort_key(sort: CommentSort, comment: &Comment) -> SortKey {
+    (
+        sort.score(comment).unwrap_or_default(),
+        comment.commented_timestamp,
+        comment.comment_id,
+    )
+}
+
+// orders the sort keys of two comments the way listings in the given order list them
+fn compare_sort_keys(sort: CommentSort, a: &SortKey, b: &SortKey) -> Ordering {
+    let ordering =
+        a.0.total_cmp(&b.0)
+            .then_with(|| a.1.cmp(&b.1))
+            .then_with(|| a.2.cmp(&b.2));
+
+    match sort {
+        CommentSort::Oldest => ordering,
+        CommentSort::Newest | CommentSort::Top | CommentSort::Controversial => ordering.reverse(),
+    }
+}
+
+// keeps the reaction based sort keys of a comment in line with its reaction counts
+fn update_reaction_scores(comment: &mut Comment) {
+    comment.total_reaction_count = total_reaction_count(&comment.reaction_counts);
+    comment.controversy_score = controversy_score(&comment.reaction_counts);
+}
+
+#[async_trait]
+impl CommentStore for InMemoryCommentStore {
+    async fn migrate(&self) -> Result<Vec<String>> {
+        // nothing outlives the process, so there is never anything to migrate
+        Ok(vec![])
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_comment(&self, comment: Comment) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+
+        if comments.contains_key(&comment.comment_id) {
+            return Err(anyhow::anyhow!("error inserting the document"));
+        }
+
+        if let Some(parent_comment_id) = comment.parent_comment_id {
+            // the comment it was branched from may have been deleted in the meantime
+            let parent_comment = comments
+                .get_mut(&parent_comment_id)
+                .ok_or(anyhow::anyhow!("branched from comment not found"))?;
+            parent_comment.branch_comment_ids.push(comment.comment_id);
+            parent_comment.direct_reply_count += 1;
+        }
+
+        for ancestor_comment_id in
+            ancestor_comment_ids_from_materialized_path(&comment.materialized_path)
+        {
+            if let Some(ancestor_comment) = comments.get_mut(&ancestor_comment_id) {
+                ancestor_comment.total_descendant_count += 1;
+            }
+        }
+
+        comments.insert(comment.comment_id, comment);
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_pending_comment(&self, pending_comment: PendingComment) -> Result<()> {
+        let mut pending_comments = self.pending_comments.write().unwrap();
+
+        if pending_comments.contains_key(&pending_comment.comment_id) {
+            return Err(anyhow::anyhow!("error inserting the document"));
+        }
+
+        pending_comments.insert(pending_comment.comment_id, pending_comment);
+
+        Ok(())
+    }
+
+    async fn find_pending_comment(&self, comment_id: Uuid) -> Result<PendingComment> {
+        let pending_comments = self.pending_comments.read().unwrap();
+
+        pending_comments
+            .get(&comment_id)
+            .cloned()
+            .ok_or(anyhow::anyhow!("pending comment not found"))
+    }
+
+    async fn find_pending_comments(&self, resource_id: Uuid) -> Result<Vec<PendingComment>> {
+        let pending_comments = self.pending_comments.read().unwrap();
+
+        let mut results: Vec<PendingComment> = pending_comments
+            .values()
+            .filter(|pending_comment| pending_comment.resource_id == resource_id)
+            .cloned()
+            .collect();
+        results.sort_by_key(|pending_comment| {
+            (
+                pending_comment.commented_timestamp,
+                pending_comment.comment_id,
+            )
+        });
+
+        Ok(results)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn delete_pending_comment(&self, comment_id: Uuid) -> Result<()> {
+        let mut pending_comments = self.pending_comments.write().unwrap();
+
+        if pending_comments.remove(&comment_id).is_none() {
+            info!("no pending comment documentations deleted")
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn append_reaction_to_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_append: CommentReaction,
+    ) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+        let mut reactions = self.reactions.write().unwrap();
+
+        let comment = comments.get_mut(&comment_id).ok_or(anyhow::anyhow!(
+            "error updating (appending reaction to) comment documents"
+        ))?;
+
+        let comment_reactions = reactions.entry(comment_id).or_default();
+        if !comment_reactions
+            .iter()
+            .any(|reaction| is_same_reaction(reaction, &comment_reaction_to_append))
+        {
+            *comment
+                .reaction_counts
+                .entry(comment_reaction_to_append.emoji_unified_code.clone())
+                .or_default() += 1;
+            update_reaction_scores(comment);
+            comment_reactions.push(comment_reaction_to_append);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn remove_reaction_from_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_remove: CommentReaction,
+    ) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+        let mut reactions = self.reactions.write().unwrap();
+
+        let removed = match (
+            comments.get_mut(&comment_id),
+            reactions.get_mut(&comment_id),
+        ) {
+            (Some(comment), Some(comment_reactions)) => {
+                let reactions_count = comment_reactions.len();
+                comment_reactions
+                    .retain(|reaction| !is_same_reaction(reaction, &comment_reaction_to_remove));
+                let removed = comment_reactions.len() != reactions_count;
+
+                if removed {
+                    let emoji_unified_code = &comment_reaction_to_remove.emoji_unified_code;
+                    if let Some(reaction_count) =
+                        comment.reaction_counts.get_mut(emoji_unified_code)
+                    {
+                        *reaction_count = reaction_count.saturating_sub(1);
+                        if *reaction_count == 0 {
+                            comment.reaction_counts.remove(emoji_unified_code);
+                        }
+                    }
+                    update_reaction_scores(comment);
+                }
+
+                removed
+            }
+            _ => false,
+        };
+
+        if !removed {
+            return Err(anyhow::anyhow!(
+                "error updating (removing reaction from) comment documents"
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn update_comment_text(&self, comment_id: Uuid, new_comment_text: String) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+
+        match comments.get_mut(&comment_id) {
+            Some(comment) => comment.comment_text = new_comment_text,
+            None => info!("no comment documentations updated"),
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn tombstone_comment(
+        &self,
+        comment_id: Uuid,
+        deleted_timestamp: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+        let mut deleted_comment_contents = self.deleted_comment_contents.write().unwrap();
+
+        match comments.get_mut(&comment_id) {
+            Some(comment) if !comment.deleted => {
+                let commenter = std::mem::replace(&mut comment.commenter, Commenter::deleted());
+                let comment_text =
+                    std::mem::replace(&mut comment.comment_text, DELETED_COMMENT_TEXT.to_string());
+                comment.deleted = true;
+                comment.deleted_timestamp = Some(deleted_timestamp);
+
+                deleted_comment_contents.insert(
+                    comment_id,
+                    DeletedCommentContent {
+                        commenter,
+                        comment_text,
+                    },
+                );
+            }
+            _ => info!("no comment documentations updated"),
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn restore_comment(&self, comment_id: Uuid) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+        let mut deleted_comment_contents = self.deleted_comment_contents.write().unwrap();
+
+        let (comment, deleted_comment_content) = match (
+            comments.get_mut(&comment_id),
+            deleted_comment_contents.remove(&comment_id),
+        ) {
+            (Some(comment), Some(deleted_comment_content)) => (comment, deleted_comment_content),
+            _ => return Err(anyhow::anyhow!("deleted comment content not found")),
+        };
+
+        comment.commenter = deleted_comment_content.commenter;
+        comment.comment_text = deleted_comment_content.comment_text;
+        comment.deleted = false;
+        comment.deleted_timestamp = None;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn discard_deleted_comment_content(&self, comment_id: Uuid) -> Result<()> {
+        let mut deleted_comment_contents = self.deleted_comment_contents.write().unwrap();
+
+        if deleted_comment_contents.remove(&comment_id).is_none() {
+            info!("no deleted comment contents discarded");
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn prune_comments(&self, comment_materialized_path: String) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+        let mut reactions = self.reactions.write().unwrap();
+        let mut deleted_comment_contents = self.deleted_comment_contents.write().unwrap();
+
+        let comments_count = comments.len();
+        comments.retain(|comment_id, comment| {
+            let retained = !comment
+                .materialized_path
+                .starts_with(&comment_materialized_path);
+            if !retained {
+                reactions.remove(comment_id);
+                deleted_comment_contents.remove(comment_id);
+            }
+            retained
+        });
+
+        let deleted_count = (comments_count - comments.len()) as u32;
+        if deleted_count == 0 {
+            info!("no comment documentations deleted");
+            return Ok(());
+        }
+
+        if let (Some(parent_comment_id), Some(pruned_comment_id)) = (
+            parent_comment_id_from_materialized_path(&comment_materialized_path),
+            comment_id_from_materialized_path(&comment_materialized_path),
+        ) {
+            if let Some(parent_comment) = comments.get_mut(&parent_comment_id) {
+                parent_comment
+                    .branch_comment_ids
+                    .retain(|branch_comment_id| branch_comment_id != &pruned_comment_id);
+                parent_comment.direct_reply_count =
+                    parent_comment.direct_reply_count.saturating_sub(1);
+            }
+        }
+
+        for ancestor_comment_id in
+            ancestor_comment_ids_from_materialized_path(&comment_materialized_path)
+        {
+            if let Some(ancestor_comment) = comments.get_mut(&ancestor_comment_id) {
+                ancestor_comment.total_descendant_count = ancestor_comment
+                    .total_descendant_count
+                    .saturating_sub(deleted_count);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn move_comments(
+        &self,
+        comment_materialized_path: String,
+        new_parent_materialized_path: String,
+    ) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+
+        let comment_id = comment_id_from_materialized_path(&comment_materialized_path)
+            .filter(|_| depth_from_materialized_path(&comment_materialized_path) > 0)
+            .ok_or_else(|| anyhow::anyhow!("not the path of a comment"))?;
+        if materialized_path_to_uuid_list(&new_parent_materialized_path)?.contains(&comment_id) {
+            return Err(anyhow::anyhow!("cannot move a comment below itself"));
+        }
+
+        let new_materialized_path =
+            append_uuid_to_materialized_path(&new_parent_materialized_path, &comment_id);
+        let new_parent_comment_id =
+            parent_comment_id_from_materialized_path(&new_materialized_path);
+        let resource_id = resource_id_from_materialized_path(&new_materialized_path)
+            .ok_or_else(|| anyhow::anyhow!("invalid materialized path"))?;
+
+        if comments
+            .get(&comment_id)
+            .filter(|comment| comment.materialized_path == comment_materialized_path)
+            .is_none()
+        {
+            return Err(anyhow::anyhow!("comment not found"));
+        }
+        if let Some(new_parent_comment_id) = new_parent_comment_id {
+            if !comments.contains_key(&new_parent_comment_id) {
+                return Err(anyhow::anyhow!("new parent comment not found"));
+            }
+        }
+
+        let mut moved_count: u32 = 0;
+        for comment in comments.values_mut() {
+            if let Some(materialized_path) = rebase_materialized_path(
+                &comment.materialized_path,
+                &comment_materialized_path,
+                &new_materialized_path,
+            ) {
+                comment.resource_id = resource_id;
+                comment.depth = depth_from_materialized_path(&materialized_path);
+                comment.ancestor_comment_ids =
+                    ancestor_comment_ids_from_materialized_path(&materialized_path);
+                comment.materialized_path = materialized_path;
+                moved_count += 1;
+            }
+        }
+
+        if let Some(comment) = comments.get_mut(&comment_id) {
+            comment.parent_comment_id = new_parent_comment_id;
+            comment.comment_type = match new_parent_comment_id {
+                Some(_) => CommentType::Branch,
+                None => CommentType::Root,
+            };
+        }
+
+        if let Some(parent_comment_id) =
+            parent_comment_id_from_materialized_path(&comment_materialized_path)
+        {
+            if let Some(parent_comment) = comments.get_mut(&parent_comment_id) {
+                parent_comment
+                    .branch_comment_ids
+                    .retain(|branch_comment_id| branch_comment_id != &comment_id);
+                parent_comment.direct_reply_count =
+                    parent_comment.direct_reply_count.saturating_sub(1);
+            }
+        }
+
+        for ancestor_comment_id in
+            ancestor_comment_ids_from_materialized_path(&comment_materialized_path)
+        {
+            if let Some(ancestor_comment) = comments.get_mut(&ancestor_comment_id) {
+                ancestor_comment.total_descendant_count = ancestor_comment
+                    .total_descendant_count
+                    .saturating_sub(moved_count);
+            }
+        }
+
+        // the moved comment takes its place among the replies by when it was made
+        if let Some(new_parent_comment_id) = new_parent_comment_id {
+            let mut branch_comments: Vec<(DateTime<Utc>, Uuid)> = comments
+                .values()
+                .filter(|comment| comment.parent_comment_id == Some(new_parent_comment_id))
+                .map(|comment| (comment.commented_timestamp, comment.comment_id))
+                .collect();
+            branch_comments.sort_by_key(|(commented_timestamp, _)| *commented_timestamp);
+
+            if let Some(new_parent_comment) = comments.get_mut(&new_parent_comment_id) {
+                new_parent_comment.branch_comment_ids = branch_comments
+                    .into_iter()
+                    .map(|(_, comment_id)| comment_id)
+                    .collect();
+                new_parent_comment.direct_reply_count += 1;
+            }
+        }
+
+        for ancestor_comment_id in
+            ancestor_comment_ids_from_materialized_path(&new_materialized_path)
+        {
+            if let Some(ancestor_comment) = comments.get_mut(&ancestor_comment_id) {
+                ancestor_comment.total_descendant_count += moved_count;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn rebuild_branch_comment_ids(&self, current_path: String) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+
+        // children of every comment below the path, oldest first
+        let mut branch_comments: Vec<(Uuid, DateTime<Utc>, Uuid)> = comments
+            .values()
+            .filter(|comment| comment.materialized_path.starts_with(&current_path))
+            .filter_map(|comment| {
+                comment.parent_comment_id.map(|parent_comment_id| {
+                    (
+                        parent_comment_id,
+                        comment.commented_timestamp,
+                        comment.comment_id,
+                    )
+                })
+            })
+            .collect();
+        branch_comments.sort_by_key(|(_, commented_timestamp, _)| *commented_timestamp);
+
+        for comment in comments.values_mut() {
+            if comment.materialized_path.starts_with(&current_path) {
+                comment.branch_comment_ids.clear();
+            }
+        }
+
+        for (parent_comment_id, _, comment_id) in branch_comments {
+            if let Some(parent_comment) = comments.get_mut(&parent_comment_id) {
+                if parent_comment.materialized_path.starts_with(&current_path) {
+                    parent_comment.branch_comment_ids.push(comment_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn find_comment(&self, comment_id: Uuid) -> Result<Comment> {
+        let comments = self.comments.read().unwrap();
+
+        comments
+            .get(&comment_id)
+            .cloned()
+            .ok_or(anyhow::Error::msg("comment not found"))
+    }
+
+    async fn find_comment_reactions(
+        &self,
+        comment_id: Uuid,
+        limit: Option<u32>,
+    ) -> Result<Vec<CommentReaction>> {
+        let reactions = self.reactions.read().unwrap();
+
+        let mut results = reactions.get(&comment_id).cloned().unwrap_or_default();
+
+        if let Some(l) = limit {
+            results.truncate(l as usize);
+        }
+
+        Ok(results)
+    }
+
+    async fn find_reactions_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, Vec<CommentReaction>>> {
+        let reactions = self.reactions.read().unwrap();
+
+        Ok(comment_ids
+            .into_iter()
+            .filter_map(|comment_id| {
+                reactions
+                    .get(&comment_id)
+                    .filter(|comment_reactions| !comment_reactions.is_empty())
+                    .map(|comment_reactions| (comment_id, comment_reactions.clone()))
+            })
+            .collect())
+    }
+
+    async fn find_replies_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+        sort: CommentSort,
+        limit: u32,
+    ) -> Result<HashMap<Uuid, Vec<Comment>>> {
+        let comments = self.comments.read().unwrap();
+
+        let mut results: HashMap<Uuid, Vec<Comment>> = HashMap::new();
+        for comment in comments.values() {
+            if let Some(parent_comment_id) = comment.parent_comment_id {
+                if comment_ids.contains(&parent_comment_id) {
+                    results
+                        .entry(parent_comment_id)
+                        .or_default()
+                        .push(comment.clone());
+                }
+            }
+        }
+
+        for replies in results.values_mut() {
+            replies.sort_by(|a, b| compare_sort_keys(sort, &sort_key(sort, a), &sort_key(sort, b)));
+            replies.truncate(limit as usize);
+        }
+
+        Ok(results)
+    }
+
+    async fn find_next_level_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        limit: Option<u32>,
+        after: Option<CommentCursor>,
+    ) -> Result<Vec<Comment>> {
+        let comments = self.comments.read().unwrap();
+        let after = after.map(|after| {
+            (
+                after.score.unwrap_or_default(),
+                after.commented_timestamp,
+                after.comment_id,
+            )
+        });
+
+        let mut results: Vec<Comment> = comments
+            .values()
+            .filter(|comment| is_next_level_path(&current_path, &comment.materialized_path))
+            .filter(|comment| match &after {
+                Some(after) => {
+                    compare_sort_keys(sort, &sort_key(sort, comment), after) == Ordering::Greater
+                }
+                None => true,
+            })
+            .cloned()
+            .collect();
+
+        results.sort_by(|a, b| compare_sort_keys(sort, &sort_key(sort, a), &sort_key(sort, b)));
+
+        if let Some(l) = limit {
+            results.truncate(l as usize);
+        }
+
+        Ok(results)
+    }
+
+    async fn find_all_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        max_depth: Option<u32>,
+    ) -> Result<Vec<Comment>> {
+        let comments = self.comments.read().unwrap();
+
+        let mut results: Vec<Comment> = comments
+            .values()
+            .filter(|comment| comment.materialized_path.starts_with(&current_path))
+            .filter(|comment| max_depth.is_none_or(|max_depth| comment.depth <= max_depth))
+            .cloned()
+            .collect();
+
+        // path length ascending, then in the requested order
+        results.sort_by(|a, b| {
+            let a_path_length = a.materialized_path.chars().count();
+            let b_path_length = b.materialized_path.chars().count();
+            a_path_length
+                .cmp(&b_path_length)
+                .then_with(|| compare_sort_keys(sort, &sort_key(sort, a), &sort_key(sort, b)))
+        });
+
+        Ok(results)
+    }
+
+    async fn find_all_comments_page(
+        &self,
+        current_path: String,
+        limit: u32,
+        after: Option<Comment>,
+    ) -> Result<Vec<Comment>> {
+        let comments = self.comments.read().unwrap();
+
+        // path length ascending, then oldest first
+        let page_key = |comment: &Comment| {
+            (
+                comment.materialized_path.chars().count(),
+                comment.commented_timestamp,
+                comment.comment_id,
+            )
+        };
+        let after_key = after.as_ref().map(page_key);
+
+        let mut results: Vec<Comment> = comments
+            .values()
+            .filter(|comment| comment.materialized_path.starts_with(&current_path))
+            .filter(|comment| after_key.is_none_or(|after_key| page_key(comment) > after_key))
+            .cloned()
+            .collect();
+
+        results.sort_by_key(page_key);
+        results.truncate(limit as usize);
+
+        Ok(results)
+    }
+
+    async fn find_expired_tombstones(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Comment>> {
+        let comments = self.comments.read().unwrap();
+
+        Ok(comments
+            .values()
+            .filter(|comment| {
+                comment.deleted
+                    && comment
+                        .deleted_timestamp
+                        .is_some_and(|deleted_timestamp| deleted_timestamp < deleted_before)
+            })
+            .cloned()
+            .collect())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_resource(&self, resource: Resource) -> Result<()> {
+        let mut resources = self.resources.write().unwrap();
+
+        if resources.contains_key(&resource.resource_id) {
+            return Err(anyhow::anyhow!("error inserting the document"));
+        }
+
+        resources.insert(resource.resource_id, resource);
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn update_resource(&self, resource: Resource) -> Result<()> {
+        let mut resources = self.resources.write().unwrap();
+
+        match resources.get_mut(&resource.resource_id) {
+            Some(stored_resource) => *stored_resource = resource,
+            None => info!("no resource documentations updated"),
+        }
+
+        Ok(())
+    }
+
+    async fn find_resource(&self, resource_id: Uuid) -> Result<Resource> {
+        let resources = self.resources.read().unwrap();
+
+        resources
+            .get(&resource_id)
+            .cloned()
+            .ok_or(anyhow::Error::msg("resource not found"))
+    }
+
+    async fn find_resource_settings(&self, resource_id: Uuid) -> Result<ResourceSettings> {
+        let resources = self.resources.read().unwrap();
+
+        Ok(resources
+            .get(&resource_id)
+            .map(|resource| resource.settings.clone())
+            .unwrap_or_default())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn merge_resources(&self, resource_alias: ResourceAlias) -> Result<()> {
+        let mut comments = self.comments.write().unwrap();
+        let mut resources = self.resources.write().unwrap();
+        let mut resource_aliases = self.resource_aliases.write().unwrap();
+
+        if resource_aliases.contains_key(&resource_alias.alias_resource_id) {
+            return Err(anyhow::anyhow!("error inserting the document"));
+        }
+
+        let alias_resource_path = resource_alias.alias_resource_id.to_string();
+        let resource_path = resource_alias.resource_id.to_string();
+        for comment in comments.values_mut() {
+            if comment.resource_id == resource_alias.alias_resource_id {
+                if let Some(materialized_path) = rebase_materialized_path(
+                    &comment.materialized_path,
+                    &alias_resource_path,
+                    &resource_path,
+                ) {
+                    comment.materialized_path = materialized_path;
+                }
+                comment.resource_id = resource_alias.resource_id;
+            }
+        }
+
+        for stored_resource_alias in resource_aliases.values_mut() {
+            if stored_resource_alias.resource_id == resource_alias.alias_resource_id {
+                stored_resource_alias.resource_id = resource_alias.resource_id;
+            }
+        }
+
+        resources.remove(&resource_alias.alias_resource_id);
+        resource_aliases.insert(resource_alias.alias_resource_id, resource_alias);
+
+        Ok(())
+    }
+
+    async fn resolve_resource_id(&self, resource_id: Uuid) -> Result<Uuid> {
+        let resource_aliases = self.resource_aliases.read().unwrap();
+
+        Ok(resource_aliases
+            .get(&resource_id)
+            .map_or(resource_id, |resource_alias| resource_alias.resource_id))
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_resource_alias(&self, resource_alias: ResourceAlias) -> Result<()> {
+        let mut resource_aliases = self.resource_aliases.write().unwrap();
+
+        if resource_aliases.contains_key(&resource_alias.alias_resource_id) {
+            return Err(anyhow::anyhow!("error inserting the document"));
+        }
+
+        resource_aliases.insert(resource_alias.alias_resource_id, resource_alias);
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn delete_resource_alias(&self, alias_resource_id: Uuid) -> Result<()> {
+        let mut resource_aliases = self.resource_aliases.write().unwrap();
+
+        if resource_aliases.remove(&alias_resource_id).is_none() {
+            info!("no resource alias documentations deleted")
+        }
+
+        Ok(())
+    }
+
+    async fn find_resource_aliases(&self, resource_id: Uuid) -> Result<Vec<ResourceAlias>> {
+        let resource_aliases = self.resource_aliases.read().unwrap();
+
+        let mut results: Vec<ResourceAlias> = resource_aliases
+            .values()
+            .filter(|resource_alias| resource_alias.resource_id == resource_id)
+            .cloned()
+            .collect();
+        results.sort_by_key(|resource_alias| {
+            (
+                resource_alias.created_timestamp,
+                resource_alias.alias_resource_id,
+            )
+        });
+
+        Ok(results)
+    }
+}
@@ -0,0 +1,747 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::{AggregateOptions, FindOptions, UpdateOptions},
+    Client, ClientSession, Collection, Database,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    common::utils::{
+        ancestor_comment_ids_from_materialized_path, comment_id_from_materialized_path,
+        depth_from_materialized_path, parent_comment_id_from_materialized_path,
+        resource_id_from_materialized_path,
+    },
+    models::{controversy_score, total_reaction_count, CommentReaction},
+    persistent::mongo::CommentReactionDocument,
+};
+
+/// Collection recording which migrations have been applied to the database.
+const MIGRATIONS_COLLECTION: &str = "schema_migrations";
+
+/// A change to the shape of the stored documents. Migrations must be idempotent: applying one
+/// twice, e.g. when two instances start at the same time, leaves the data as applying it once.
+#[async_trait]
+pub trait MongoMigration: Send + Sync {
+    /// Unique id, prefixed with a sequence number so that ids sort in the order they apply.
+    fn id(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Applies the migration. The session isn't in a transaction, migrations touching every
+    /// document would exceed its limits.
+    async fn up(&self, db: &Database, session: &mut ClientSession) -> Result<()>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AppliedMigration {
+    migration_id: String,
+    description: String,
+    applied_timestamp: DateTime<Utc>,
+}
+
+// how many updates the migrations send to the server at a time
+const UPDATE_BATCH_SIZE: usize = 500;
+
+// sends the update statements, each a document with the filter in `q` and the update in `u`,
+// to the server in a single command
+async fn run_updates(db: &Database, collection_name: &str, updates: Vec<Document>) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let response = db
+        .run_command(doc! { "update": collection_name, "updates": updates }, None)
+        .await?;
+
+    // the command succeeds even when single statements fail, which it reports alongside
+    match response.get_array("writeErrors") {
+        Ok(write_errors) if !write_errors.is_empty() => Err(anyhow::anyhow!(
+            "error updating {} documents: {:?}",
+            collection_name,
+            write_errors
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Every migration, in the order they apply.
+fn mongo_migrations() -> Vec<Box<dyn MongoMigration>> {
+    vec![
+        Box::new(StampCommentSchemaVersion),
+        Box::new(RebuildBranchCommentIds),
+        Box::new(RecountCommentCounters),
+        Box::new(MoveReactionsToOwnCollection),
+        Box::new(StampCommentDeleted),
+        Box::new(StampCommentDeletedTimestamp),
+        Box::new(RegisterResourcesWithSettings),
+        Box::new(StampCommentHierarchy),
+        Box::new(StampCommentAncestorIds),
+        Box::new(StampCommentReactionScores),
+    ]
+}
+
+/// Applies the migrations that haven't been applied yet and returns their ids.
+pub async fn run_mongo_migrations(client: &Client, db: &Database) -> Result<Vec<String>> {
+    let migrations_collection: Collection<AppliedMigration> = db.collection(MIGRATIONS_COLLECTION);
+
+    let applied_migration_ids: Vec<String> = migrations_collection
+        .find(None, None)
+        .await?
+        .try_collect::<Vec<AppliedMigration>>()
+        .await?
+        .into_iter()
+        .map(|applied_migration| applied_migration.migration_id)
+        .collect();
+
+    let mut session = client.start_session(None).await?;
+
+    let mut newly_applied_migration_ids = Vec::new();
+    for migration in mongo_migrations() {
+        if applied_migration_ids
+            .iter()
+            .any(|migration_id| migration_id == migration.id())
+        {
+            continue;
+        }
+
+        info!(
+            "applying migration {}: {}",
+            migration.id(),
+            migration.description()
+        );
+        migration.up(db, &mut session).await?;
+
+        migrations_collection
+            .insert_one(
+                AppliedMigration {
+                    migration_id: migration.id().to_string(),
+                    description: migration.description().to_string(),
+                    applied_timestamp: Utc::now(),
+                },
+                None,
+            )
+            .await?;
+
+        newly_applied_migration_ids.push(migration.id().to_string());
+    }
+
+    Ok(newly_applied_migration_ids)
+}
+
+// ---
+
+struct StampCommentSchemaVersion;
+
+#[async_trait]
+impl MongoMigration for StampCommentSchemaVersion {
+    fn id(&self) -> &'static str {
+        "0001_stamp_comment_schema_version"
+    }
+
+    fn description(&self) -> &'static str {
+        "set schema_version on comments stored before it existed"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<Document> = db.collection("comments");
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$exists": false } },
+                doc! { "$set": { "schema_version": 1 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+// length of a uuid together with the "->" separator in front of it
+const PATH_SEGMENT_LENGTH: i32 = 38;
+
+#[derive(Deserialize)]
+struct BranchCommentIdsGroup {
+    // root comments are grouped under their resource id, which matches no comment
+    #[serde(rename = "_id")]
+    parent_path: String,
+    branch_comment_ids: Vec<Bson>,
+}
+
+struct RebuildBranchCommentIds;
+
+#[async_trait]
+impl MongoMigration for RebuildBranchCommentIds {
+    fn id(&self) -> &'static str {
+        "0002_rebuild_branch_comment_ids"
+    }
+
+    fn description(&self) -> &'static str {
+        "populate branch_comment_ids, which used to be written empty, from materialized paths"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<Document> = db.collection("comments");
+
+        comments_collection
+            .update_many(doc! {}, doc! { "$set": { "branch_comment_ids": [] } }, None)
+            .await?;
+
+        // comments don't carry their parent's id yet, so replies are grouped by their parent's
+        // path. the groups spill to disk rather than fail on large collections, and are written
+        // as they come
+        let pipeline = vec![
+            doc! {
+                "$sort": {
+                    "commented_timestamp": 1  // ascending order, the order replies were appended in
+                }
+            },
+            doc! {
+                "$group": {
+                    "_id": {
+                        "$substrCP": [
+                            "$materialized_path",
+                            0,
+                            {
+                                "$subtract": [
+                                    { "$strLenCP": "$materialized_path" },
+                                    PATH_SEGMENT_LENGTH
+                                ]
+                            }
+                        ]
+                    },
+                    "branch_comment_ids": { "$push": "$comment_id" }
+                }
+            },
+        ];
+        let aggregate_options = AggregateOptions::builder().allow_disk_use(true).build();
+
+        let mut groups = comments_collection
+            .aggregate(pipeline, Some(aggregate_options))
+            .await?
+            .with_type::<BranchCommentIdsGroup>();
+
+        while let Some(group) = groups.try_next().await? {
+            let Some(parent_comment_id) = comment_id_from_materialized_path(&group.parent_path)
+            else {
+                continue;
+            };
+
+            comments_collection
+                .update_one(
+                    doc! { "comment_id": bson::to_bson(&parent_comment_id)? },
+                    doc! { "$set": { "branch_comment_ids": group.branch_comment_ids } },
+                    None,
+                )
+                .await?;
+        }
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 2 } },
+                doc! { "$set": { "schema_version": 2 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+// the part of a comment the migrations below read
+#[derive(Debug, Deserialize)]
+struct CommentPath {
+    comment_id: Uuid,
+    materialized_path: String,
+}
+
+// the parts of a comment the migrations below read, as stored while reactions were embedded in
+// the comment document
+#[derive(Debug, Deserialize)]
+struct CommentWithEmbeddedReactions {
+    comment_id: Uuid,
+    #[serde(default)]
+    reactions: Vec<CommentReaction>,
+}
+
+struct RecountCommentCounters;
+
+#[async_trait]
+impl MongoMigration for RecountCommentCounters {
+    fn id(&self) -> &'static str {
+        "0003_recount_comment_counters"
+    }
+
+    fn description(&self) -> &'static str {
+        "deduplicate reactions and compute the reply and reaction counters of every comment"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<CommentWithEmbeddedReactions> =
+            db.collection("comments");
+
+        // the counters are gathered from the paths alone first, leaving the reactions behind
+        let find_options = FindOptions::builder()
+            .projection(doc! { "comment_id": 1, "materialized_path": 1 })
+            .build();
+        let mut comment_paths = comments_collection
+            .clone_with_type::<CommentPath>()
+            .find(None, Some(find_options))
+            .await?;
+
+        let mut direct_reply_counts: HashMap<Uuid, u32> = HashMap::new();
+        let mut total_descendant_counts: HashMap<Uuid, u32> = HashMap::new();
+        while let Some(comment) = comment_paths.try_next().await? {
+            if let Some(parent_comment_id) =
+                parent_comment_id_from_materialized_path(&comment.materialized_path)
+            {
+                *direct_reply_counts.entry(parent_comment_id).or_default() += 1;
+            }
+
+            for ancestor_comment_id in
+                ancestor_comment_ids_from_materialized_path(&comment.materialized_path)
+            {
+                *total_descendant_counts
+                    .entry(ancestor_comment_id)
+                    .or_default() += 1;
+            }
+        }
+
+        let mut comments = comments_collection.find(None, None).await?;
+
+        let mut updates: Vec<Document> = Vec::new();
+        while let Some(comment) = comments.try_next().await? {
+            // reactions used to be appended unconditionally, keep the first one of each reactor
+            // and emoji
+            let mut kept_reactions: HashSet<(Uuid, String)> = HashSet::new();
+            let reactions: Vec<CommentReaction> = comment
+                .reactions
+                .into_iter()
+                .filter(|reaction| {
+                    kept_reactions.insert((
+                        reaction.reactor.account_id,
+                        reaction.emoji_unified_code.clone(),
+                    ))
+                })
+                .collect();
+
+            let mut reaction_counts: BTreeMap<String, u32> = BTreeMap::new();
+            for reaction in &reactions {
+                *reaction_counts
+                    .entry(reaction.emoji_unified_code.clone())
+                    .or_default() += 1;
+            }
+
+            updates.push(doc! {
+                "q": { "comment_id": bson::to_bson(&comment.comment_id)? },
+                "u": {
+                    "$set": {
+                        "reactions": bson::to_bson(&reactions)?,
+                        "reaction_counts": bson::to_bson(&reaction_counts)?,
+                        "direct_reply_count": direct_reply_counts
+                            .get(&comment.comment_id)
+                            .copied()
+                            .unwrap_or_default(),
+                        "total_descendant_count": total_descendant_counts
+                            .get(&comment.comment_id)
+                            .copied()
+                            .unwrap_or_default(),
+                    }
+                },
+            });
+
+            if updates.len() == UPDATE_BATCH_SIZE {
+                run_updates(db, "comments", std::mem::take(&mut updates)).await?;
+            }
+        }
+        run_updates(db, "comments", updates).await?;
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 3 } },
+                doc! { "$set": { "schema_version": 3 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+struct MoveReactionsToOwnCollection;
+
+#[async_trait]
+impl MongoMigration for MoveReactionsToOwnCollection {
+    fn id(&self) -> &'static str {
+        "0004_move_reactions_to_own_collection"
+    }
+
+    fn description(&self) -> &'static str {
+        "move the reactions embedded in comments to the comment_reactions collection"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<CommentWithEmbeddedReactions> =
+            db.collection("comments");
+        let comment_reactions_collection: Collection<Document> = db.collection("comment_reactions");
+
+        let mut cursor = comments_collection
+            .find(doc! { "reactions": { "$exists": true } }, None)
+            .await?;
+
+        while let Some(comment) = cursor.try_next().await? {
+            for reaction in comment.reactions {
+                // upserting keeps the move idempotent if it is interrupted and applied again.
+                // reaction_counts were computed from the same reactions and stay as they are
+                let reaction_filter = doc! {
+                    "comment_id": bson::to_bson(&comment.comment_id)?,
+                    "reactor.account_id": bson::to_bson(&reaction.reactor.account_id)?,
+                    "emoji_unified_code": &reaction.emoji_unified_code,
+                };
+                let reaction_document =
+                    bson::to_document(&CommentReactionDocument::new(comment.comment_id, reaction))?;
+
+                comment_reactions_collection
+                    .update_one(
+                        reaction_filter,
+                        doc! { "$setOnInsert": reaction_document },
+                        UpdateOptions::builder().upsert(true).build(),
+                    )
+                    .await?;
+            }
+
+            comments_collection
+                .update_one(
+                    doc! { "comment_id": bson::to_bson(&comment.comment_id)? },
+                    doc! { "$unset": { "reactions": "" } },
+                    None,
+                )
+                .await?;
+        }
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 4 } },
+                doc! { "$set": { "schema_version": 4 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+struct StampCommentDeleted;
+
+#[async_trait]
+impl MongoMigration for StampCommentDeleted {
+    fn id(&self) -> &'static str {
+        "0005_stamp_comment_deleted"
+    }
+
+    fn description(&self) -> &'static str {
+        "set deleted on comments stored before comments could be left as tombstones"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<Document> = db.collection("comments");
+
+        comments_collection
+            .update_many(
+                doc! { "deleted": { "$exists": false } },
+                doc! { "$set": { "deleted": false } },
+                None,
+            )
+            .await?;
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 5 } },
+                doc! { "$set": { "schema_version": 5 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+struct StampCommentDeletedTimestamp;
+
+#[async_trait]
+impl MongoMigration for StampCommentDeletedTimestamp {
+    fn id(&self) -> &'static str {
+        "0006_stamp_comment_deleted_timestamp"
+    }
+
+    fn description(&self) -> &'static str {
+        "set deleted_timestamp on comments stored before tombstones could be restored"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<Document> = db.collection("comments");
+
+        // the text and author of earlier tombstones weren't kept, so they can't be restored
+        comments_collection
+            .update_many(
+                doc! { "deleted_timestamp": { "$exists": false } },
+                doc! { "$set": { "deleted_timestamp": null } },
+                None,
+            )
+            .await?;
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 6 } },
+                doc! { "$set": { "schema_version": 6 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+struct RegisterResourcesWithSettings;
+
+#[async_trait]
+impl MongoMigration for RegisterResourcesWithSettings {
+    fn id(&self) -> &'static str {
+        "0007_register_resources_with_settings"
+    }
+
+    fn description(&self) -> &'static str {
+        "turn the resources that only had settings into registered resources without a title"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let resources_collection: Collection<Document> = db.collection("resources");
+
+        resources_collection
+            .update_many(
+                doc! { "title": { "$exists": false } },
+                doc! { "$set": { "title": "" } },
+                None,
+            )
+            .await?;
+
+        // when the settings were first stored isn't known, the resources count as registered now
+        resources_collection
+            .update_many(
+                doc! { "created_timestamp": { "$exists": false } },
+                doc! { "$set": { "created_timestamp": bson::to_bson(&Utc::now())? } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+struct StampCommentHierarchy;
+
+#[async_trait]
+impl MongoMigration for StampCommentHierarchy {
+    fn id(&self) -> &'static str {
+        "0008_stamp_comment_hierarchy"
+    }
+
+    fn description(&self) -> &'static str {
+        "set resource_id, parent_comment_id and depth on comments from their materialized paths"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<CommentPath> = db.collection("comments");
+
+        let mut cursor = comments_collection
+            .find(doc! { "resource_id": { "$exists": false } }, None)
+            .await?;
+
+        while let Some(comment) = cursor.try_next().await? {
+            let resource_id = resource_id_from_materialized_path(&comment.materialized_path)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "invalid materialized path of comment {}",
+                        comment.comment_id
+                    )
+                })?;
+
+            comments_collection
+                .update_one(
+                    doc! { "comment_id": bson::to_bson(&comment.comment_id)? },
+                    doc! {
+                        "$set": {
+                            "resource_id": bson::to_bson(&resource_id)?,
+                            "parent_comment_id": bson::to_bson(
+                                &parent_comment_id_from_materialized_path(
+                                    &comment.materialized_path,
+                                ),
+                            )?,
+                            "depth": depth_from_materialized_path(&comment.materialized_path),
+                        }
+                    },
+                    None,
+                )
+                .await?;
+        }
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 7 } },
+                doc! { "$set": { "schema_version": 7 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+struct StampCommentAncestorIds;
+
+#[async_trait]
+impl MongoMigration for StampCommentAncestorIds {
+    fn id(&self) -> &'static str {
+        "0009_stamp_comment_ancestor_ids"
+    }
+
+    fn description(&self) -> &'static str {
+        "set ancestor_comment_ids on comments from their materialized paths"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<CommentPath> = db.collection("comments");
+
+        let mut cursor = comments_collection
+            .find(doc! { "ancestor_comment_ids": { "$exists": false } }, None)
+            .await?;
+
+        while let Some(comment) = cursor.try_next().await? {
+            comments_collection
+                .update_one(
+                    doc! { "comment_id": bson::to_bson(&comment.comment_id)? },
+                    doc! {
+                        "$set": {
+                            "ancestor_comment_ids": bson::to_bson(
+                                &ancestor_comment_ids_from_materialized_path(
+                                    &comment.materialized_path,
+                                ),
+                            )?,
+                        }
+                    },
+                    None,
+                )
+                .await?;
+        }
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 8 } },
+                doc! { "$set": { "schema_version": 8 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+// ---
+
+// the part of a comment the migration below reads
+#[derive(Debug, Deserialize)]
+struct CommentReactionCounts {
+    comment_id: Uuid,
+    #[serde(default)]
+    reaction_counts: BTreeMap<String, u32>,
+}
+
+struct StampCommentReactionScores;
+
+#[async_trait]
+impl MongoMigration for StampCommentReactionScores {
+    fn id(&self) -> &'static str {
+        "0010_stamp_comment_reaction_scores"
+    }
+
+    fn description(&self) -> &'static str {
+        "set total_reaction_count and controversy_score on comments from their reaction counts"
+    }
+
+    async fn up(&self, db: &Database, _session: &mut ClientSession) -> Result<()> {
+        let comments_collection: Collection<CommentReactionCounts> = db.collection("comments");
+
+        let mut cursor = comments_collection
+            .find(doc! { "total_reaction_count": { "$exists": false } }, None)
+            .await?;
+
+        while let Some(comment) = cursor.try_next().await? {
+            comments_collection
+                .update_one(
+                    doc! { "comment_id": bson::to_bson(&comment.comment_id)? },
+                    doc! {
+                        "$set": {
+                            "total_reaction_count": i64::from(
+                                total_reaction_count(&comment.reaction_counts),
+                            ),
+                            "controversy_score": controversy_score(&comment.reaction_counts),
+                        }
+                    },
+                    None,
+                )
+                .await?;
+        }
+
+        comments_collection
+            .update_many(
+                doc! { "schema_version": { "$lt": 9 } },
+                doc! { "$set": { "schema_version": 9 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migration_ids_are_unique_and_sorted() {
+        let migration_ids: Vec<&str> = mongo_migrations()
+            .iter()
+            .map(|migration| migration.id())
+            .collect();
+
+        // sorting fails on ids out of order, dedup on repeated ones
+        let mut sorted_migration_ids = migration_ids.clone();
+        sorted_migration_ids.sort_unstable();
+        sorted_migration_ids.dedup();
+        assert_eq!(sorted_migration_ids, migration_ids);
+    }
+}
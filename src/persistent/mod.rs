@@ -1,40 +1,233 @@
 use anyhow::Result;
-use axum_macros::FromRef;
-mod mongo;
-use mongodb::{bson::doc, options::ClientOptions, Client};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Debug, str::FromStr, time::Duration};
+use uuid::Uuid;
 
-#[derive(FromRef, Serialize, Deserialize, Clone, Debug)]
-pub struct MongoDbConfig {
-    pub mongo_db_name: String,
-}
+mod memory;
+mod migrations;
+mod mongo;
+mod postgres;
+mod sqlite;
 
-#[derive(Debug)]
-pub struct PersistentLayer {
-    pub mongo_client: Client,
+pub use memory::InMemoryCommentStore;
+pub use mongo::{init_mongo_connection, MongoCommentStore, MongoDbConfig};
+pub use postgres::{init_postgres_connection, PostgresCommentStore};
+pub use sqlite::{init_sqlite_connection, SqliteCommentStore};
 
-    pub mongo_config: MongoDbConfig,
+use crate::models::{
+    Comment, CommentCursor, CommentReaction, CommentSort, PendingComment, Resource, ResourceAlias,
+    ResourceSettings,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Mongo,
+    Postgres,
+    Sqlite,
+    Memory,
 }
 
-pub async fn init_mongo_connection(
-    connection_string: &str,
-    max_pool_size: Option<u32>,
-) -> Result<Client> {
-    let mut options = ClientOptions::parse(connection_string).await?;
+impl FromStr for StorageBackend {
+    type Err = anyhow::Error;
 
-    if let Some(max_pool_size) = max_pool_size {
-        options.max_pool_size = Some(max_pool_size);
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "mongo" | "mongodb" => Ok(StorageBackend::Mongo),
+            "postgres" | "postgresql" => Ok(StorageBackend::Postgres),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            "memory" => Ok(StorageBackend::Memory),
+            _ => Err(anyhow::anyhow!("unknown storage backend: {}", s)),
+        }
     }
+}
+
+/// How many times a transaction that conflicted with a concurrent one is run before giving up.
+pub(crate) const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+
+/// Time to wait before running a transaction again, doubling with every attempt.
+pub(crate) fn transaction_retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(10 * 2u64.pow(attempt))
+}
+
+/// Storage backend for comments. Handlers are generic over this trait so the server can run
+/// against any database that can keep comments addressable by their materialized path.
+#[async_trait]
+pub trait CommentStore: Debug + Send + Sync + 'static {
+    /// Brings the stored data up to the shape this version of commenter expects and returns the
+    /// ids of the migrations that were applied.
+    async fn migrate(&self) -> Result<Vec<String>>;
+
+    /// Inserts the comment and, for a branch comment, appends its id to the
+    /// `branch_comment_ids` of the comment it was branched from. Writes that span several
+    /// comments are atomic, so inserting a branch comment fails if the comment it was branched
+    /// from is deleted concurrently.
+    async fn insert_comment(&self, comment: Comment) -> Result<()>;
+
+    /// Holds a comment made on a pre-moderated resource until it is approved or rejected.
+    async fn insert_pending_comment(&self, pending_comment: PendingComment) -> Result<()>;
+
+    async fn find_pending_comment(&self, comment_id: Uuid) -> Result<PendingComment>;
+
+    /// Finds the comments held for the resource, oldest first.
+    async fn find_pending_comments(&self, resource_id: Uuid) -> Result<Vec<PendingComment>>;
+
+    /// Drops a held comment, once it is published or rejected.
+    async fn delete_pending_comment(&self, comment_id: Uuid) -> Result<()>;
+
+    /// Stores the reaction apart from the comment and counts it in the comment's
+    /// `reaction_counts`. A reactor reacts with a given emoji at most once.
+    async fn append_reaction_to_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_append: CommentReaction,
+    ) -> Result<()>;
+
+    async fn remove_reaction_from_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_remove: CommentReaction,
+    ) -> Result<()>;
+
+    async fn update_comment_text(&self, comment_id: Uuid, new_comment_text: String) -> Result<()>;
+
+    /// Replaces the text and the author of the comment with a tombstone and marks it deleted at
+    /// the given time, leaving its replies, counters and reactions in place. The original text
+    /// and author are kept apart until they are discarded, so that the comment can be restored.
+    /// Tombstoning a tombstone does nothing.
+    async fn tombstone_comment(
+        &self,
+        comment_id: Uuid,
+        deleted_timestamp: DateTime<Utc>,
+    ) -> Result<()>;
+
+    /// Puts back the text and the author a tombstone replaced. Fails if they were discarded.
+    async fn restore_comment(&self, comment_id: Uuid) -> Result<()>;
+
+    /// Drops the text and the author kept for a tombstone, after which it can't be restored.
+    async fn discard_deleted_comment_content(&self, comment_id: Uuid) -> Result<()>;
+
+    /// Finds the tombstones deleted before the given time, whether or not their text and author
+    /// are still kept.
+    async fn find_expired_tombstones(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Comment>>;
 
-    let client = Client::with_options(options.clone())?;
+    /// Deletes the comment at the given materialized path together with all of its descendants,
+    /// and removes its id from the `branch_comment_ids` of the comment it was branched from.
+    async fn prune_comments(&self, comment_materialized_path: String) -> Result<()>;
 
-    let db_name = options
-        .default_database
-        .clone()
-        .expect("database name must be specified in the connection string");
+    /// Moves the comment at the given materialized path together with all of its descendants
+    /// below the given path of a resource or comment, and moves its id and the counters of its
+    /// descendants from its old ancestors to its new ones. Fails if the new parent lies in the
+    /// moved subtree.
+    async fn move_comments(
+        &self,
+        comment_materialized_path: String,
+        new_parent_materialized_path: String,
+    ) -> Result<()>;
 
-    let db = client.database(&db_name);
-    db.run_command(doc! { "ping": 1 }, None).await?;
+    /// Recomputes `branch_comment_ids` from the materialized paths for every comment below the
+    /// given path, for data written before the lists were maintained.
+    async fn rebuild_branch_comment_ids(&self, current_path: String) -> Result<()>;
 
-    Ok(client)
+    async fn find_comment(&self, comment_id: Uuid) -> Result<Comment>;
+
+    /// Finds the reactions to the given comment, oldest first.
+    async fn find_comment_reactions(
+        &self,
+        comment_id: Uuid,
+        limit: Option<u32>,
+    ) -> Result<Vec<CommentReaction>>;
+
+    /// Finds the reactions to each of the given comments, oldest first. Comments nobody reacted
+    /// to are left out.
+    async fn find_reactions_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, Vec<CommentReaction>>>;
+
+    /// Finds at most `limit` replies to each of the given comments, in the order
+    /// `find_next_level_comments` lists them. Comments without replies are left out.
+    async fn find_replies_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+        sort: CommentSort,
+        limit: u32,
+    ) -> Result<HashMap<Uuid, Vec<Comment>>>;
+
+    /// Finds the comments exactly one level below the given materialized path in the given sort
+    /// order, ties broken by timestamp and then by id in the same direction. With a cursor,
+    /// starts after the comment it points at.
+    async fn find_next_level_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        limit: Option<u32>,
+        after: Option<CommentCursor>,
+    ) -> Result<Vec<Comment>>;
+
+    /// Finds every comment below the given materialized path, ordered by path length ascending
+    /// and then in the given sort order. With a maximum depth, leaves out the comments deeper
+    /// than it in their thread.
+    async fn find_all_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        max_depth: Option<u32>,
+    ) -> Result<Vec<Comment>>;
+
+    /// Finds at most `limit` comments below the given materialized path, in the order
+    /// `find_all_comments` lists them oldest first. With a comment of the listing, starts after
+    /// it, so that a whole thread can be read a page at a time.
+    async fn find_all_comments_page(
+        &self,
+        current_path: String,
+        limit: u32,
+        after: Option<Comment>,
+    ) -> Result<Vec<Comment>>;
+
+    /// Registers the resource. Fails if it is registered already.
+    async fn insert_resource(&self, resource: Resource) -> Result<()>;
+
+    /// Replaces the record of a registered resource, settings included.
+    async fn update_resource(&self, resource: Resource) -> Result<()>;
+
+    async fn find_resource(&self, resource_id: Uuid) -> Result<Resource>;
+
+    /// Finds the settings of the resource, or the default settings if it isn't registered.
+    async fn find_resource_settings(&self, resource_id: Uuid) -> Result<ResourceSettings>;
+
+    /// Moves every comment of the source resource to the target resource, keeping everything
+    /// but their materialized paths and resource ids, and leaves the source id behind as an alias
+    /// of the target. Aliases of the source become aliases of the target, and the source's
+    /// registration is removed.
+    async fn merge_resources(&self, resource_alias: ResourceAlias) -> Result<()>;
+
+    /// Returns the resource the given id is an alias of, or the id itself if it isn't one.
+    async fn resolve_resource_id(&self, resource_id: Uuid) -> Result<Uuid>;
+
+    /// Registers the alias. Fails if the id is an alias already.
+    async fn insert_resource_alias(&self, resource_alias: ResourceAlias) -> Result<()>;
+
+    /// Removes the alias, which makes the id stand for a resource of its own again.
+    async fn delete_resource_alias(&self, alias_resource_id: Uuid) -> Result<()>;
+
+    /// Finds the aliases of the resource, oldest first.
+    async fn find_resource_aliases(&self, resource_id: Uuid) -> Result<Vec<ResourceAlias>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_retry_delay_doubles() {
+        let delays: Vec<Duration> = (1..MAX_TRANSACTION_ATTEMPTS)
+            .map(transaction_retry_delay)
+            .collect();
+
+        assert_eq!(delays[0], Duration::from_millis(20));
+        assert!(delays.windows(2).all(|pair| pair[1] == pair[0] * 2));
+    }
 }
@@ -1,201 +1,1788 @@
 use anyhow::Result;
-use futures::stream::TryStreamExt;
+use async_trait::async_trait;
+use axum_macros::FromRef;
+use chrono::{DateTime, Utc};
+use futures::{stream::TryStreamExt, FutureExt};
 use mongodb::{
     bson::{
         self, doc,
         Bson::{self, Null},
         Document,
     },
-    options::FindOptions,
-    Collection,
+    error::{ErrorKind, WriteFailure},
+    options::{AggregateOptions, ClientOptions, FindOptions, IndexOptions},
+    Client, ClientSession, Collection, Database, IndexModel,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use tracing::{info, instrument};
 use uuid::Uuid;
 
 use crate::{
-    models::{Comment, CommentReaction},
-    persistent::PersistentLayer,
+    common::utils::{
+        ancestor_comment_ids_from_materialized_path, append_uuid_to_materialized_path,
+        comment_id_from_materialized_path, depth_from_materialized_path,
+        materialized_path_to_uuid_list, parent_comment_id_from_materialized_path,
+        resource_id_from_materialized_path,
+    },
+    models::{
+        controversy_score, total_reaction_count, Comment, CommentCursor, CommentReaction,
+        CommentReactor, CommentSort, CommentType, Commenter, PendingComment, Resource,
+        ResourceAlias, ResourceSettings, DELETED_COMMENT_TEXT,
+    },
+    persistent::{migrations::run_mongo_migrations, CommentStore},
 };
 
-impl PersistentLayer {
+#[derive(FromRef, Serialize, Deserialize, Clone, Debug)]
+pub struct MongoDbConfig {
+    pub mongo_db_name: String,
+}
+
+#[derive(Debug)]
+pub struct MongoCommentStore {
+    pub mongo_client: Client,
+
+    pub mongo_config: MongoDbConfig,
+}
+
+pub async fn init_mongo_connection(
+    connection_string: &str,
+    max_pool_size: Option<u32>,
+) -> Result<Client> {
+    let mut options = ClientOptions::parse(connection_string).await?;
+
+    if let Some(max_pool_size) = max_pool_size {
+        options.max_pool_size = Some(max_pool_size);
+    }
+
+    let client = Client::with_options(options.clone())?;
+
+    let db_name = options
+        .default_database
+        .clone()
+        .expect("database name must be specified in the connection string");
+
+    let db = client.database(&db_name);
+    db.run_command(doc! { "ping": 1 }, None).await?;
+
+    Ok(client)
+}
+
+// error code the server answers with when listing the indexes of a collection that doesn't exist
+const NAMESPACE_NOT_FOUND: i32 = 26;
+
+// indexes the comment queries rely on. subtrees and next levels are looked up by equality on
+// resource_id, parent_comment_id or the multikey ancestor_comment_ids rather than by matching
+// materialized paths, and next levels are listed in every order straight from an index
+fn comment_indexes() -> Vec<IndexModel> {
+    vec![
+        IndexModel::builder()
+            .keys(doc! { "comment_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("comment_id_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(
+                doc! { "resource_id": 1, "depth": 1, "commented_timestamp": -1, "comment_id": -1 },
+            )
+            .options(
+                IndexOptions::builder()
+                    .name("resource_id_depth_commented_timestamp_comment_id".to_string())
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "parent_comment_id": 1, "commented_timestamp": -1, "comment_id": -1 })
+            .options(
+                IndexOptions::builder()
+                    .name("parent_comment_id_commented_timestamp_comment_id".to_string())
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {
+                "resource_id": 1,
+                "depth": 1,
+                "total_reaction_count": -1,
+                "commented_timestamp": -1,
+                "comment_id": -1,
+            })
+            .options(
+                IndexOptions::builder()
+                    .name(
+                        "resource_id_depth_total_reaction_count_commented_timestamp_comment_id"
+                            .to_string(),
+                    )
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {
+                "parent_comment_id": 1,
+                "total_reaction_count": -1,
+                "commented_timestamp": -1,
+                "comment_id": -1,
+            })
+            .options(
+                IndexOptions::builder()
+                    .name(
+                        "parent_comment_id_total_reaction_count_commented_timestamp_comment_id"
+                            .to_string(),
+                    )
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {
+                "resource_id": 1,
+                "depth": 1,
+                "controversy_score": -1,
+                "commented_timestamp": -1,
+                "comment_id": -1,
+            })
+            .options(
+                IndexOptions::builder()
+                    .name(
+                        "resource_id_depth_controversy_score_commented_timestamp_comment_id"
+                            .to_string(),
+                    )
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {
+                "parent_comment_id": 1,
+                "controversy_score": -1,
+                "commented_timestamp": -1,
+                "comment_id": -1,
+            })
+            .options(
+                IndexOptions::builder()
+                    .name(
+                        "parent_comment_id_controversy_score_commented_timestamp_comment_id"
+                            .to_string(),
+                    )
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "ancestor_comment_ids": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("ancestor_comment_ids".to_string())
+                    .build(),
+            )
+            .build(),
+        // only tombstones are purged once their restore window expires
+        IndexModel::builder()
+            .keys(doc! { "deleted_timestamp": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("deleted_timestamp_of_tombstones".to_string())
+                    .partial_filter_expression(doc! { "deleted": true })
+                    .build(),
+            )
+            .build(),
+    ]
+}
+
+// reactions live in their own collection so that comments don't grow with their reactions.
+// a reactor reacts with a given emoji at most once
+fn comment_reaction_indexes() -> Vec<IndexModel> {
+    vec![IndexModel::builder()
+        .keys(doc! { "comment_id": 1, "reactor.account_id": 1, "emoji_unified_code": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("comment_id_reactor_emoji_unique".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build()]
+}
+
+// text and author of tombstones are looked up by their comment
+fn deleted_comment_content_indexes() -> Vec<IndexModel> {
+    vec![IndexModel::builder()
+        .keys(doc! { "comment_id": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("comment_id_unique".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build()]
+}
+
+// held comments are approved or rejected by their id, and listed per resource oldest first
+fn pending_comment_indexes() -> Vec<IndexModel> {
+    vec![
+        IndexModel::builder()
+            .keys(doc! { "comment_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("comment_id_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "resource_id": 1, "commented_timestamp": 1, "comment_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("resource_id_commented_timestamp_comment_id".to_string())
+                    .build(),
+            )
+            .build(),
+    ]
+}
+
+// resources are looked up by their id, and have at most one document each
+fn resource_indexes() -> Vec<IndexModel> {
+    vec![IndexModel::builder()
+        .keys(doc! { "resource_id": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("resource_id_unique".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build()]
+}
+
+// aliases are looked up by their id, and repointed by the resource they stand for
+fn resource_alias_indexes() -> Vec<IndexModel> {
+    vec![
+        IndexModel::builder()
+            .keys(doc! { "alias_resource_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("alias_resource_id_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "resource_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("resource_id".to_string())
+                    .build(),
+            )
+            .build(),
+    ]
+}
+
+// creates the given indexes that don't exist on the collection yet and returns their names
+async fn ensure_collection_indexes(
+    collection: &Collection<Document>,
+    indexes: Vec<IndexModel>,
+) -> Result<Vec<String>> {
+    let existing_index_names = match collection.list_index_names().await {
+        Ok(index_names) => index_names,
+        Err(e) => match *e.kind {
+            ErrorKind::Command(ref command_error) if command_error.code == NAMESPACE_NOT_FOUND => {
+                vec![]
+            }
+            _ => return Err(e.into()),
+        },
+    };
+
+    let missing_indexes: Vec<IndexModel> = indexes
+        .into_iter()
+        .filter(|index| {
+            index
+                .options
+                .as_ref()
+                .and_then(|options| options.name.as_ref())
+                .map(|name| !existing_index_names.contains(name))
+                .unwrap_or(true)
+        })
+        .collect();
+
+    if missing_indexes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let create_result = collection.create_indexes(missing_indexes, None).await?;
+
+    Ok(create_result.index_names)
+}
+
+impl MongoCommentStore {
+    /// Creates the indexes of the `comments`, `comment_reactions`, `deleted_comment_contents`,
+    /// `pending_comments`, `resources` and `resource_aliases` collections that don't exist yet
+    /// and returns the names of the ones it created.
+    pub async fn ensure_indexes(&self) -> Result<Vec<String>> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+
+        let mut created_index_names =
+            ensure_collection_indexes(&db.collection("comments"), comment_indexes()).await?;
+        created_index_names.extend(
+            ensure_collection_indexes(
+                &db.collection("comment_reactions"),
+                comment_reaction_indexes(),
+            )
+            .await?,
+        );
+        created_index_names.extend(
+            ensure_collection_indexes(
+                &db.collection("deleted_comment_contents"),
+                deleted_comment_content_indexes(),
+            )
+            .await?,
+        );
+        created_index_names.extend(
+            ensure_collection_indexes(
+                &db.collection("pending_comments"),
+                pending_comment_indexes(),
+            )
+            .await?,
+        );
+        created_index_names.extend(
+            ensure_collection_indexes(&db.collection("resources"), resource_indexes()).await?,
+        );
+        created_index_names.extend(
+            ensure_collection_indexes(&db.collection("resource_aliases"), resource_alias_indexes())
+                .await?,
+        );
+
+        Ok(created_index_names)
+    }
+}
+
+// error code the server answers with when a write violates a unique index
+const DUPLICATE_KEY: i32 = 11000;
+
+fn is_duplicate_key_error(error: &mongodb::error::Error) -> bool {
+    matches!(
+        *error.kind,
+        ErrorKind::Write(WriteFailure::WriteError(ref write_error))
+            if write_error.code == DUPLICATE_KEY
+    )
+}
+
+/// A reaction as stored in the `comment_reactions` collection.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct CommentReactionDocument {
+    pub comment_id: Uuid,
+    pub reactor: CommentReactor,
+    pub emoji_unified_code: String,
+}
+
+impl CommentReactionDocument {
+    pub fn new(comment_id: Uuid, comment_reaction: CommentReaction) -> Self {
+        CommentReactionDocument {
+            comment_id,
+            reactor: comment_reaction.reactor,
+            emoji_unified_code: comment_reaction.emoji_unified_code,
+        }
+    }
+}
+
+/// Text and author of a tombstone that can still be restored, as stored in the
+/// `deleted_comment_contents` collection.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct DeletedCommentContentDocument {
+    comment_id: Uuid,
+    commenter: Commenter,
+    comment_text: String,
+    deleted_timestamp: bson::DateTime,
+}
+
+impl From<CommentReactionDocument> for CommentReaction {
+    fn from(document: CommentReactionDocument) -> Self {
+        CommentReaction {
+            reactor: document.reactor,
+            emoji_unified_code: document.emoji_unified_code,
+        }
+    }
+}
+
+fn reaction_count_field(emoji_unified_code: &str) -> String {
+    format!("reaction_counts.{}", emoji_unified_code)
+}
+
+// recomputes the reaction based sort keys of the comment matching the filter from its reaction
+// counts
+async fn update_reaction_scores(
+    comments_collection: &Collection<Document>,
+    comment_filter: Document,
+    session: &mut ClientSession,
+) -> mongodb::error::Result<()> {
+    let reaction_counts: BTreeMap<String, u32> = match comments_collection
+        .find_one_with_session(comment_filter.clone(), None, session)
+        .await?
+        .and_then(|comment| comment.get_document("reaction_counts").ok().cloned())
+    {
+        Some(reaction_counts) => bson::from_document(reaction_counts)?,
+        None => BTreeMap::new(),
+    };
+
+    comments_collection
+        .update_one_with_session(
+            comment_filter,
+            doc! {
+                "$set": {
+                    "total_reaction_count": i64::from(total_reaction_count(&reaction_counts)),
+                    "controversy_score": controversy_score(&reaction_counts),
+                }
+            },
+            None,
+            session,
+        )
+        .await?;
+
+    Ok(())
+}
+
+// the fields listings in the given order sort comments by, most significant first, and whether
+// they are sorted in descending order
+fn sort_fields(sort: CommentSort) -> (&'static [&'static str], bool) {
+    match sort {
+        CommentSort::Newest => (&["commented_timestamp", "comment_id"], true),
+        CommentSort::Oldest => (&["commented_timestamp", "comment_id"], false),
+        CommentSort::Top => (
+            &["total_reaction_count", "commented_timestamp", "comment_id"],
+            true,
+        ),
+        CommentSort::Controversial => (
+            &["controversy_score", "commented_timestamp", "comment_id"],
+            true,
+        ),
+    }
+}
+
+fn sort_document(sort: CommentSort) -> Document {
+    let (fields, descending) = sort_fields(sort);
+
+    fields
+        .iter()
+        .map(|field| {
+            (
+                field.to_string(),
+                Bson::Int32(if descending { -1 } else { 1 }),
+            )
+        })
+        .collect()
+}
+
+// the comments listed after the one the cursor points at: those whose sort key compares past
+// the cursor's on the first field it differs in
+fn after_filter(sort: CommentSort, after: &CommentCursor) -> Result<Document> {
+    let (fields, descending) = sort_fields(sort);
+    let score = after.score.unwrap_or_default();
+
+    let mut values = match sort {
+        CommentSort::Newest | CommentSort::Oldest => vec![],
+        CommentSort::Top => vec![Bson::Int64(score as i64)],
+        CommentSort::Controversial => vec![Bson::Double(score)],
+    };
+    values.push(bson::to_bson(&after.commented_timestamp)?);
+    values.push(bson::to_bson(&after.comment_id)?);
+
+    let operator = if descending { "$lt" } else { "$gt" };
+    let alternatives: Vec<Document> = (0..fields.len())
+        .map(|position| {
+            let mut alternative: Document = fields[..position]
+                .iter()
+                .zip(&values)
+                .map(|(field, value)| (field.to_string(), value.clone()))
+                .collect();
+            alternative.insert(
+                fields[position],
+                doc! { operator: values[position].clone() },
+            );
+            alternative
+        })
+        .collect();
+
+    Ok(doc! { "$or": alternatives })
+}
+
+// the comments at and below the given materialized path. a path of a single uuid is the path of
+// a resource, any longer one the path of a comment
+fn subtree_filter(materialized_path: &str) -> Result<Document> {
+    match materialized_path_to_uuid_list(materialized_path)?.as_slice() {
+        [resource_id] => Ok(doc! { "resource_id": bson::to_bson(resource_id)? }),
+        [_, .., comment_id] => Ok(doc! {
+            "$or": [
+                { "comment_id": bson::to_bson(comment_id)? },
+                { "ancestor_comment_ids": bson::to_bson(comment_id)? },
+            ]
+        }),
+        [] => Err(anyhow::anyhow!("empty materialized path")),
+    }
+}
+
+// the comments directly below the given materialized path: the root comments of a resource, or
+// the replies to a comment
+fn next_level_filter(materialized_path: &str) -> Result<Document> {
+    match materialized_path_to_uuid_list(materialized_path)?.as_slice() {
+        [resource_id] => Ok(doc! { "resource_id": bson::to_bson(resource_id)?, "depth": 1 }),
+        [_, .., comment_id] => Ok(doc! { "parent_comment_id": bson::to_bson(comment_id)? }),
+        [] => Err(anyhow::anyhow!("empty materialized path")),
+    }
+}
+
+// the part of a comment that moving it reads
+#[derive(Deserialize)]
+struct CommentPathDocument {
+    comment_id: Uuid,
+    materialized_path: String,
+}
+
+// an update pipeline rewriting the comments at and below `old_path` to sit at and below
+// `new_path`, all of them in a single statement on the server however many there are. the
+// path is rebased by its length in code points, comparable since paths are plain ascii, and
+// the ancestors above `old_path` are swapped for those above `new_path`
+fn rebase_subtree_pipeline(old_path: &str, new_path: &str) -> Result<Vec<Document>> {
+    let old_ancestor_count = ancestor_comment_ids_from_materialized_path(old_path).len() as i64;
+    let new_ancestor_comment_ids = ancestor_comment_ids_from_materialized_path(new_path);
+    let depth_change = i64::from(depth_from_materialized_path(new_path))
+        - i64::from(depth_from_materialized_path(old_path));
+    let resource_id = resource_id_from_materialized_path(new_path)
+        .ok_or_else(|| anyhow::anyhow!("not a materialized path"))?;
+
+    Ok(vec![doc! {
+        "$set": {
+            "materialized_path": {
+                "$concat": [
+                    new_path,
+                    {
+                        "$substrCP": [
+                            "$materialized_path",
+                            old_path.len() as i64,
+                            {
+                                "$subtract": [
+                                    { "$strLenCP": "$materialized_path" },
+                                    old_path.len() as i64,
+                                ]
+                            },
+                        ]
+                    },
+                ]
+            },
+            "resource_id": { "$literal": bson::to_bson(&resource_id)? },
+            "depth": { "$add": ["$depth", depth_change] },
+            "ancestor_comment_ids": {
+                "$concatArrays": [
+                    { "$literal": bson::to_bson(&new_ancestor_comment_ids)? },
+                    {
+                        "$slice": [
+                            "$ancestor_comment_ids",
+                            old_ancestor_count,
+                            { "$add": [{ "$size": "$ancestor_comment_ids" }, 1] },
+                        ]
+                    },
+                ]
+            },
+        }
+    }])
+}
+
+#[derive(Deserialize)]
+struct BranchCommentIdsGroup {
+    #[serde(rename = "_id")]
+    parent_comment_id: Uuid,
+    branch_comment_ids: Vec<Bson>,
+}
+
+// how many comments a rebuild of `branch_comment_ids` rewrites per transaction
+const REBUILD_CHUNK_SIZE: i64 = 500;
+
+// recomputes `branch_comment_ids` of the next chunk of comments matching the filter, those with
+// an id past the given one, from their replies, oldest reply first. returns the id of the last
+// comment of the chunk, or `None` once there are none left
+async fn rebuild_branch_comment_ids_chunk(
+    db: &Database,
+    session: &mut ClientSession,
+    filter: Document,
+    after_comment_id: Option<Uuid>,
+) -> mongodb::error::Result<Option<Uuid>> {
+    let comments_collection: Collection<Document> = db.collection("comments");
+
+    let chunk_filter = match after_comment_id {
+        Some(after_comment_id) => doc! {
+            "$and": [filter, { "comment_id": { "$gt": bson::to_bson(&after_comment_id)? } }]
+        },
+        None => filter,
+    };
+    let find_options = FindOptions::builder()
+        .sort(doc! { "comment_id": 1 })
+        .projection(doc! { "comment_id": 1, "materialized_path": 1 })
+        .limit(Some(REBUILD_CHUNK_SIZE))
+        .build();
+
+    let parent_comment_ids: Vec<Uuid> = comments_collection
+        .clone_with_type::<CommentPathDocument>()
+        .find_with_session(chunk_filter, Some(find_options), session)
+        .await?
+        .stream(session)
+        .map_ok(|comment| comment.comment_id)
+        .try_collect()
+        .await?;
+
+    let Some(last_comment_id) = parent_comment_ids.last().copied() else {
+        return Ok(None);
+    };
+
+    let pipeline = vec![
+        doc! {
+            "$match": { "parent_comment_id": { "$in": bson::to_bson(&parent_comment_ids)? } }
+        },
+        doc! {
+            "$sort": {
+                "commented_timestamp": 1  // ascending order, the order replies were appended in
+            }
+        },
+        doc! {
+            "$group": {
+                "_id": "$parent_comment_id",
+                "branch_comment_ids": { "$push": "$comment_id" }
+            }
+        },
+    ];
+    let aggregate_options = AggregateOptions::builder().allow_disk_use(true).build();
+
+    let mut branch_comment_ids: HashMap<Uuid, Vec<Bson>> = comments_collection
+        .aggregate_with_session(pipeline, Some(aggregate_options), session)
+        .await?
+        .with_type::<BranchCommentIdsGroup>()
+        .stream(session)
+        .map_ok(|group| (group.parent_comment_id, group.branch_comment_ids))
+        .try_collect()
+        .await?;
+
+    for parent_comment_id in parent_comment_ids {
+        comments_collection
+            .update_one_with_session(
+                doc! { "comment_id": bson::to_bson(&parent_comment_id)? },
+                doc! {
+                    "$set": {
+                        "branch_comment_ids": branch_comment_ids
+                            .remove(&parent_comment_id)
+                            .unwrap_or_default()
+                    }
+                },
+                None,
+                session,
+            )
+            .await?;
+    }
+
+    Ok(Some(last_comment_id))
+}
+
+// callbacks of transactions fail with a custom error carrying a message when the data doesn't
+// allow the write, which is turned back into a plain error here
+fn transaction_error(error: mongodb::error::Error) -> anyhow::Error {
+    match error.get_custom::<String>() {
+        Some(message) => anyhow::anyhow!(message.clone()),
+        None => error.into(),
+    }
+}
+
+#[async_trait]
+impl CommentStore for MongoCommentStore {
+    async fn migrate(&self) -> Result<Vec<String>> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+
+        run_mongo_migrations(&self.mongo_client, &db).await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_comment(&self, comment: Comment) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Document> = db.collection("comments");
+
+        let bson_comment = bson::to_bson(&comment)?
+            .as_document()
+            .ok_or(anyhow::anyhow!(
+                "bson_resource: failed to convert BSON to document"
+            ))?
+            .clone();
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (&comments_collection, &comment, &bson_comment),
+                |session, (comments_collection, comment, bson_comment)| {
+                    async move {
+                        let insert_result = comments_collection
+                            .insert_one_with_session(bson_comment.clone(), None, session)
+                            .await?;
+
+                        if let Null = insert_result.inserted_id {
+                            return Err(mongodb::error::Error::custom(
+                                "error inserting the document".to_string(),
+                            ));
+                        }
+
+                        if let Some(parent_comment_id) = comment.parent_comment_id {
+                            let update_filter = doc! {
+                                "comment_id": bson::to_bson(&parent_comment_id)?
+                            };
+
+                            let update = doc! {
+                                "$push": {
+                                    "branch_comment_ids": bson::to_bson(&comment.comment_id)?,
+                                },
+                                "$inc": {
+                                    "direct_reply_count": 1,
+                                }
+                            };
+
+                            let update_result = comments_collection
+                                .update_one_with_session(update_filter, update, None, session)
+                                .await?;
+
+                            // the comment it was branched from may have been deleted in the
+                            // meantime
+                            if update_result.matched_count == 0 {
+                                return Err(mongodb::error::Error::custom(
+                                    "branched from comment not found".to_string(),
+                                ));
+                            }
+                        }
+
+                        let ancestor_comment_ids =
+                            ancestor_comment_ids_from_materialized_path(&comment.materialized_path);
+                        if !ancestor_comment_ids.is_empty() {
+                            let update_filter = doc! {
+                                "comment_id": {
+                                    "$in": bson::to_bson(&ancestor_comment_ids)?
+                                }
+                            };
+
+                            let update = doc! {
+                                "$inc": {
+                                    "total_descendant_count": 1,
+                                }
+                            };
+
+                            comments_collection
+                                .update_many_with_session(update_filter, update, None, session)
+                                .await?;
+                        }
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_pending_comment(&self, pending_comment: PendingComment) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let pending_comments_collection: Collection<PendingComment> =
+            db.collection("pending_comments");
+
+        pending_comments_collection
+            .insert_one(pending_comment, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn find_pending_comment(&self, comment_id: Uuid) -> Result<PendingComment> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let pending_comments_collection: Collection<PendingComment> =
+            db.collection("pending_comments");
+
+        let filter = doc! {
+            "comment_id": bson::to_bson(&comment_id)?
+        };
+
+        pending_comments_collection
+            .find_one(filter, None)
+            .await?
+            .ok_or_else(|| anyhow::Error::msg("pending comment not found"))
+    }
+
+    async fn find_pending_comments(&self, resource_id: Uuid) -> Result<Vec<PendingComment>> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let pending_comments_collection: Collection<PendingComment> =
+            db.collection("pending_comments");
+
+        let filter = doc! {
+            "resource_id": bson::to_bson(&resource_id)?
+        };
+        let find_options = FindOptions::builder()
+            .sort(doc! { "commented_timestamp": 1, "comment_id": 1 })
+            .build();
+
+        let results: Vec<PendingComment> = pending_comments_collection
+            .find(filter, find_options)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(results)
+    }
+
     #[instrument(level = "trace", skip_all)]
-    pub async fn insert_comment_mongo(&self, comment: Comment) -> Result<()> {
+    async fn delete_pending_comment(&self, comment_id: Uuid) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let pending_comments_collection: Collection<PendingComment> =
+            db.collection("pending_comments");
+
+        let delete_filter = doc! {
+            "comment_id": bson::to_bson(&comment_id)?
+        };
+
+        let delete_result = pending_comments_collection
+            .delete_one(delete_filter, None)
+            .await?;
+
+        if delete_result.deleted_count == 0 {
+            info!("no pending comment documentations deleted")
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn append_reaction_to_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_append: CommentReaction,
+    ) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Document> = db.collection("comments");
+        let comment_reactions_collection: Collection<CommentReactionDocument> =
+            db.collection("comment_reactions");
+
+        let comment_reaction_document =
+            CommentReactionDocument::new(comment_id, comment_reaction_to_append);
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        let transaction_result = session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &comment_reactions_collection,
+                    &comment_reaction_document,
+                ),
+                |session, (comments_collection, comment_reactions_collection, reaction)| {
+                    async move {
+                        let comment_filter = doc! {
+                            "comment_id": bson::to_bson(&reaction.comment_id)?
+                        };
+
+                        let comment_count = comments_collection
+                            .count_documents_with_session(comment_filter.clone(), None, session)
+                            .await?;
+
+                        if comment_count == 0 {
+                            return Err(mongodb::error::Error::custom(
+                                "error updating (appending reaction to) comment documents"
+                                    .to_string(),
+                            ));
+                        }
+
+                        // a write that fails aborts the transaction, so look for the reaction
+                        // first rather than relying on the unique index
+                        let reaction_filter = doc! {
+                            "comment_id": bson::to_bson(&reaction.comment_id)?,
+                            "reactor.account_id": bson::to_bson(&reaction.reactor.account_id)?,
+                            "emoji_unified_code": &reaction.emoji_unified_code,
+                        };
+
+                        let reaction_count = comment_reactions_collection
+                            .count_documents_with_session(reaction_filter, None, session)
+                            .await?;
+
+                        if reaction_count > 0 {
+                            return Ok(());
+                        }
+
+                        comment_reactions_collection
+                            .insert_one_with_session(*reaction, None, session)
+                            .await?;
+
+                        let mut reaction_count_increment = Document::new();
+                        reaction_count_increment
+                            .insert(reaction_count_field(&reaction.emoji_unified_code), 1);
+
+                        comments_collection
+                            .update_one_with_session(
+                                comment_filter.clone(),
+                                doc! { "$inc": reaction_count_increment },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        update_reaction_scores(comments_collection, comment_filter, session)
+                            .await?;
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await;
+
+        match transaction_result {
+            // a concurrent request stored the same reaction first, which leaves nothing to do
+            Err(e) if is_duplicate_key_error(&e) => Ok(()),
+            result => result.map_err(transaction_error),
+        }
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn remove_reaction_from_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_remove: CommentReaction,
+    ) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Document> = db.collection("comments");
+        let comment_reactions_collection: Collection<CommentReactionDocument> =
+            db.collection("comment_reactions");
+
+        let comment_reaction_document =
+            CommentReactionDocument::new(comment_id, comment_reaction_to_remove);
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &comment_reactions_collection,
+                    &comment_reaction_document,
+                ),
+                |session, (comments_collection, comment_reactions_collection, reaction)| {
+                    async move {
+                        let delete_filter = doc! {
+                            "comment_id": bson::to_bson(&reaction.comment_id)?,
+                            "reactor.account_id": bson::to_bson(&reaction.reactor.account_id)?,
+                            "emoji_unified_code": &reaction.emoji_unified_code,
+                        };
+
+                        let delete_result = comment_reactions_collection
+                            .delete_one_with_session(delete_filter, None, session)
+                            .await?;
+
+                        if delete_result.deleted_count == 0 {
+                            return Err(mongodb::error::Error::custom(
+                                "error updating (removing reaction from) comment documents"
+                                    .to_string(),
+                            ));
+                        }
+
+                        let reaction_count_field =
+                            reaction_count_field(&reaction.emoji_unified_code);
+                        let mut reaction_count_decrement = Document::new();
+                        reaction_count_decrement.insert(reaction_count_field.clone(), -1);
+
+                        comments_collection
+                            .update_one_with_session(
+                                doc! { "comment_id": bson::to_bson(&reaction.comment_id)? },
+                                doc! { "$inc": reaction_count_decrement },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        // drop the emoji from the counts once nobody reacts with it anymore
+                        let mut update_filter = doc! {
+                            "comment_id": bson::to_bson(&reaction.comment_id)?
+                        };
+                        update_filter.insert(reaction_count_field.clone(), doc! { "$lte": 0 });
+
+                        let mut reaction_count_unset = Document::new();
+                        reaction_count_unset.insert(reaction_count_field, "");
+
+                        comments_collection
+                            .update_one_with_session(
+                                update_filter,
+                                doc! { "$unset": reaction_count_unset },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        update_reaction_scores(
+                            comments_collection,
+                            doc! { "comment_id": bson::to_bson(&reaction.comment_id)? },
+                            session,
+                        )
+                        .await?;
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn update_comment_text(&self, comment_id: Uuid, new_comment_text: String) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Comment> = db.collection("comments");
+
+        let update_filter = doc! {
+            "comment_id": bson::to_bson(&comment_id)?
+        };
+
+        let update = doc! {
+            "$set": {
+                "comment_text": new_comment_text,
+            }
+        };
+
+        let update_result = comments_collection
+            .update_one(update_filter, update, None)
+            .await?;
+
+        if update_result.modified_count == 0 {
+            info!("no comment documentations updated")
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn tombstone_comment(
+        &self,
+        comment_id: Uuid,
+        deleted_timestamp: DateTime<Utc>,
+    ) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Comment> = db.collection("comments");
+        let deleted_comment_contents_collection: Collection<DeletedCommentContentDocument> =
+            db.collection("deleted_comment_contents");
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &deleted_comment_contents_collection,
+                    &(comment_id, deleted_timestamp),
+                ),
+                |session,
+                 (
+                    comments_collection,
+                    deleted_comment_contents_collection,
+                    (comment_id, deleted_timestamp),
+                )| {
+                    async move {
+                        let comment_filter = doc! {
+                            "comment_id": bson::to_bson(comment_id)?,
+                            "deleted": { "$ne": true },
+                        };
+
+                        let comment = match comments_collection
+                            .find_one_with_session(comment_filter.clone(), None, session)
+                            .await?
+                        {
+                            Some(comment) => comment,
+                            None => {
+                                info!("no comment documentations updated");
+                                return Ok(());
+                            }
+                        };
+
+                        // keep the text and the author apart, so that the comment can be restored
+                        deleted_comment_contents_collection
+                            .insert_one_with_session(
+                                DeletedCommentContentDocument {
+                                    comment_id: *comment_id,
+                                    commenter: comment.commenter,
+                                    comment_text: comment.comment_text,
+                                    deleted_timestamp: bson::DateTime::from_millis(
+                                        deleted_timestamp.timestamp_millis(),
+                                    ),
+                                },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        let update = doc! {
+                            "$set": {
+                                "comment_text": DELETED_COMMENT_TEXT,
+                                "commenter": bson::to_bson(&Commenter::deleted())?,
+                                "deleted": true,
+                                "deleted_timestamp": bson::to_bson(deleted_timestamp)?,
+                            }
+                        };
+
+                        comments_collection
+                            .update_one_with_session(comment_filter, update, None, session)
+                            .await?;
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn restore_comment(&self, comment_id: Uuid) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Comment> = db.collection("comments");
+        let deleted_comment_contents_collection: Collection<DeletedCommentContentDocument> =
+            db.collection("deleted_comment_contents");
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &deleted_comment_contents_collection,
+                    &comment_id,
+                ),
+                |session, (comments_collection, deleted_comment_contents_collection, comment_id)| {
+                    async move {
+                        let comment_filter = doc! {
+                            "comment_id": bson::to_bson(comment_id)?
+                        };
+
+                        let deleted_comment_content = deleted_comment_contents_collection
+                            .find_one_and_delete_with_session(comment_filter.clone(), None, session)
+                            .await?
+                            .ok_or_else(|| {
+                                mongodb::error::Error::custom(
+                                    "deleted comment content not found".to_string(),
+                                )
+                            })?;
+
+                        let update = doc! {
+                            "$set": {
+                                "comment_text": deleted_comment_content.comment_text,
+                                "commenter": bson::to_bson(&deleted_comment_content.commenter)?,
+                                "deleted": false,
+                                "deleted_timestamp": Null,
+                            }
+                        };
+
+                        comments_collection
+                            .update_one_with_session(comment_filter, update, None, session)
+                            .await?;
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn discard_deleted_comment_content(&self, comment_id: Uuid) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let deleted_comment_contents_collection: Collection<DeletedCommentContentDocument> =
+            db.collection("deleted_comment_contents");
+
+        let delete_result = deleted_comment_contents_collection
+            .delete_one(doc! { "comment_id": bson::to_bson(&comment_id)? }, None)
+            .await?;
+
+        if delete_result.deleted_count == 0 {
+            info!("no deleted comment contents discarded");
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn prune_comments(&self, comment_materialized_path: String) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Document> = db.collection("comments");
+        let comment_reactions_collection: Collection<Document> = db.collection("comment_reactions");
+        let deleted_comment_contents_collection: Collection<Document> =
+            db.collection("deleted_comment_contents");
+
+        // the comment and everything below it
+        let pruned_filter = subtree_filter(&comment_materialized_path)?;
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &comment_reactions_collection,
+                    &deleted_comment_contents_collection,
+                    &comment_materialized_path,
+                    &pruned_filter,
+                ),
+                |session,
+                 (
+                    comments_collection,
+                    comment_reactions_collection,
+                    deleted_comment_contents_collection,
+                    pruned_path,
+                    pruned_filter,
+                )| {
+                    async move {
+                        let filter = pruned_filter.clone();
+
+                        let pruned_comment_ids = comments_collection
+                            .distinct_with_session("comment_id", filter.clone(), None, session)
+                            .await?;
+
+                        let delete_result = comments_collection
+                            .delete_many_with_session(filter, None, session)
+                            .await?;
+
+                        if delete_result.deleted_count == 0 {
+                            info!("no comment documentations deleted");
+                            return Ok(());
+                        }
+
+                        comment_reactions_collection
+                            .delete_many_with_session(
+                                doc! { "comment_id": { "$in": pruned_comment_ids.clone() } },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        deleted_comment_contents_collection
+                            .delete_many_with_session(
+                                doc! { "comment_id": { "$in": pruned_comment_ids } },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        if let (Some(parent_comment_id), Some(pruned_comment_id)) = (
+                            parent_comment_id_from_materialized_path(pruned_path),
+                            comment_id_from_materialized_path(pruned_path),
+                        ) {
+                            let update_filter = doc! {
+                                "comment_id": bson::to_bson(&parent_comment_id)?
+                            };
+
+                            let update = doc! {
+                                "$pull": {
+                                    "branch_comment_ids": bson::to_bson(&pruned_comment_id)?,
+                                },
+                                "$inc": {
+                                    "direct_reply_count": -1,
+                                }
+                            };
+
+                            comments_collection
+                                .update_one_with_session(update_filter, update, None, session)
+                                .await?;
+                        }
+
+                        let ancestor_comment_ids =
+                            ancestor_comment_ids_from_materialized_path(pruned_path);
+                        if !ancestor_comment_ids.is_empty() {
+                            let update_filter = doc! {
+                                "comment_id": {
+                                    "$in": bson::to_bson(&ancestor_comment_ids)?
+                                }
+                            };
+
+                            let update = doc! {
+                                "$inc": {
+                                    "total_descendant_count": -(delete_result.deleted_count as i64),
+                                }
+                            };
+
+                            comments_collection
+                                .update_many_with_session(update_filter, update, None, session)
+                                .await?;
+                        }
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn move_comments(
+        &self,
+        comment_materialized_path: String,
+        new_parent_materialized_path: String,
+    ) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Document> = db.collection("comments");
+
+        let comment_id = comment_id_from_materialized_path(&comment_materialized_path)
+            .filter(|_| depth_from_materialized_path(&comment_materialized_path) > 0)
+            .ok_or_else(|| anyhow::anyhow!("not the path of a comment"))?;
+        if materialized_path_to_uuid_list(&new_parent_materialized_path)?.contains(&comment_id) {
+            return Err(anyhow::anyhow!("cannot move a comment below itself"));
+        }
+
+        let new_materialized_path =
+            append_uuid_to_materialized_path(&new_parent_materialized_path, &comment_id);
+
+        // the comment and everything below it
+        let moved_filter = subtree_filter(&comment_materialized_path)?;
+        let rebase_pipeline =
+            rebase_subtree_pipeline(&comment_materialized_path, &new_materialized_path)?;
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &comment_materialized_path,
+                    &new_materialized_path,
+                    &moved_filter,
+                    &rebase_pipeline,
+                ),
+                |session,
+                 (
+                    comments_collection,
+                    old_path,
+                    new_path,
+                    moved_filter,
+                    rebase_pipeline,
+                )| {
+                    async move {
+                        let comment_paths_collection =
+                            comments_collection.clone_with_type::<CommentPathDocument>();
+
+                        // a concurrent move may have taken the comment elsewhere already
+                        let moved_comment = comment_paths_collection
+                            .find_one_with_session(
+                                doc! { "comment_id": bson::to_bson(&comment_id)? },
+                                None,
+                                session,
+                            )
+                            .await?;
+                        if moved_comment
+                            .is_none_or(|comment| comment.materialized_path != **old_path)
+                        {
+                            return Err(mongodb::error::Error::custom(
+                                "comment not found".to_string(),
+                            ));
+                        }
+
+                        let new_parent_comment_id =
+                            parent_comment_id_from_materialized_path(new_path);
+                        if let Some(new_parent_comment_id) = new_parent_comment_id {
+                            let new_parent_count = comments_collection
+                                .count_documents_with_session(
+                                    doc! { "comment_id": bson::to_bson(&new_parent_comment_id)? },
+                                    None,
+                                    session,
+                                )
+                                .await?;
+
+                            if new_parent_count == 0 {
+                                return Err(mongodb::error::Error::custom(
+                                    "new parent comment not found".to_string(),
+                                ));
+                            }
+                        }
+
+                        // the whole subtree is rewritten in one statement, however large it is
+                        let moved_count = comments_collection
+                            .count_documents_with_session(moved_filter.clone(), None, session)
+                            .await? as i64;
+                        comments_collection
+                            .update_many_with_session(
+                                moved_filter.clone(),
+                                rebase_pipeline.clone(),
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        let comment_type = match new_parent_comment_id {
+                            Some(_) => CommentType::Branch,
+                            None => CommentType::Root,
+                        };
+                        comments_collection
+                            .update_one_with_session(
+                                doc! {
+                                    "comment_id": bson::to_bson(
+                                        &comment_id_from_materialized_path(new_path),
+                                    )?
+                                },
+                                doc! {
+                                    "$set": {
+                                        "parent_comment_id": bson::to_bson(&new_parent_comment_id)?,
+                                        "comment_type": bson::to_bson(&comment_type)?,
+                                    }
+                                },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        if let (Some(parent_comment_id), Some(moved_comment_id)) = (
+                            parent_comment_id_from_materialized_path(old_path),
+                            comment_id_from_materialized_path(old_path),
+                        ) {
+                            comments_collection
+                                .update_one_with_session(
+                                    doc! { "comment_id": bson::to_bson(&parent_comment_id)? },
+                                    doc! {
+                                        "$pull": {
+                                            "branch_comment_ids": bson::to_bson(&moved_comment_id)?,
+                                        },
+                                        "$inc": {
+                                            "direct_reply_count": -1,
+                                        }
+                                    },
+                                    None,
+                                    session,
+                                )
+                                .await?;
+                        }
+
+                        comments_collection
+                            .update_many_with_session(
+                                doc! {
+                                    "comment_id": {
+                                        "$in": bson::to_bson(
+                                            &ancestor_comment_ids_from_materialized_path(old_path),
+                                        )?
+                                    }
+                                },
+                                doc! { "$inc": { "total_descendant_count": -moved_count } },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        // the moved comment takes its place among the replies by when it was made
+                        if let Some(new_parent_comment_id) = new_parent_comment_id {
+                            let find_options = FindOptions::builder()
+                                .sort(doc! { "commented_timestamp": 1 })
+                                .build();
+                            let branch_comment_ids: Vec<Uuid> = comment_paths_collection
+                                .find_with_session(
+                                    doc! {
+                                        "parent_comment_id": bson::to_bson(&new_parent_comment_id)?
+                                    },
+                                    find_options,
+                                    session,
+                                )
+                                .await?
+                                .stream(session)
+                                .map_ok(|comment| comment.comment_id)
+                                .try_collect()
+                                .await?;
+
+                            comments_collection
+                                .update_one_with_session(
+                                    doc! { "comment_id": bson::to_bson(&new_parent_comment_id)? },
+                                    doc! {
+                                        "$set": {
+                                            "branch_comment_ids": bson::to_bson(
+                                                &branch_comment_ids,
+                                            )?,
+                                        },
+                                        "$inc": {
+                                            "direct_reply_count": 1,
+                                        }
+                                    },
+                                    None,
+                                    session,
+                                )
+                                .await?;
+                        }
+
+                        comments_collection
+                            .update_many_with_session(
+                                doc! {
+                                    "comment_id": {
+                                        "$in": bson::to_bson(
+                                            &ancestor_comment_ids_from_materialized_path(new_path),
+                                        )?
+                                    }
+                                },
+                                doc! { "$inc": { "total_descendant_count": moved_count } },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn rebuild_branch_comment_ids(&self, current_path: String) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+
+        let filter = subtree_filter(&current_path)?;
+
+        // a chunk at a time, each in its own transaction, so that large threads stay within
+        // the limits of a transaction
+        let mut session = self.mongo_client.start_session(None).await?;
+        let mut after_comment_id: Option<Uuid> = None;
+        loop {
+            after_comment_id = session
+                .with_transaction(
+                    (&db, &filter, after_comment_id),
+                    |session, (db, filter, after_comment_id)| {
+                        rebuild_branch_comment_ids_chunk(
+                            db,
+                            session,
+                            filter.clone(),
+                            *after_comment_id,
+                        )
+                        .boxed()
+                    },
+                    None,
+                )
+                .await
+                .map_err(transaction_error)?;
+
+            if after_comment_id.is_none() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn find_comment(&self, comment_id: Uuid) -> Result<Comment> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
         let comments_collection: Collection<Document> = db.collection("comments");
 
-        let bson_comment = bson::to_bson(&comment)?
-            .as_document()
-            .ok_or(anyhow::anyhow!(
-                "bson_resource: failed to convert BSON to document"
-            ))?
-            .clone();
-
-        let insert_result = comments_collection.insert_one(bson_comment, None).await?;
+        let filter = doc! {
+            "comment_id": bson::to_bson(&comment_id)?
+        };
 
-        if let Null = insert_result.inserted_id {
-            return Err(anyhow::anyhow!("error inserting the document"));
+        if let Some(document) = comments_collection.find_one(filter, None).await? {
+            let comment: Comment = bson::from_bson(Bson::Document(document))?;
+            Ok(comment)
+        } else {
+            Err(anyhow::Error::msg("comment not found"))
         }
-
-        Ok(())
     }
 
-    #[instrument(level = "trace", skip_all)]
-    pub async fn append_reaction_to_comment_mongo(
+    async fn find_comment_reactions(
         &self,
         comment_id: Uuid,
-        comment_reaction_to_append: CommentReaction,
-    ) -> Result<()> {
+        limit: Option<u32>,
+    ) -> Result<Vec<CommentReaction>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
-        let comments_collection: Collection<Comment> = db.collection("comments");
+        let comment_reactions_collection: Collection<CommentReactionDocument> =
+            db.collection("comment_reactions");
 
-        let update_filter = doc! {
+        let filter = doc! {
             "comment_id": bson::to_bson(&comment_id)?
         };
 
-        let update = doc! {
-            "$push": {
-                "reactions": bson::to_bson(&comment_reaction_to_append)?,
+        let find_options = {
+            let builder = FindOptions::builder().sort(doc! {
+                "_id": 1  // object ids grow with insertion time, oldest first
+            });
+
+            match limit {
+                Some(l) => builder.limit(Some(i64::from(l))).build(),
+                None => builder.build(),
             }
         };
 
-        let update_result = comments_collection
-            .update_one(update_filter, update, None)
+        let results: Vec<CommentReaction> = comment_reactions_collection
+            .find(filter, Some(find_options))
+            .await?
+            .map_ok(CommentReaction::from)
+            .try_collect()
             .await?;
 
-        if update_result.modified_count == 0 {
-            return Err(anyhow::anyhow!(
-                "error updating (appending reaction to) comment documents"
-            ));
-        }
-
-        Ok(())
+        Ok(results)
     }
 
-    #[instrument(level = "trace", skip_all)]
-    pub async fn remove_reaction_from_comment_mongo(
+    async fn find_reactions_of_comments(
         &self,
-        comment_id: Uuid,
-        comment_reaction_to_remove: CommentReaction,
-    ) -> Result<()> {
+        comment_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, Vec<CommentReaction>>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
-        let comments_collection: Collection<Comment> = db.collection("comments");
+        let comment_reactions_collection: Collection<CommentReactionDocument> =
+            db.collection("comment_reactions");
 
-        let update_filter = doc! {
-            "comment_id": bson::to_bson(&comment_id)?
+        let filter = doc! {
+            "comment_id": { "$in": bson::to_bson(&comment_ids)? }
         };
 
-        let update = doc! {
-            "$pull": {
-                "reactions": bson::to_bson(&comment_reaction_to_remove)?,
-            }
-        };
+        let find_options = FindOptions::builder()
+            .sort(doc! {
+                "_id": 1  // object ids grow with insertion time, oldest first
+            })
+            .build();
 
-        let update_result = comments_collection
-            .update_one(update_filter, update, None)
+        let mut cursor = comment_reactions_collection
+            .find(filter, Some(find_options))
             .await?;
 
-        if update_result.modified_count == 0 {
-            return Err(anyhow::anyhow!(
-                "error updating (removing reaction from) comment documents"
-            ));
+        let mut results: HashMap<Uuid, Vec<CommentReaction>> = HashMap::new();
+        while let Some(comment_reaction) = cursor.try_next().await? {
+            results
+                .entry(comment_reaction.comment_id)
+                .or_default()
+                .push(CommentReaction::from(comment_reaction));
         }
 
-        Ok(())
+        Ok(results)
     }
 
-    #[instrument(level = "trace", skip_all)]
-    pub async fn update_comment_mongo<T>(&self, comment_id: Uuid, new_comment: T) -> Result<()>
-    where
-        T: serde::Serialize,
-    {
+    async fn find_replies_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+        sort: CommentSort,
+        limit: u32,
+    ) -> Result<HashMap<Uuid, Vec<Comment>>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
-        let comments_collection: Collection<Comment> = db.collection("comments");
-
-        let update_filter = doc! {
-            "comment_id": bson::to_bson(&comment_id)?
-        };
+        let comments_collection: Collection<Document> = db.collection("comments");
 
-        let update = doc! {
-            "$set": bson::to_bson(&new_comment)?
-        };
+        // the replies are ranked per comment they reply to, so that one query serves them all
+        let pipeline = vec![
+            doc! { "$match": { "parent_comment_id": { "$in": bson::to_bson(&comment_ids)? } } },
+            doc! {
+                "$setWindowFields": {
+                    "partitionBy": "$parent_comment_id",
+                    "sortBy": sort_document(sort),
+                    "output": { "reply_rank": { "$documentNumber": {} } },
+                }
+            },
+            doc! { "$match": { "reply_rank": { "$lte": i64::from(limit) } } },
+            doc! { "$sort": { "reply_rank": 1 } },
+            doc! { "$unset": "reply_rank" },
+        ];
 
-        let update_result = comments_collection
-            .update_one(update_filter, update, None)
-            .await?;
+        let mut cursor = comments_collection.aggregate(pipeline, None).await?;
 
-        if update_result.modified_count == 0 {
-            info!("no comment documentations updated")
+        let mut results: HashMap<Uuid, Vec<Comment>> = HashMap::new();
+        while let Some(document) = cursor.try_next().await? {
+            let comment: Comment = bson::from_bson(Bson::Document(document))?;
+            if let Some(parent_comment_id) = comment.parent_comment_id {
+                results.entry(parent_comment_id).or_default().push(comment);
+            }
         }
 
-        Ok(())
+        Ok(results)
     }
 
-    #[instrument(level = "trace", skip_all)]
-    pub async fn prune_comments_mongo(&self, comment_materialized_path: String) -> Result<()> {
+    async fn find_next_level_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        limit: Option<u32>,
+        after: Option<CommentCursor>,
+    ) -> Result<Vec<Comment>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
-        let comments_collection: Collection<Comment> = db.collection("comments");
+        let comments_collection: Collection<Document> = db.collection("comments");
 
-        let regex_pattern = format!("^{}", comment_materialized_path); // starts with the given path
-        let filter = doc! {
-            "materialized_path": {
-                "$regex": regex_pattern
+        let mut filter = next_level_filter(&current_path)?;
+        if let Some(after) = after {
+            filter.extend(after_filter(sort, &after)?);
+        }
+
+        let find_options = {
+            let builder = FindOptions::builder().sort(sort_document(sort));
+
+            match limit {
+                Some(l) => builder.limit(Some(i64::from(l))).build(),
+                None => builder.build(),
             }
         };
 
-        let delete_result = comments_collection.delete_many(filter, None).await?;
+        let mut cursor = comments_collection.find(filter, Some(find_options)).await?;
 
-        if delete_result.deleted_count == 0 {
-            info!("no comment documentations deleted");
+        let mut results: Vec<Comment> = Vec::new();
+        while let Some(document) = cursor.try_next().await? {
+            let comment: Comment = bson::from_bson(Bson::Document(document))?;
+            results.push(comment);
         }
 
-        Ok(())
+        Ok(results)
     }
 
-    pub async fn find_comment(&self, comment_id: Uuid) -> Result<Comment> {
+    async fn find_all_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        max_depth: Option<u32>,
+    ) -> Result<Vec<Comment>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
         let comments_collection: Collection<Document> = db.collection("comments");
 
-        let filter = doc! {
-            "comment_id": bson::to_bson(&comment_id)?
-        };
+        let mut filter = subtree_filter(&current_path)?;
+        if let Some(max_depth) = max_depth {
+            filter.insert("depth", doc! { "$lte": max_depth });
+        }
 
-        if let Some(document) = comments_collection.find_one(filter, None).await? {
+        let mut sort_by = doc! { "depth": 1 }; // ascending order
+        sort_by.extend(sort_document(sort));
+
+        let find_options = FindOptions::builder().sort(sort_by).build();
+
+        let mut cursor = comments_collection.find(filter, Some(find_options)).await?;
+
+        let mut results: Vec<Comment> = Vec::new();
+        while let Some(document) = cursor.try_next().await? {
             let comment: Comment = bson::from_bson(Bson::Document(document))?;
-            Ok(comment)
-        } else {
-            Err(anyhow::Error::msg("comment not found"))
+            results.push(comment);
         }
+
+        Ok(results)
     }
 
-    pub async fn find_next_level_comments(
+    async fn find_all_comments_page(
         &self,
         current_path: String,
-        limit: Option<u32>,
+        limit: u32,
+        after: Option<Comment>,
     ) -> Result<Vec<Comment>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
         let comments_collection: Collection<Document> = db.collection("comments");
 
-        let regex_pattern = format!(
-            r"^{}->[0-9a-fA-F]{{8}}-[0-9a-fA-F]{{4}}-[0-9a-fA-F]{{4}}-[0-9a-fA-F]{{4}}-[0-9a-fA-F]{{12}}$", // don't be scared, it's just uuid's regex :)
-            current_path
-        );
+        let mut filter = subtree_filter(&current_path)?;
+        if let Some(after) = after {
+            // deeper comments, or those listed after it at the same depth
+            let after_cursor = CommentCursor::of(&after, CommentSort::Oldest);
+            let mut same_depth_filter = doc! { "depth": after.depth };
+            same_depth_filter.extend(after_filter(CommentSort::Oldest, &after_cursor)?);
 
-        let filter = doc! {
-            "materialized_path": {
-                "$regex": regex_pattern
-            }
-        };
+            filter = doc! {
+                "$and": [
+                    filter,
+                    { "$or": [{ "depth": { "$gt": after.depth } }, same_depth_filter] },
+                ]
+            };
+        }
 
-        let find_options = {
-            let builder = FindOptions::builder().sort(doc! {
-                "commented_timestamp": -1  // indicates descending order, latest first
-            });
+        let mut sort_by = doc! { "depth": 1 }; // ascending order
+        sort_by.extend(sort_document(CommentSort::Oldest));
 
-            match limit {
-                Some(l) => builder.limit(Some(i64::from(l))).build(),
-                None => builder.build(),
-            }
-        };
+        let find_options = FindOptions::builder()
+            .sort(sort_by)
+            .limit(Some(i64::from(limit)))
+            .build();
 
         let mut cursor = comments_collection.find(filter, Some(find_options)).await?;
 
@@ -208,38 +1795,18 @@ impl PersistentLayer {
         Ok(results)
     }
 
-    pub async fn find_all_comments(&self, current_path: String) -> Result<Vec<Comment>> {
+    async fn find_expired_tombstones(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Comment>> {
         let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
         let comments_collection: Collection<Document> = db.collection("comments");
 
-        let regex_pattern = format!("^{}", current_path);
-
-        let pipeline = vec![
-            doc! {
-                "$match": {
-                    "materialized_path": {
-                        "$regex": regex_pattern
-                    }
-                }
-            },
-            doc! {
-                "$addFields": {
-                    "path_length": {
-                        "$strLenCP": "$materialized_path"
-                    }
-                }
-            },
-            doc! {
-                "$sort": {
-                    "path_length": 1,  // ascending order
-                    "commented_timestamp": -1  // descending order
-                }
-            },
-        ];
+        let filter = doc! {
+            "deleted": true,
+            "deleted_timestamp": { "$lt": bson::to_bson(&deleted_before)? }
+        };
 
-        let mut cursor = comments_collection.aggregate(pipeline, None).await?;
+        let mut cursor = comments_collection.find(filter, None).await?;
 
-        let mut results: Vec<Comment> = Vec::new();
+        let mut results: Vec<Comment> = vec![];
         while let Some(document) = cursor.try_next().await? {
             let comment: Comment = bson::from_bson(Bson::Document(document))?;
             results.push(comment);
@@ -247,4 +1814,267 @@ impl PersistentLayer {
 
         Ok(results)
     }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_resource(&self, resource: Resource) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resources_collection: Collection<Resource> = db.collection("resources");
+
+        // the unique index on resource_id refuses registering a resource twice
+        resources_collection.insert_one(resource, None).await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn update_resource(&self, resource: Resource) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resources_collection: Collection<Resource> = db.collection("resources");
+
+        let update_filter = doc! {
+            "resource_id": bson::to_bson(&resource.resource_id)?
+        };
+
+        let update_result = resources_collection
+            .replace_one(update_filter, resource, None)
+            .await?;
+
+        if update_result.matched_count == 0 {
+            info!("no resource documentations updated")
+        }
+
+        Ok(())
+    }
+
+    async fn find_resource(&self, resource_id: Uuid) -> Result<Resource> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resources_collection: Collection<Resource> = db.collection("resources");
+
+        let filter = doc! {
+            "resource_id": bson::to_bson(&resource_id)?
+        };
+
+        resources_collection
+            .find_one(filter, None)
+            .await?
+            .ok_or_else(|| anyhow::Error::msg("resource not found"))
+    }
+
+    async fn find_resource_settings(&self, resource_id: Uuid) -> Result<ResourceSettings> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resources_collection: Collection<Resource> = db.collection("resources");
+
+        let filter = doc! {
+            "resource_id": bson::to_bson(&resource_id)?
+        };
+
+        let resource = resources_collection.find_one(filter, None).await?;
+
+        Ok(resource
+            .map(|resource| resource.settings)
+            .unwrap_or_default())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn merge_resources(&self, resource_alias: ResourceAlias) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let comments_collection: Collection<Document> = db.collection("comments");
+        let resources_collection: Collection<Document> = db.collection("resources");
+        let resource_aliases_collection: Collection<ResourceAlias> =
+            db.collection("resource_aliases");
+
+        // every comment of the source is rebased in one statement
+        let rebase_pipeline = rebase_subtree_pipeline(
+            &resource_alias.alias_resource_id.to_string(),
+            &resource_alias.resource_id.to_string(),
+        )?;
+
+        let mut session = self.mongo_client.start_session(None).await?;
+        session
+            .with_transaction(
+                (
+                    &comments_collection,
+                    &resources_collection,
+                    &resource_aliases_collection,
+                    &resource_alias,
+                    &rebase_pipeline,
+                ),
+                |session,
+                 (
+                    comments_collection,
+                    resources_collection,
+                    resource_aliases_collection,
+                    resource_alias,
+                    rebase_pipeline,
+                )| {
+                    async move {
+                        let alias_resource_id = bson::to_bson(&resource_alias.alias_resource_id)?;
+                        let resource_id = bson::to_bson(&resource_alias.resource_id)?;
+
+                        comments_collection
+                            .update_many_with_session(
+                                doc! { "resource_id": &alias_resource_id },
+                                rebase_pipeline.clone(),
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        resource_aliases_collection
+                            .update_many_with_session(
+                                doc! { "resource_id": &alias_resource_id },
+                                doc! { "$set": { "resource_id": &resource_id } },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        // the unique index on alias_resource_id refuses merging a resource twice
+                        resource_aliases_collection
+                            .insert_one_with_session(*resource_alias, None, session)
+                            .await?;
+
+                        resources_collection
+                            .delete_one_with_session(
+                                doc! { "resource_id": &alias_resource_id },
+                                None,
+                                session,
+                            )
+                            .await?;
+
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                None,
+            )
+            .await
+            .map_err(transaction_error)
+    }
+
+    async fn resolve_resource_id(&self, resource_id: Uuid) -> Result<Uuid> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resource_aliases_collection: Collection<ResourceAlias> =
+            db.collection("resource_aliases");
+
+        let filter = doc! {
+            "alias_resource_id": bson::to_bson(&resource_id)?
+        };
+
+        Ok(resource_aliases_collection
+            .find_one(filter, None)
+            .await?
+            .map_or(resource_id, |resource_alias| resource_alias.resource_id))
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_resource_alias(&self, resource_alias: ResourceAlias) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resource_aliases_collection: Collection<ResourceAlias> =
+            db.collection("resource_aliases");
+
+        // the unique index on alias_resource_id refuses registering an alias twice
+        resource_aliases_collection
+            .insert_one(resource_alias, None)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn delete_resource_alias(&self, alias_resource_id: Uuid) -> Result<()> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resource_aliases_collection: Collection<ResourceAlias> =
+            db.collection("resource_aliases");
+
+        let delete_filter = doc! {
+            "alias_resource_id": bson::to_bson(&alias_resource_id)?
+        };
+
+        let delete_result = resource_aliases_collection
+            .delete_one(delete_filter, None)
+            .await?;
+
+        if delete_result.deleted_count == 0 {
+            info!("no resource alias documentations deleted")
+        }
+
+        Ok(())
+    }
+
+    async fn find_resource_aliases(&self, resource_id: Uuid) -> Result<Vec<ResourceAlias>> {
+        let db = self.mongo_client.database(&self.mongo_config.mongo_db_name);
+        let resource_aliases_collection: Collection<ResourceAlias> =
+            db.collection("resource_aliases");
+
+        let filter = doc! {
+            "resource_id": bson::to_bson(&resource_id)?
+        };
+        let find_options = FindOptions::builder()
+            .sort(doc! { "created_timestamp": 1, "alias_resource_id": 1 })
+            .build();
+
+        let results: Vec<ResourceAlias> = resource_aliases_collection
+            .find(filter, find_options)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_names(indexes: &[IndexModel]) -> Vec<&str> {
+        indexes
+            .iter()
+            .map(|index| {
+                index
+                    .options
+                    .as_ref()
+                    .and_then(|options| options.name.as_deref())
+                    .expect("indexes are created by name")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn indexes_are_named_uniquely_per_collection() {
+        // existing indexes are told apart by name, a nameless or repeated one would be created
+        // again on every start
+        for indexes in [
+            comment_indexes(),
+            comment_reaction_indexes(),
+            deleted_comment_content_indexes(),
+            pending_comment_indexes(),
+            resource_indexes(),
+            resource_alias_indexes(),
+        ] {
+            let names = index_names(&indexes);
+            let mut unique_names = names.clone();
+            unique_names.sort_unstable();
+            unique_names.dedup();
+            assert_eq!(unique_names.len(), names.len(), "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn comment_ids_are_unique() {
+        let indexes = comment_indexes();
+        let comment_id_index = indexes
+            .iter()
+            .find(|index| index.keys == doc! { "comment_id": 1 })
+            .unwrap();
+
+        assert_eq!(
+            comment_id_index
+                .options
+                .as_ref()
+                .and_then(|options| options.unique),
+            Some(true)
+        );
+    }
 }
@@ -0,0 +1,1349 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{future::BoxFuture, FutureExt};
+use sqlx::{
+    migrate::Migrate,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    types::Json,
+    FromRow, SqliteConnection, SqlitePool,
+};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    common::utils::{
+        ancestor_comment_ids_from_materialized_path, append_uuid_to_materialized_path,
+        comment_id_from_materialized_path, materialized_path_to_uuid_list,
+        parent_comment_id_from_materialized_path, resource_id_from_materialized_path,
+    },
+    models::{
+        controversy_score, total_reaction_count, Comment, CommentCursor, CommentReaction,
+        CommentReactor, CommentSort, CommentType, Commenter, PendingComment, Resource,
+        ResourceAlias, ResourceSettings, CURRENT_COMMENT_SCHEMA_VERSION, DELETED_COMMENT_TEXT,
+    },
+    persistent::{transaction_retry_delay, CommentStore, MAX_TRANSACTION_ATTEMPTS},
+};
+
+const RESOURCE_SETTINGS_COLUMNS: &str = "delete_mode, comments_open, max_depth, moderation_mode";
+
+const COMMENT_COLUMNS: &str =
+    "comment_id, comment_type, commenter_account_id, commenter_username, \
+     commented_timestamp, comment_text, branch_comment_ids, materialized_path, \
+     resource_id, parent_comment_id, depth, direct_reply_count, total_descendant_count, \
+     reaction_counts, total_reaction_count, controversy_score, deleted, deleted_timestamp";
+
+const PENDING_COMMENT_COLUMNS: &str =
+    "comment_id, resource_id, parent_comment_id, commenter_account_id, commenter_username, \
+     commented_timestamp, comment_text";
+
+#[derive(Debug)]
+pub struct SqliteCommentStore {
+    pub pool: SqlitePool,
+}
+
+pub async fn init_sqlite_connection(database_path: &str) -> Result<SqlitePool> {
+    let options = SqliteConnectOptions::new()
+        .filename(database_path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal);
+
+    let pool = SqlitePoolOptions::new().connect_with(options).await?;
+
+    Ok(pool)
+}
+
+// json path of the count of the given emoji in reaction_counts. emoji unified codes are
+// checked to be hex code points joined by dashes, so they never need escaping
+fn reaction_count_json_path(emoji_unified_code: &str) -> String {
+    format!("$.\"{}\"", emoji_unified_code)
+}
+
+// number of uuids in a materialized path
+fn path_depth(materialized_path: &str) -> i64 {
+    materialized_path.split("->").count() as i64
+}
+
+// the columns listings in the given order are sorted by, most significant first, and whether
+// they are sorted in descending order
+fn sort_columns(sort: CommentSort) -> (&'static [&'static str], bool) {
+    match sort {
+        CommentSort::Newest => (&["commented_timestamp", "comment_id"], true),
+        CommentSort::Oldest => (&["commented_timestamp", "comment_id"], false),
+        CommentSort::Top => (
+            &["total_reaction_count", "commented_timestamp", "comment_id"],
+            true,
+        ),
+        CommentSort::Controversial => (
+            &["controversy_score", "commented_timestamp", "comment_id"],
+            true,
+        ),
+    }
+}
+
+// ORDER BY terms of listings in the given order
+fn order_by(sort: CommentSort) -> String {
+    let (columns, descending) = sort_columns(sort);
+    let direction = if descending { "DESC" } else { "ASC" };
+
+    columns
+        .iter()
+        .map(|column| format!("{} {}", column, direction))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// condition matching the comments listed after the one the cursor points at, whose sort key is
+// bound to the parameters from the given one on
+fn after_condition(sort: CommentSort, first_parameter: usize) -> String {
+    let (columns, descending) = sort_columns(sort);
+    let parameters: Vec<String> = (first_parameter..first_parameter + columns.len())
+        .map(|parameter| format!("?{}", parameter))
+        .collect();
+
+    format!(
+        "({}) {} ({})",
+        columns.join(", "),
+        if descending { "<" } else { ">" },
+        parameters.join(", ")
+    )
+}
+
+// condition matching the comments directly below the given materialized path, and the id it
+// binds to the first parameter: the root comments of a resource, or the replies to a comment.
+// both are served in every order by the sort indexes
+fn next_level_condition(materialized_path: &str) -> Result<(&'static str, Uuid)> {
+    match materialized_path_to_uuid_list(materialized_path)?.as_slice() {
+        [resource_id] => Ok(("resource_id = ?1 AND depth = 1", *resource_id)),
+        [_, .., comment_id] => Ok(("parent_comment_id = ?1", *comment_id)),
+        [] => Err(anyhow::anyhow!("empty materialized path")),
+    }
+}
+
+// recomputes the reaction based sort keys of a comment from its reaction counts
+async fn update_reaction_scores(connection: &mut SqliteConnection, comment_id: Uuid) -> Result<()> {
+    let (reaction_counts,): (Json<BTreeMap<String, u32>>,) =
+        sqlx::query_as("SELECT reaction_counts FROM comments WHERE comment_id = ?1")
+            .bind(comment_id.to_string())
+            .fetch_one(&mut *connection)
+            .await?;
+
+    sqlx::query(
+        "UPDATE comments SET total_reaction_count = ?2, controversy_score = ?3 \
+         WHERE comment_id = ?1",
+    )
+    .bind(comment_id.to_string())
+    .bind(i64::from(total_reaction_count(&reaction_counts)))
+    .bind(controversy_score(&reaction_counts))
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct CommentRow {
+    comment_id: String,
+    comment_type: String,
+    commenter_account_id: String,
+    commenter_username: String,
+    commented_timestamp: DateTime<Utc>,
+    comment_text: String,
+    branch_comment_ids: String,
+    materialized_path: String,
+    resource_id: String,
+    parent_comment_id: Option<String>,
+    depth: i64,
+    direct_reply_count: i64,
+    total_descendant_count: i64,
+    reaction_counts: Json<BTreeMap<String, u32>>,
+    total_reaction_count: i64,
+    controversy_score: f64,
+    deleted: bool,
+    deleted_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+struct PendingCommentRow {
+    comment_id: String,
+    resource_id: String,
+    parent_comment_id: Option<String>,
+    commenter_account_id: String,
+    commenter_username: String,
+    commented_timestamp: DateTime<Utc>,
+    comment_text: String,
+}
+
+#[derive(FromRow)]
+struct ResourceRow {
+    resource_id: String,
+    title: String,
+    canonical_url: Option<String>,
+    owner_account_id: Option<String>,
+    created_timestamp: DateTime<Utc>,
+    #[sqlx(flatten)]
+    settings: ResourceSettingsRow,
+}
+
+#[derive(FromRow)]
+struct ResourceSettingsRow {
+    delete_mode: String,
+    comments_open: bool,
+    max_depth: Option<i64>,
+    moderation_mode: String,
+}
+
+#[derive(FromRow)]
+struct ResourceAliasRow {
+    alias_resource_id: String,
+    resource_id: String,
+    created_timestamp: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct CommentReactionRow {
+    reactor_account_id: String,
+    reactor_username: String,
+    emoji_unified_code: String,
+}
+
+#[derive(FromRow)]
+struct CommentIdReactionRow {
+    comment_id: String,
+    #[sqlx(flatten)]
+    reaction: CommentReactionRow,
+}
+
+impl TryFrom<CommentRow> for Comment {
+    type Error = anyhow::Error;
+
+    fn try_from(row: CommentRow) -> Result<Self> {
+        Ok(Comment {
+            comment_id: Uuid::parse_str(&row.comment_id)?,
+            comment_type: row.comment_type.parse()?,
+            commenter: Commenter {
+                account_id: Uuid::parse_str(&row.commenter_account_id)?,
+                username: row.commenter_username,
+            },
+            commented_timestamp: row.commented_timestamp,
+            comment_text: row.comment_text,
+            branch_comment_ids: serde_json::from_str(&row.branch_comment_ids)?,
+            // the ancestors are part of the path, so they aren't stored apart
+            ancestor_comment_ids: ancestor_comment_ids_from_materialized_path(
+                &row.materialized_path,
+            ),
+            materialized_path: row.materialized_path,
+            resource_id: Uuid::parse_str(&row.resource_id)?,
+            parent_comment_id: row
+                .parent_comment_id
+                .map(|parent_comment_id| Uuid::parse_str(&parent_comment_id))
+                .transpose()?,
+            depth: row.depth.try_into()?,
+            direct_reply_count: row.direct_reply_count.try_into()?,
+            total_descendant_count: row.total_descendant_count.try_into()?,
+            reaction_counts: row.reaction_counts.0,
+            total_reaction_count: row.total_reaction_count.try_into()?,
+            controversy_score: row.controversy_score,
+            deleted: row.deleted,
+            deleted_timestamp: row.deleted_timestamp,
+            // the table layout is versioned by the sql migrations, so every row is
+            // already in the current shape
+            schema_version: CURRENT_COMMENT_SCHEMA_VERSION,
+        })
+    }
+}
+
+impl TryFrom<PendingCommentRow> for PendingComment {
+    type Error = anyhow::Error;
+
+    fn try_from(row: PendingCommentRow) -> Result<Self> {
+        Ok(PendingComment {
+            comment_id: Uuid::parse_str(&row.comment_id)?,
+            resource_id: Uuid::parse_str(&row.resource_id)?,
+            parent_comment_id: row
+                .parent_comment_id
+                .map(|parent_comment_id| Uuid::parse_str(&parent_comment_id))
+                .transpose()?,
+            commenter: Commenter {
+                account_id: Uuid::parse_str(&row.commenter_account_id)?,
+                username: row.commenter_username,
+            },
+            commented_timestamp: row.commented_timestamp,
+            comment_text: row.comment_text,
+        })
+    }
+}
+
+impl TryFrom<ResourceRow> for Resource {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ResourceRow) -> Result<Self> {
+        Ok(Resource {
+            resource_id: Uuid::parse_str(&row.resource_id)?,
+            title: row.title,
+            canonical_url: row.canonical_url,
+            owner_account_id: row
+                .owner_account_id
+                .map(|owner_account_id| Uuid::parse_str(&owner_account_id))
+                .transpose()?,
+            created_timestamp: row.created_timestamp,
+            settings: row.settings.try_into()?,
+        })
+    }
+}
+
+impl TryFrom<ResourceSettingsRow> for ResourceSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ResourceSettingsRow) -> Result<Self> {
+        Ok(ResourceSettings {
+            delete_mode: row.delete_mode.parse()?,
+            comments_open: row.comments_open,
+            max_depth: row.max_depth.map(u32::try_from).transpose()?,
+            moderation_mode: row.moderation_mode.parse()?,
+        })
+    }
+}
+
+impl TryFrom<ResourceAliasRow> for ResourceAlias {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ResourceAliasRow) -> Result<Self> {
+        Ok(ResourceAlias {
+            alias_resource_id: Uuid::parse_str(&row.alias_resource_id)?,
+            resource_id: Uuid::parse_str(&row.resource_id)?,
+            created_timestamp: row.created_timestamp,
+        })
+    }
+}
+
+impl TryFrom<CommentReactionRow> for CommentReaction {
+    type Error = anyhow::Error;
+
+    fn try_from(row: CommentReactionRow) -> Result<Self> {
+        Ok(CommentReaction {
+            reactor: CommentReactor {
+                account_id: Uuid::parse_str(&row.reactor_account_id)?,
+                username: row.reactor_username,
+            },
+            emoji_unified_code: row.emoji_unified_code,
+        })
+    }
+}
+
+// primary result codes of the errors a transaction fails with when another connection holds the
+// database lock, after which it can be run again
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+fn is_transient_transaction_error(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(database_error)) => database_error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            // sqlite reports extended result codes, whose low byte is the primary one
+            .map(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+impl SqliteCommentStore {
+    // runs the callback in a transaction, and runs it again when the database is locked by
+    // another connection. the context is passed to the callback on every attempt
+    async fn with_transaction<C, R, F>(&self, context: &C, callback: F) -> Result<R>
+    where
+        C: Sync + ?Sized,
+        R: Send,
+        F: for<'a> Fn(&'a mut SqliteConnection, &'a C) -> BoxFuture<'a, Result<R>> + Send + Sync,
+    {
+        let mut attempt = 1;
+        loop {
+            let result: Result<R> = async {
+                let mut transaction = self.pool.begin().await?;
+
+                let value = callback(&mut transaction, context).await?;
+
+                transaction.commit().await?;
+
+                Ok(value)
+            }
+            .await;
+
+            match result {
+                Err(e)
+                    if attempt < MAX_TRANSACTION_ATTEMPTS && is_transient_transaction_error(&e) =>
+                {
+                    tokio::time::sleep(transaction_retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommentStore for SqliteCommentStore {
+    async fn migrate(&self) -> Result<Vec<String>> {
+        let migrator = sqlx::migrate!("./migrations/sqlite");
+
+        let applied_versions: Vec<i64> = {
+            let mut connection = self.pool.acquire().await?;
+            connection.ensure_migrations_table().await?;
+            connection
+                .list_applied_migrations()
+                .await?
+                .into_iter()
+                .map(|applied_migration| applied_migration.version)
+                .collect()
+        };
+
+        // sqlx records the applied migrations in its own _sqlx_migrations table
+        migrator.run(&self.pool).await?;
+
+        Ok(migrator
+            .iter()
+            .filter(|migration| !applied_versions.contains(&migration.version))
+            .map(|migration| format!("{}_{}", migration.version, migration.description))
+            .collect())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_comment(&self, comment: Comment) -> Result<()> {
+        self.with_transaction(&comment, |connection, comment| {
+            async move {
+                sqlx::query(
+                    "INSERT INTO comments (comment_id, comment_type, commenter_account_id, \
+                     commenter_username, commented_timestamp, comment_text, branch_comment_ids, \
+                     materialized_path, path_depth, resource_id, parent_comment_id, depth, \
+                     deleted, deleted_timestamp) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                )
+                .bind(comment.comment_id.to_string())
+                .bind(comment.comment_type.as_str())
+                .bind(comment.commenter.account_id.to_string())
+                .bind(&comment.commenter.username)
+                .bind(comment.commented_timestamp)
+                .bind(&comment.comment_text)
+                .bind(serde_json::to_string(&comment.branch_comment_ids)?)
+                .bind(&comment.materialized_path)
+                .bind(path_depth(&comment.materialized_path))
+                .bind(comment.resource_id.to_string())
+                .bind(
+                    comment
+                        .parent_comment_id
+                        .map(|parent_comment_id| parent_comment_id.to_string()),
+                )
+                .bind(i64::from(comment.depth))
+                .bind(comment.deleted)
+                .bind(comment.deleted_timestamp)
+                .execute(&mut *connection)
+                .await?;
+
+                if let Some(parent_comment_id) = comment.parent_comment_id {
+                    let update_result = sqlx::query(
+                        "UPDATE comments \
+                         SET branch_comment_ids = json_insert(branch_comment_ids, '$[#]', ?2), \
+                         direct_reply_count = direct_reply_count + 1 WHERE comment_id = ?1",
+                    )
+                    .bind(parent_comment_id.to_string())
+                    .bind(comment.comment_id.to_string())
+                    .execute(&mut *connection)
+                    .await?;
+
+                    // the comment it was branched from may have been deleted in the meantime
+                    if update_result.rows_affected() == 0 {
+                        return Err(anyhow::anyhow!("branched from comment not found"));
+                    }
+                }
+
+                let ancestor_comment_ids =
+                    ancestor_comment_ids_from_materialized_path(&comment.materialized_path);
+                if !ancestor_comment_ids.is_empty() {
+                    sqlx::query(
+                        "UPDATE comments SET total_descendant_count = total_descendant_count + 1 \
+                         WHERE comment_id IN (SELECT value FROM json_each(?1))",
+                    )
+                    .bind(serde_json::to_string(&ancestor_comment_ids)?)
+                    .execute(&mut *connection)
+                    .await?;
+                }
+
+                Ok(())
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_pending_comment(&self, pending_comment: PendingComment) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT INTO pending_comments ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            PENDING_COMMENT_COLUMNS
+        ))
+        .bind(pending_comment.comment_id.to_string())
+        .bind(pending_comment.resource_id.to_string())
+        .bind(
+            pending_comment
+                .parent_comment_id
+                .map(|parent_comment_id| parent_comment_id.to_string()),
+        )
+        .bind(pending_comment.commenter.account_id.to_string())
+        .bind(&pending_comment.commenter.username)
+        .bind(pending_comment.commented_timestamp)
+        .bind(&pending_comment.comment_text)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_pending_comment(&self, comment_id: Uuid) -> Result<PendingComment> {
+        let row: Option<PendingCommentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM pending_comments WHERE comment_id = ?1",
+            PENDING_COMMENT_COLUMNS
+        ))
+        .bind(comment_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => PendingComment::try_from(row),
+            None => Err(anyhow::Error::msg("pending comment not found")),
+        }
+    }
+
+    async fn find_pending_comments(&self, resource_id: Uuid) -> Result<Vec<PendingComment>> {
+        let rows: Vec<PendingCommentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM pending_comments WHERE resource_id = ?1 \
+             ORDER BY commented_timestamp ASC, comment_id ASC",
+            PENDING_COMMENT_COLUMNS
+        ))
+        .bind(resource_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(PendingComment::try_from).collect()
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn delete_pending_comment(&self, comment_id: Uuid) -> Result<()> {
+        let delete_result = sqlx::query("DELETE FROM pending_comments WHERE comment_id = ?1")
+            .bind(comment_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        if delete_result.rows_affected() == 0 {
+            info!("no pending comment documentations deleted")
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn append_reaction_to_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_append: CommentReaction,
+    ) -> Result<()> {
+        self.with_transaction(
+            &(comment_id, comment_reaction_to_append),
+            |connection, (comment_id, comment_reaction_to_append)| {
+                async move {
+                    // the foreign key on comment_id rejects reactions to comments that don't
+                    // exist, and a reactor reacts with a given emoji at most once
+                    let insert_result = sqlx::query(
+                        "INSERT INTO comment_reactions (comment_id, reactor_account_id, \
+                         reactor_username, emoji_unified_code) VALUES (?1, ?2, ?3, ?4) \
+                         ON CONFLICT (comment_id, reactor_account_id, emoji_unified_code) \
+                         DO NOTHING",
+                    )
+                    .bind(comment_id.to_string())
+                    .bind(comment_reaction_to_append.reactor.account_id.to_string())
+                    .bind(&comment_reaction_to_append.reactor.username)
+                    .bind(&comment_reaction_to_append.emoji_unified_code)
+                    .execute(&mut *connection)
+                    .await?;
+
+                    if insert_result.rows_affected() > 0 {
+                        sqlx::query(
+                            "UPDATE comments SET reaction_counts = json_set(reaction_counts, ?2, \
+                             COALESCE(json_extract(reaction_counts, ?2), 0) + 1) \
+                             WHERE comment_id = ?1",
+                        )
+                        .bind(comment_id.to_string())
+                        .bind(reaction_count_json_path(
+                            &comment_reaction_to_append.emoji_unified_code,
+                        ))
+                        .execute(&mut *connection)
+                        .await?;
+
+                        update_reaction_scores(connection, *comment_id).await?;
+                    }
+
+                    Ok(())
+                }
+                .boxed()
+            },
+        )
+        .await
+        .context("error updating (appending reaction to) comment documents")
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn remove_reaction_from_comment(
+        &self,
+        comment_id: Uuid,
+        comment_reaction_to_remove: CommentReaction,
+    ) -> Result<()> {
+        self.with_transaction(
+            &(comment_id, comment_reaction_to_remove),
+            |connection, (comment_id, comment_reaction_to_remove)| {
+                async move {
+                    let delete_result = sqlx::query(
+                        "DELETE FROM comment_reactions WHERE comment_id = ?1 \
+                         AND reactor_account_id = ?2 AND emoji_unified_code = ?3",
+                    )
+                    .bind(comment_id.to_string())
+                    .bind(comment_reaction_to_remove.reactor.account_id.to_string())
+                    .bind(&comment_reaction_to_remove.emoji_unified_code)
+                    .execute(&mut *connection)
+                    .await?;
+
+                    if delete_result.rows_affected() == 0 {
+                        return Err(anyhow::anyhow!(
+                            "error updating (removing reaction from) comment documents"
+                        ));
+                    }
+
+                    // the emoji is dropped from the counts once nobody reacts with it anymore
+                    sqlx::query(
+                        "UPDATE comments SET reaction_counts = CASE \
+                             WHEN json_extract(reaction_counts, ?2) > 1 THEN json_set(\
+                                 reaction_counts, ?2, json_extract(reaction_counts, ?2) - 1\
+                             ) \
+                             ELSE json_remove(reaction_counts, ?2) \
+                         END WHERE comment_id = ?1",
+                    )
+                    .bind(comment_id.to_string())
+                    .bind(reaction_count_json_path(
+                        &comment_reaction_to_remove.emoji_unified_code,
+                    ))
+                    .execute(&mut *connection)
+                    .await?;
+
+                    update_reaction_scores(connection, *comment_id).await?;
+
+                    Ok(())
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn update_comment_text(&self, comment_id: Uuid, new_comment_text: String) -> Result<()> {
+        let update_result =
+            sqlx::query("UPDATE comments SET comment_text = ?2 WHERE comment_id = ?1")
+                .bind(comment_id.to_string())
+                .bind(&new_comment_text)
+                .execute(&self.pool)
+                .await?;
+
+        if update_result.rows_affected() == 0 {
+            info!("no comment documentations updated")
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn tombstone_comment(
+        &self,
+        comment_id: Uuid,
+        deleted_timestamp: DateTime<Utc>,
+    ) -> Result<()> {
+        self.with_transaction(
+            &(comment_id, deleted_timestamp),
+            |connection, (comment_id, deleted_timestamp)| {
+                async move {
+                    // keep the text and the author apart, so that the comment can be restored
+                    sqlx::query(
+                        "INSERT INTO deleted_comment_contents (comment_id, commenter_account_id, \
+                         commenter_username, comment_text, deleted_timestamp) \
+                         SELECT comment_id, commenter_account_id, commenter_username, \
+                         comment_text, ?2 FROM comments WHERE comment_id = ?1 AND NOT deleted",
+                    )
+                    .bind(comment_id.to_string())
+                    .bind(deleted_timestamp)
+                    .execute(&mut *connection)
+                    .await?;
+
+                    let deleted_commenter = Commenter::deleted();
+
+                    let update_result = sqlx::query(
+                        "UPDATE comments SET comment_text = ?2, commenter_account_id = ?3, \
+                         commenter_username = ?4, deleted = TRUE, deleted_timestamp = ?5 \
+                         WHERE comment_id = ?1 AND NOT deleted",
+                    )
+                    .bind(comment_id.to_string())
+                    .bind(DELETED_COMMENT_TEXT)
+                    .bind(deleted_commenter.account_id.to_string())
+                    .bind(&deleted_commenter.username)
+                    .bind(deleted_timestamp)
+                    .execute(&mut *connection)
+                    .await?;
+
+                    if update_result.rows_affected() == 0 {
+                        info!("no comment documentations updated")
+                    }
+
+                    Ok(())
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn restore_comment(&self, comment_id: Uuid) -> Result<()> {
+        self.with_transaction(&comment_id, |connection, comment_id| {
+            async move {
+                let update_result = sqlx::query(
+                    "UPDATE comments SET comment_text = kept.comment_text, \
+                     commenter_account_id = kept.commenter_account_id, \
+                     commenter_username = kept.commenter_username, \
+                     deleted = FALSE, deleted_timestamp = NULL \
+                     FROM deleted_comment_contents AS kept \
+                     WHERE comments.comment_id = ?1 AND kept.comment_id = comments.comment_id",
+                )
+                .bind(comment_id.to_string())
+                .execute(&mut *connection)
+                .await?;
+
+                if update_result.rows_affected() == 0 {
+                    return Err(anyhow::anyhow!("deleted comment content not found"));
+                }
+
+                sqlx::query("DELETE FROM deleted_comment_contents WHERE comment_id = ?1")
+                    .bind(comment_id.to_string())
+                    .execute(&mut *connection)
+                    .await?;
+
+                Ok(())
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn discard_deleted_comment_content(&self, comment_id: Uuid) -> Result<()> {
+        let delete_result =
+            sqlx::query("DELETE FROM deleted_comment_contents WHERE comment_id = ?1")
+                .bind(comment_id.to_string())
+                .execute(&self.pool)
+                .await?;
+
+        if delete_result.rows_affected() == 0 {
+            info!("no deleted comment contents discarded");
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn prune_comments(&self, comment_materialized_path: String) -> Result<()> {
+        self.with_transaction(
+            &comment_materialized_path,
+            |connection, comment_materialized_path| {
+                async move {
+                    // reactions go with their comments through ON DELETE CASCADE
+                    let delete_result =
+                        sqlx::query("DELETE FROM comments WHERE materialized_path GLOB ?1 || '*'")
+                            .bind(comment_materialized_path)
+                            .execute(&mut *connection)
+                            .await?;
+
+                    let deleted_count = delete_result.rows_affected();
+                    if deleted_count == 0 {
+                        info!("no comment documentations deleted");
+                        return Ok(());
+                    }
+
+                    if let (Some(parent_comment_id), Some(pruned_comment_id)) = (
+                        parent_comment_id_from_materialized_path(comment_materialized_path),
+                        comment_id_from_materialized_path(comment_materialized_path),
+                    ) {
+                        sqlx::query(
+                            "UPDATE comments SET branch_comment_ids = ( \
+                                 SELECT json_group_array(value) FROM json_each(branch_comment_ids) \
+                                 WHERE value != ?2 \
+                             ), direct_reply_count = direct_reply_count - 1 WHERE comment_id = ?1",
+                        )
+                        .bind(parent_comment_id.to_string())
+                        .bind(pruned_comment_id.to_string())
+                        .execute(&mut *connection)
+                        .await?;
+                    }
+
+                    let ancestor_comment_ids =
+                        ancestor_comment_ids_from_materialized_path(comment_materialized_path);
+                    if !ancestor_comment_ids.is_empty() {
+                        sqlx::query(
+                            "UPDATE comments \
+                             SET total_descendant_count = total_descendant_count - ?2 \
+                             WHERE comment_id IN (SELECT value FROM json_each(?1))",
+                        )
+                        .bind(serde_json::to_string(&ancestor_comment_ids)?)
+                        .bind(i64::try_from(deleted_count)?)
+                        .execute(&mut *connection)
+                        .await?;
+                    }
+
+                    Ok(())
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn move_comments(
+        &self,
+        comment_materialized_path: String,
+        new_parent_materialized_path: String,
+    ) -> Result<()> {
+        self.with_transaction(
+            &(comment_materialized_path, new_parent_materialized_path),
+            |connection, (comment_materialized_path, new_parent_materialized_path)| {
+                async move {
+                    let comment_id = comment_id_from_materialized_path(comment_materialized_path)
+                        .filter(|_| path_depth(comment_materialized_path) > 1)
+                        .ok_or_else(|| anyhow::anyhow!("not the path of a comment"))?;
+                    if materialized_path_to_uuid_list(new_parent_materialized_path)?
+                        .contains(&comment_id)
+                    {
+                        return Err(anyhow::anyhow!("cannot move a comment below itself"));
+                    }
+
+                    let new_materialized_path =
+                        append_uuid_to_materialized_path(new_parent_materialized_path, &comment_id);
+                    let new_parent_comment_id =
+                        parent_comment_id_from_materialized_path(&new_materialized_path);
+                    let resource_id = resource_id_from_materialized_path(&new_materialized_path)
+                        .ok_or_else(|| anyhow::anyhow!("invalid materialized path"))?;
+
+                    let depth_change =
+                        path_depth(&new_materialized_path) - path_depth(comment_materialized_path);
+
+                    let move_result = sqlx::query(
+                        "UPDATE comments \
+                         SET materialized_path = ?2 || substr(materialized_path, ?3), \
+                         path_depth = path_depth + ?4, depth = depth + ?4, resource_id = ?5 \
+                         WHERE materialized_path GLOB ?1 || '*'",
+                    )
+                    .bind(comment_materialized_path)
+                    .bind(&new_materialized_path)
+                    .bind(i64::try_from(comment_materialized_path.len() + 1)?)
+                    .bind(depth_change)
+                    .bind(resource_id.to_string())
+                    .execute(&mut *connection)
+                    .await?;
+
+                    let moved_count = i64::try_from(move_result.rows_affected())?;
+                    if moved_count == 0 {
+                        return Err(anyhow::anyhow!("comment not found"));
+                    }
+
+                    sqlx::query(
+                        "UPDATE comments SET parent_comment_id = ?2, comment_type = ?3 \
+                         WHERE comment_id = ?1",
+                    )
+                    .bind(comment_id.to_string())
+                    .bind(new_parent_comment_id.map(|uuid| uuid.to_string()))
+                    .bind(match new_parent_comment_id {
+                        Some(_) => CommentType::Branch.as_str(),
+                        None => CommentType::Root.as_str(),
+                    })
+                    .execute(&mut *connection)
+                    .await?;
+
+                    if let Some(parent_comment_id) =
+                        parent_comment_id_from_materialized_path(comment_materialized_path)
+                    {
+                        sqlx::query(
+                            "UPDATE comments SET branch_comment_ids = ( \
+                                 SELECT json_group_array(value) FROM json_each(branch_comment_ids) \
+                                 WHERE value != ?2 \
+                             ), direct_reply_count = direct_reply_count - 1 WHERE comment_id = ?1",
+                        )
+                        .bind(parent_comment_id.to_string())
+                        .bind(comment_id.to_string())
+                        .execute(&mut *connection)
+                        .await?;
+                    }
+
+                    sqlx::query(
+                        "UPDATE comments \
+                         SET total_descendant_count = total_descendant_count - ?2 \
+                         WHERE comment_id IN (SELECT value FROM json_each(?1))",
+                    )
+                    .bind(serde_json::to_string(
+                        &ancestor_comment_ids_from_materialized_path(comment_materialized_path),
+                    )?)
+                    .bind(moved_count)
+                    .execute(&mut *connection)
+                    .await?;
+
+                    // the moved comment takes its place among the replies by when it was made
+                    if let Some(new_parent_comment_id) = new_parent_comment_id {
+                        let update_result = sqlx::query(
+                            "UPDATE comments SET branch_comment_ids = ( \
+                                 SELECT json_group_array(comment_id) FROM ( \
+                                     SELECT child.comment_id FROM comments AS child \
+                                     WHERE child.parent_comment_id = comments.comment_id \
+                                     ORDER BY child.commented_timestamp \
+                                 ) \
+                             ), direct_reply_count = direct_reply_count + 1 WHERE comment_id = ?1",
+                        )
+                        .bind(new_parent_comment_id.to_string())
+                        .execute(&mut *connection)
+                        .await?;
+
+                        if update_result.rows_affected() == 0 {
+                            return Err(anyhow::anyhow!("new parent comment not found"));
+                        }
+                    }
+
+                    sqlx::query(
+                        "UPDATE comments \
+                         SET total_descendant_count = total_descendant_count + ?2 \
+                         WHERE comment_id IN (SELECT value FROM json_each(?1))",
+                    )
+                    .bind(serde_json::to_string(
+                        &ancestor_comment_ids_from_materialized_path(&new_materialized_path),
+                    )?)
+                    .bind(moved_count)
+                    .execute(&mut *connection)
+                    .await?;
+
+                    Ok(())
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn rebuild_branch_comment_ids(&self, current_path: String) -> Result<()> {
+        sqlx::query(
+            "UPDATE comments SET branch_comment_ids = ( \
+                 SELECT json_group_array(comment_id) FROM ( \
+                     SELECT child.comment_id FROM comments AS child \
+                     WHERE child.materialized_path GLOB comments.materialized_path || '->*' \
+                     AND child.path_depth = comments.path_depth + 1 \
+                     ORDER BY child.commented_timestamp \
+                 ) \
+             ) WHERE materialized_path GLOB ?1 || '*'",
+        )
+        .bind(&current_path)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_comment(&self, comment_id: Uuid) -> Result<Comment> {
+        let row: Option<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM comments WHERE comment_id = ?1",
+            COMMENT_COLUMNS
+        ))
+        .bind(comment_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Comment::try_from(row),
+            None => Err(anyhow::Error::msg("comment not found")),
+        }
+    }
+
+    async fn find_comment_reactions(
+        &self,
+        comment_id: Uuid,
+        limit: Option<u32>,
+    ) -> Result<Vec<CommentReaction>> {
+        // a negative limit means no limit in sqlite
+        let rows: Vec<CommentReactionRow> = sqlx::query_as(
+            "SELECT reactor_account_id, reactor_username, emoji_unified_code \
+             FROM comment_reactions WHERE comment_id = ?1 ORDER BY reaction_id LIMIT ?2",
+        )
+        .bind(comment_id.to_string())
+        .bind(limit.map(i64::from).unwrap_or(-1))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(CommentReaction::try_from).collect()
+    }
+
+    async fn find_reactions_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, Vec<CommentReaction>>> {
+        // the ids go in as one json array, sqlite takes no lists of parameters
+        let rows: Vec<CommentIdReactionRow> = sqlx::query_as(
+            "SELECT comment_id, reactor_account_id, reactor_username, emoji_unified_code \
+             FROM comment_reactions WHERE comment_id IN (SELECT value FROM json_each(?1)) \
+             ORDER BY reaction_id",
+        )
+        .bind(serde_json::to_string(&comment_ids)?)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut results: HashMap<Uuid, Vec<CommentReaction>> = HashMap::new();
+        for row in rows {
+            results
+                .entry(Uuid::parse_str(&row.comment_id)?)
+                .or_default()
+                .push(CommentReaction::try_from(row.reaction)?);
+        }
+
+        Ok(results)
+    }
+
+    async fn find_replies_of_comments(
+        &self,
+        comment_ids: Vec<Uuid>,
+        sort: CommentSort,
+        limit: u32,
+    ) -> Result<HashMap<Uuid, Vec<Comment>>> {
+        // the replies are ranked per comment they reply to, so that one query serves them all.
+        // the ids go in as one json array, sqlite takes no lists of parameters
+        let rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY parent_comment_id \
+             ORDER BY {}) AS reply_rank FROM comments \
+             WHERE parent_comment_id IN (SELECT value FROM json_each(?1))) \
+             AS replies WHERE reply_rank <= ?2 ORDER BY reply_rank",
+            COMMENT_COLUMNS,
+            order_by(sort)
+        ))
+        .bind(serde_json::to_string(&comment_ids)?)
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut results: HashMap<Uuid, Vec<Comment>> = HashMap::new();
+        for row in rows {
+            let comment = Comment::try_from(row)?;
+            if let Some(parent_comment_id) = comment.parent_comment_id {
+                results.entry(parent_comment_id).or_default().push(comment);
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn find_next_level_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        limit: Option<u32>,
+        after: Option<CommentCursor>,
+    ) -> Result<Vec<Comment>> {
+        let after_condition = match after {
+            Some(_) => format!("AND {}", after_condition(sort, 3)),
+            None => String::new(),
+        };
+
+        let (next_level_condition, id) = next_level_condition(&current_path)?;
+
+        let query_text = format!(
+            "SELECT {} FROM comments WHERE {} {} ORDER BY {} LIMIT ?2",
+            COMMENT_COLUMNS,
+            next_level_condition,
+            after_condition,
+            order_by(sort)
+        );
+
+        // a negative limit means no limit in sqlite
+        let mut query = sqlx::query_as(&query_text)
+            .bind(id.to_string())
+            .bind(limit.map(i64::from).unwrap_or(-1));
+
+        if let Some(after) = after {
+            let score = after.score.unwrap_or_default();
+            query = match sort {
+                CommentSort::Newest | CommentSort::Oldest => query,
+                CommentSort::Top => query.bind(score as i64),
+                CommentSort::Controversial => query.bind(score),
+            };
+            query = query
+                .bind(after.commented_timestamp)
+                .bind(after.comment_id.to_string());
+        }
+
+        let rows: Vec<CommentRow> = query.fetch_all(&self.pool).await?;
+
+        rows.into_iter().map(Comment::try_from).collect()
+    }
+
+    async fn find_all_comments(
+        &self,
+        current_path: String,
+        sort: CommentSort,
+        max_depth: Option<u32>,
+    ) -> Result<Vec<Comment>> {
+        // every uuid has the same length, so ordering by the number of path elements is
+        // the same as ordering by the length of the materialized path
+        let rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM comments WHERE materialized_path GLOB ?1 || '*' \
+             AND (?2 IS NULL OR depth <= ?2) ORDER BY path_depth ASC, {}",
+            COMMENT_COLUMNS,
+            order_by(sort)
+        ))
+        .bind(&current_path)
+        .bind(max_depth.map(i64::from))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(Comment::try_from).collect()
+    }
+
+    async fn find_all_comments_page(
+        &self,
+        current_path: String,
+        limit: u32,
+        after: Option<Comment>,
+    ) -> Result<Vec<Comment>> {
+        let after_condition = match after {
+            Some(_) => "AND (path_depth, commented_timestamp, comment_id) > (?3, ?4, ?5)",
+            None => "",
+        };
+
+        let query_text = format!(
+            "SELECT {} FROM comments WHERE materialized_path GLOB ?1 || '*' {} \
+             ORDER BY path_depth ASC, {} LIMIT ?2",
+            COMMENT_COLUMNS,
+            after_condition,
+            order_by(CommentSort::Oldest)
+        );
+
+        let mut query = sqlx::query_as(&query_text)
+            .bind(&current_path)
+            .bind(i64::from(limit));
+
+        if let Some(after) = after {
+            query = query
+                .bind(path_depth(&after.materialized_path))
+                .bind(after.commented_timestamp)
+                .bind(after.comment_id.to_string());
+        }
+
+        let rows: Vec<CommentRow> = query.fetch_all(&self.pool).await?;
+
+        rows.into_iter().map(Comment::try_from).collect()
+    }
+
+    async fn find_expired_tombstones(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Comment>> {
+        let rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM comments WHERE deleted AND deleted_timestamp < ?1",
+            COMMENT_COLUMNS
+        ))
+        .bind(deleted_before)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(Comment::try_from).collect()
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_resource(&self, resource: Resource) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT INTO resources (resource_id, title, canonical_url, owner_account_id, \
+             created_timestamp, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            RESOURCE_SETTINGS_COLUMNS
+        ))
+        .bind(resource.resource_id.to_string())
+        .bind(&resource.title)
+        .bind(&resource.canonical_url)
+        .bind(
+            resource
+                .owner_account_id
+                .map(|owner_account_id| owner_account_id.to_string()),
+        )
+        .bind(resource.created_timestamp)
+        .bind(resource.settings.delete_mode.as_str())
+        .bind(resource.settings.comments_open)
+        .bind(resource.settings.max_depth.map(i64::from))
+        .bind(resource.settings.moderation_mode.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn update_resource(&self, resource: Resource) -> Result<()> {
+        let update_result = sqlx::query(
+            "UPDATE resources SET title = ?2, canonical_url = ?3, owner_account_id = ?4, \
+             delete_mode = ?5, comments_open = ?6, max_depth = ?7, moderation_mode = ?8 \
+             WHERE resource_id = ?1",
+        )
+        .bind(resource.resource_id.to_string())
+        .bind(&resource.title)
+        .bind(&resource.canonical_url)
+        .bind(
+            resource
+                .owner_account_id
+                .map(|owner_account_id| owner_account_id.to_string()),
+        )
+        .bind(resource.settings.delete_mode.as_str())
+        .bind(resource.settings.comments_open)
+        .bind(resource.settings.max_depth.map(i64::from))
+        .bind(resource.settings.moderation_mode.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        if update_result.rows_affected() == 0 {
+            info!("no resource documentations updated")
+        }
+
+        Ok(())
+    }
+
+    async fn find_resource(&self, resource_id: Uuid) -> Result<Resource> {
+        let row: Option<ResourceRow> = sqlx::query_as(&format!(
+            "SELECT resource_id, title, canonical_url, owner_account_id, created_timestamp, {} \
+             FROM resources WHERE resource_id = ?1",
+            RESOURCE_SETTINGS_COLUMNS
+        ))
+        .bind(resource_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Resource::try_from(row),
+            None => Err(anyhow::Error::msg("resource not found")),
+        }
+    }
+
+    async fn find_resource_settings(&self, resource_id: Uuid) -> Result<ResourceSettings> {
+        let row: Option<ResourceSettingsRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM resources WHERE resource_id = ?1",
+            RESOURCE_SETTINGS_COLUMNS
+        ))
+        .bind(resource_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => ResourceSettings::try_from(row),
+            None => Ok(ResourceSettings::default()),
+        }
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn merge_resources(&self, resource_alias: ResourceAlias) -> Result<()> {
+        self.with_transaction(&resource_alias, |connection, resource_alias| {
+            async move {
+                // every uuid is 36 characters long, the rest of the path is kept as it is
+                sqlx::query(
+                    "UPDATE comments SET materialized_path = ?2 || substr(materialized_path, 37), \
+                     resource_id = ?2 WHERE resource_id = ?1",
+                )
+                .bind(resource_alias.alias_resource_id.to_string())
+                .bind(resource_alias.resource_id.to_string())
+                .execute(&mut *connection)
+                .await?;
+
+                sqlx::query("UPDATE resource_aliases SET resource_id = ?2 WHERE resource_id = ?1")
+                    .bind(resource_alias.alias_resource_id.to_string())
+                    .bind(resource_alias.resource_id.to_string())
+                    .execute(&mut *connection)
+                    .await?;
+
+                sqlx::query(
+                    "INSERT INTO resource_aliases (alias_resource_id, resource_id, \
+                     created_timestamp) VALUES (?1, ?2, ?3)",
+                )
+                .bind(resource_alias.alias_resource_id.to_string())
+                .bind(resource_alias.resource_id.to_string())
+                .bind(resource_alias.created_timestamp)
+                .execute(&mut *connection)
+                .await?;
+
+                sqlx::query("DELETE FROM resources WHERE resource_id = ?1")
+                    .bind(resource_alias.alias_resource_id.to_string())
+                    .execute(&mut *connection)
+                    .await?;
+
+                Ok(())
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    async fn resolve_resource_id(&self, resource_id: Uuid) -> Result<Uuid> {
+        let row: Option<(String,)> =
+            sqlx::query_as("SELECT resource_id FROM resource_aliases WHERE alias_resource_id = ?1")
+                .bind(resource_id.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+
+        match row {
+            Some((resource_id,)) => Ok(Uuid::parse_str(&resource_id)?),
+            None => Ok(resource_id),
+        }
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn insert_resource_alias(&self, resource_alias: ResourceAlias) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO resource_aliases (alias_resource_id, resource_id, created_timestamp) \
+             VALUES (?1, ?2, ?3)",
+        )
+        .bind(resource_alias.alias_resource_id.to_string())
+        .bind(resource_alias.resource_id.to_string())
+        .bind(resource_alias.created_timestamp)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all)]
+    async fn delete_resource_alias(&self, alias_resource_id: Uuid) -> Result<()> {
+        let delete_result =
+            sqlx::query("DELETE FROM resource_aliases WHERE alias_resource_id = ?1")
+                .bind(alias_resource_id.to_string())
+                .execute(&self.pool)
+                .await?;
+
+        if delete_result.rows_affected() == 0 {
+            info!("no resource alias documentations deleted")
+        }
+
+        Ok(())
+    }
+
+    async fn find_resource_aliases(&self, resource_id: Uuid) -> Result<Vec<ResourceAlias>> {
+        let rows: Vec<ResourceAliasRow> = sqlx::query_as(
+            "SELECT alias_resource_id, resource_id, created_timestamp FROM resource_aliases \
+             WHERE resource_id = ?1 ORDER BY created_timestamp ASC, alias_resource_id ASC",
+        )
+        .bind(resource_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(ResourceAlias::try_from).collect()
+    }
+}
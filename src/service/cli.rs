@@ -0,0 +1,106 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use tracing::info;
+
+use crate::service::{
+    export::{write_thread_export, ExportFormat},
+    import::{import_export, ImportFormat},
+    purge::purge_expired_comments,
+    server::{apply_migrations, init_comment_store, init_server, Config},
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "commenter", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the web server (the default when no command is given)
+    Serve,
+    /// Apply pending storage migrations and exit
+    Migrate,
+    /// Purge deleted comments whose restore window has expired and exit
+    Purge,
+    /// Import the comments of an export from another comment system and exit
+    Import {
+        /// Comment system the export comes from
+        #[arg(long, value_enum)]
+        format: ImportFormat,
+        /// JSON object mapping the threads of the export to resource ids, optional for commenter's
+        /// own exports
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+        /// Path of the export
+        export: PathBuf,
+    },
+    /// Export the comments of a resource, with their reactions, and exit
+    Export {
+        /// Layout of the export
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// File to write the export to, the standard output if not given
+        #[arg(long)]
+        output: Option<PathBuf>,
+        resource_id: Uuid,
+    },
+}
+
+pub async fn run(cli: Cli) {
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => init_server().await,
+        Command::Migrate => {
+            let config = Config::from_env();
+            let comment_store = init_comment_store(&config).await;
+            apply_migrations(comment_store.as_ref()).await;
+        }
+        Command::Purge => {
+            let config = Config::from_env();
+            let comment_store = init_comment_store(&config).await;
+            let purged_count =
+                purge_expired_comments(comment_store.as_ref(), config.restore_window())
+                    .await
+                    .unwrap();
+            info!("Purged {} deleted comments", purged_count);
+        }
+        Command::Import {
+            format,
+            mapping,
+            export,
+        } => {
+            let config = Config::from_env();
+            let comment_store = init_comment_store(&config).await;
+            let report = import_export(comment_store.as_ref(), format, &export, mapping.as_deref())
+                .await
+                .unwrap();
+            for skipped_entry in &report.skipped_entries {
+                info!(
+                    "Skipped {}: {}",
+                    skipped_entry.entry_id, skipped_entry.reason
+                );
+            }
+            info!(
+                "Imported {} comments, {} were imported before, skipped {} entries and {} spam",
+                report.imported_count,
+                report.existing_count,
+                report.skipped_count(),
+                report.spam_count()
+            );
+        }
+        Command::Export {
+            format,
+            output,
+            resource_id,
+        } => {
+            let config = Config::from_env();
+            let comment_store = init_comment_store(&config).await;
+            write_thread_export(comment_store, resource_id, format, output.as_deref())
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use futures::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Arc};
+use tokio::{
+    fs::File,
+    io::{self, AsyncWrite, AsyncWriteExt},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{Comment, CommentReaction, Commenter},
+    persistent::CommentStore,
+};
+
+/// Version of the export format, bumped whenever a change to it would trip up older importers.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// How many comments an export reads at a time.
+pub const EXPORT_PAGE_SIZE: u32 = 500;
+
+/// How an export is laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// A single JSON document: the header with the comments in `comments`.
+    #[default]
+    Json,
+    /// One JSON document per line: the header, then one line per comment.
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// Opens an export, telling which resource the comments belong to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportHeader {
+    pub version: u32,
+    pub resource_id: Uuid,
+}
+
+/// A comment of an export, together with its reactions. Replies point at the comment they reply
+/// to, which is exported before them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedComment {
+    pub comment_id: Uuid,
+    /// `None` for a root comment.
+    pub parent_comment_id: Option<Uuid>,
+    /// The path of the comment where it was exported from, for reference. Importers place the
+    /// comment by `parent_comment_id`.
+    pub materialized_path: String,
+    pub commenter: Commenter,
+    pub commented_timestamp: DateTime<Utc>,
+    pub comment_text: String,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub deleted_timestamp: Option<DateTime<Utc>>,
+    /// Oldest first.
+    #[serde(default)]
+    pub reactions: Vec<CommentReaction>,
+}
+
+/// A whole export in the `json` format.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportDocument {
+    #[serde(flatten)]
+    pub header: ExportHeader,
+    pub comments: Vec<ExportedComment>,
+}
+
+/// Streams the thread of the resource, or of the resource it is an alias of, in the given format,
+/// shallowest comments first and then oldest first. The thread is read [`EXPORT_PAGE_SIZE`]
+/// comments at a time, together with their reactions, as the stream is polled.
+pub async fn export_thread<S: CommentStore + ?Sized>(
+    comment_store: Arc<S>,
+    resource_id: Uuid,
+    format: ExportFormat,
+) -> Result<impl Stream<Item = Result<String>>> {
+    let resource_id = comment_store.resolve_resource_id(resource_id).await?;
+
+    let header = serde_json::to_string(&ExportHeader {
+        version: EXPORT_FORMAT_VERSION,
+        resource_id,
+    })?;
+    let (opening, closing) = match format {
+        // the header object is left open for the comments to go in
+        ExportFormat::Json => (
+            format!(
+                "{},\"comments\":[",
+                header.strip_suffix('}').unwrap_or(&header)
+            ),
+            Some("]}\n".to_string()),
+        ),
+        ExportFormat::Ndjson => (format!("{}\n", header), None),
+    };
+
+    // every page starts after the last comment of the previous one, until a page comes short
+    let exported_pages = stream::try_unfold(
+        (None, false),
+        move |(after, exhausted): (Option<Comment>, bool)| {
+            let comment_store = comment_store.clone();
+            async move {
+                if exhausted {
+                    return Ok(None);
+                }
+
+                let is_first_page = after.is_none();
+                let comments = comment_store
+                    .find_all_comments_page(resource_id.to_string(), EXPORT_PAGE_SIZE, after)
+                    .await?;
+                if comments.is_empty() {
+                    return Ok(None);
+                }
+
+                let mut reactions = comment_store
+                    .find_reactions_of_comments(
+                        comments.iter().map(|comment| comment.comment_id).collect(),
+                    )
+                    .await?;
+
+                let exhausted = comments.len() < EXPORT_PAGE_SIZE as usize;
+                let last_comment = comments.last().cloned();
+
+                let mut exported_page = String::new();
+                for (index, comment) in comments.into_iter().enumerate() {
+                    let exported_comment = serde_json::to_string(&ExportedComment {
+                        reactions: reactions.remove(&comment.comment_id).unwrap_or_default(),
+                        comment_id: comment.comment_id,
+                        parent_comment_id: comment.parent_comment_id,
+                        materialized_path: comment.materialized_path,
+                        commenter: comment.commenter,
+                        commented_timestamp: comment.commented_timestamp,
+                        comment_text: comment.comment_text,
+                        deleted: comment.deleted,
+                        deleted_timestamp: comment.deleted_timestamp,
+                    })?;
+
+                    match format {
+                        ExportFormat::Json if is_first_page && index == 0 => {
+                            exported_page.push_str(&exported_comment)
+                        }
+                        ExportFormat::Json => {
+                            exported_page.push(',');
+                            exported_page.push_str(&exported_comment);
+                        }
+                        ExportFormat::Ndjson => {
+                            exported_page.push_str(&exported_comment);
+                            exported_page.push('\n');
+                        }
+                    }
+                }
+
+                Ok(Some((exported_page, (last_comment, exhausted))))
+            }
+        },
+    );
+
+    Ok(stream::once(async move { Ok(opening) })
+        .chain(exported_pages)
+        .chain(stream::iter(closing.map(Ok))))
+}
+
+/// Writes the export of the thread of the resource to the given file, or to the standard output.
+pub async fn write_thread_export(
+    comment_store: Arc<dyn CommentStore>,
+    resource_id: Uuid,
+    format: ExportFormat,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let mut output: Box<dyn AsyncWrite + Unpin> = match output_path {
+        Some(output_path) => Box::new(File::create(output_path).await?),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut exported_thread = Box::pin(export_thread(comment_store, resource_id, format).await?);
+    while let Some(chunk) = exported_thread.next().await {
+        output.write_all(chunk?.as_bytes()).await?;
+    }
+    output.flush().await?;
+
+    Ok(())
+}
@@ -0,0 +1,1091 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use roxmltree::{Document, Node};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
+use uuid::Uuid;
+
+use crate::{
+    common::utils::{append_uuid_to_materialized_path, uuid_list_to_materialized_path},
+    models::{
+        Comment, CommentReaction, CommentType, Commenter, CURRENT_COMMENT_SCHEMA_VERSION,
+        DELETED_COMMENT_TEXT,
+    },
+    persistent::CommentStore,
+    service::export::{ExportDocument, ExportHeader, ExportedComment, EXPORT_FORMAT_VERSION},
+};
+
+/// Comment systems whose exports can be imported.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ImportFormat {
+    /// The XML export of a Disqus forum.
+    Disqus,
+    /// The WXR export of a WordPress site.
+    Wordpress,
+    /// An export of commenter itself, in either format.
+    Commenter,
+}
+
+/// Why an entry of an export was left out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    Spam,
+    /// Its thread isn't mapped to a resource.
+    UnmappedThread,
+    /// It is awaiting moderation.
+    Unapproved,
+    /// It is a pingback or a trackback.
+    NotAComment,
+    /// The comment it replies to was left out or isn't in the export.
+    ParentLeftOut,
+    /// It was deleted and none of its replies are kept, so there is no need for a tombstone.
+    DeletedWithoutReplies,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Spam => "spam",
+            SkipReason::UnmappedThread => "thread not mapped to a resource",
+            SkipReason::Unapproved => "awaiting moderation",
+            SkipReason::NotAComment => "pingback or trackback",
+            SkipReason::ParentLeftOut => "replies to a comment that was left out",
+            SkipReason::DeletedWithoutReplies => "deleted without replies",
+        })
+    }
+}
+
+/// An entry of an export that was left out, by its id in the export.
+#[derive(Clone, Debug)]
+pub struct SkippedEntry {
+    pub entry_id: String,
+    pub reason: SkipReason,
+}
+
+/// What an import did with the entries of the export.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Comments added to their threads.
+    pub imported_count: usize,
+    /// Comments an earlier import of the export added already, which are left as they are but
+    /// for the reactions they are missing.
+    pub existing_count: usize,
+    /// Entries left out, in the order they were left out.
+    pub skipped_entries: Vec<SkippedEntry>,
+}
+
+impl ImportReport {
+    fn skip(&mut self, entry_id: impl Into<String>, reason: SkipReason) {
+        self.skipped_entries.push(SkippedEntry {
+            entry_id: entry_id.into(),
+            reason,
+        });
+    }
+
+    /// Number of entries left out because they were spam.
+    pub fn spam_count(&self) -> usize {
+        self.skipped_entries
+            .iter()
+            .filter(|skipped_entry| skipped_entry.reason == SkipReason::Spam)
+            .count()
+    }
+
+    /// Number of entries left out for any other reason than being spam.
+    pub fn skipped_count(&self) -> usize {
+        self.skipped_entries.len() - self.spam_count()
+    }
+}
+
+/// A comment read from an export, before it is placed in its thread.
+#[derive(Debug, Clone)]
+pub struct ImportedComment {
+    /// Id of the entry in the export, as reported when the comment is left out.
+    pub entry_id: String,
+    /// Derived from the id of the entry in the export, so that importing the export again finds
+    /// the comment.
+    pub comment_id: Uuid,
+    /// The resource the thread is mapped to, which only root comments are placed under.
+    pub resource_id: Uuid,
+    pub parent_comment_id: Option<Uuid>,
+    pub commenter: Commenter,
+    pub commented_timestamp: DateTime<Utc>,
+    pub comment_text: String,
+    /// When the comment was deleted, `None` if it wasn't.
+    pub deleted_timestamp: Option<DateTime<Utc>>,
+    /// Oldest first.
+    pub reactions: Vec<CommentReaction>,
+}
+
+/// Imports the export at the given path, whose threads are mapped to resource ids by the JSON
+/// object in the mapping file. Only commenter's own exports can do without a mapping.
+pub async fn import_export(
+    comment_store: &dyn CommentStore,
+    format: ImportFormat,
+    export_path: &Path,
+    mapping_path: Option<&Path>,
+) -> Result<ImportReport> {
+    let export = fs::read_to_string(export_path)
+        .with_context(|| format!("reading {}", export_path.display()))?;
+    let thread_mapping: HashMap<String, Uuid> = match mapping_path {
+        Some(mapping_path) => serde_json::from_str(
+            &fs::read_to_string(mapping_path)
+                .with_context(|| format!("reading {}", mapping_path.display()))?,
+        )
+        .context("parsing the thread mapping")?,
+        None if matches!(format, ImportFormat::Commenter) => HashMap::new(),
+        None => anyhow::bail!("a thread mapping is required for {:?} exports", format),
+    };
+
+    let (comments, report) = match format {
+        ImportFormat::Disqus => parse_disqus_export(&export, &thread_mapping)?,
+        ImportFormat::Wordpress => parse_wordpress_export(&export, &thread_mapping)?,
+        ImportFormat::Commenter => parse_commenter_export(&export, &thread_mapping)?,
+    };
+
+    import_comments(comment_store, comments, report).await
+}
+
+/// Adds the comments to their threads, parents before their replies and each level in the
+/// order the comments were made. Comments imported before only get the reactions they are
+/// missing, and deleted comments are kept as tombstones only if some of their replies are
+/// kept. Resource settings don't apply, imported comments are taken whatever they are.
+pub async fn import_comments(
+    comment_store: &dyn CommentStore,
+    comments: Vec<ImportedComment>,
+    mut report: ImportReport,
+) -> Result<ImportReport> {
+    let depths = thread_depths(&comments);
+
+    let mut comments: Vec<(u32, ImportedComment)> = comments
+        .into_iter()
+        .filter_map(|comment| match depths.get(&comment.comment_id) {
+            Some(Some(depth)) => Some((*depth, comment)),
+            _ => {
+                report.skip(comment.entry_id, SkipReason::ParentLeftOut);
+                None
+            }
+        })
+        .collect();
+
+    // deepest first, so that whether a deleted comment has replies to keep is known when it is
+    // reached
+    comments.sort_by(|(depth, comment), (other_depth, other_comment)| {
+        (
+            other_depth,
+            other_comment.commented_timestamp,
+            other_comment.comment_id,
+        )
+            .cmp(&(depth, comment.commented_timestamp, comment.comment_id))
+    });
+
+    let mut kept_parent_comment_ids = HashSet::new();
+    comments.retain(|(_, comment)| {
+        let is_kept = comment.deleted_timestamp.is_none()
+            || kept_parent_comment_ids.contains(&comment.comment_id);
+        if !is_kept {
+            report.skip(&comment.entry_id, SkipReason::DeletedWithoutReplies);
+        } else if let Some(parent_comment_id) = comment.parent_comment_id {
+            kept_parent_comment_ids.insert(parent_comment_id);
+        }
+        is_kept
+    });
+
+    for (_, imported_comment) in comments.into_iter().rev() {
+        let comment_id = imported_comment.comment_id;
+
+        if comment_store.find_comment(comment_id).await.is_ok() {
+            // an earlier import may have stopped between adding the comment and its reactions,
+            // and appending a reaction it already has does nothing
+            for reaction in imported_comment.reactions {
+                comment_store
+                    .append_reaction_to_comment(comment_id, reaction)
+                    .await?;
+            }
+            report.existing_count += 1;
+            continue;
+        }
+
+        let (comment_type, materialized_path, resource_id, depth, ancestor_comment_ids) =
+            match imported_comment.parent_comment_id {
+                // the parent was imported before its replies, or by an earlier import
+                Some(parent_comment_id) => {
+                    let parent_comment = comment_store.find_comment(parent_comment_id).await?;
+                    (
+                        CommentType::Branch,
+                        append_uuid_to_materialized_path(
+                            &parent_comment.materialized_path,
+                            &comment_id,
+                        ),
+                        parent_comment.resource_id,
+                        parent_comment.depth + 1,
+                        [
+                            parent_comment.ancestor_comment_ids.as_slice(),
+                            &[parent_comment.comment_id],
+                        ]
+                        .concat(),
+                    )
+                }
+                None => {
+                    let resource_id = comment_store
+                        .resolve_resource_id(imported_comment.resource_id)
+                        .await?;
+                    (
+                        CommentType::Root,
+                        uuid_list_to_materialized_path(&[resource_id, comment_id]),
+                        resource_id,
+                        1,
+                        vec![],
+                    )
+                }
+            };
+
+        // what a tombstone replaced isn't imported, so there is nothing to restore
+        let (commenter, comment_text) = match imported_comment.deleted_timestamp {
+            Some(_) => (Commenter::deleted(), DELETED_COMMENT_TEXT.to_string()),
+            None => (imported_comment.commenter, imported_comment.comment_text),
+        };
+
+        comment_store
+            .insert_comment(Comment {
+                comment_id,
+                comment_type,
+                commenter,
+                commented_timestamp: imported_comment.commented_timestamp,
+                comment_text,
+                branch_comment_ids: vec![],
+                materialized_path,
+                resource_id,
+                parent_comment_id: imported_comment.parent_comment_id,
+                depth,
+                ancestor_comment_ids,
+                direct_reply_count: 0,
+                total_descendant_count: 0,
+                reaction_counts: BTreeMap::new(),
+                total_reaction_count: 0,
+                controversy_score: 0.0,
+                deleted: imported_comment.deleted_timestamp.is_some(),
+                deleted_timestamp: imported_comment.deleted_timestamp,
+                schema_version: CURRENT_COMMENT_SCHEMA_VERSION,
+            })
+            .await?;
+
+        for reaction in imported_comment.reactions {
+            comment_store
+                .append_reaction_to_comment(comment_id, reaction)
+                .await?;
+        }
+        report.imported_count += 1;
+    }
+
+    Ok(report)
+}
+
+/// The depth of every comment in its thread, `None` for the comments whose chain of parents
+/// leads to a comment that isn't among them, or loops.
+fn thread_depths(comments: &[ImportedComment]) -> HashMap<Uuid, Option<u32>> {
+    let parent_comment_ids: HashMap<Uuid, Option<Uuid>> = comments
+        .iter()
+        .map(|comment| (comment.comment_id, comment.parent_comment_id))
+        .collect();
+
+    let mut depths: HashMap<Uuid, Option<u32>> = HashMap::new();
+    for comment in comments {
+        // walk up until a comment whose depth is known, then fill in the chain walked
+        let mut chain = vec![];
+        let mut next_comment_id = Some(comment.comment_id);
+        let mut depth = Some(0);
+        while let Some(comment_id) = next_comment_id {
+            if let Some(known_depth) = depths.get(&comment_id) {
+                depth = *known_depth;
+                break;
+            }
+            match parent_comment_ids.get(&comment_id) {
+                Some(parent_comment_id) if chain.len() <= comments.len() => {
+                    chain.push(comment_id);
+                    next_comment_id = *parent_comment_id;
+                }
+                _ => {
+                    depth = None;
+                    break;
+                }
+            }
+        }
+
+        for comment_id in chain.into_iter().rev() {
+            depth = depth.map(|depth| depth + 1);
+            depths.insert(comment_id, depth);
+        }
+    }
+
+    depths
+}
+
+const DISQUS_URL: &str = "https://disqus.com/";
+const DISQUS_INTERNALS_NAMESPACE: &str = "http://disqus.com/disqus-internals";
+
+/// Reads an export of commenter itself, in either format. The resource of the export is mapped
+/// by its id, and the comments go to that resource again if it isn't in the mapping. Comments
+/// keep their ids.
+pub fn parse_commenter_export(
+    export: &str,
+    thread_mapping: &HashMap<String, Uuid>,
+) -> Result<(Vec<ImportedComment>, ImportReport)> {
+    let export_document = match serde_json::from_str::<ExportDocument>(export) {
+        Ok(export_document) => export_document,
+        // one header line, then one line per comment
+        Err(_) => {
+            let mut lines = export.lines().filter(|line| !line.trim().is_empty());
+            let header: ExportHeader =
+                serde_json::from_str(lines.next().context("commenter export without a header")?)
+                    .context("parsing the header of the commenter export")?;
+            let comments = lines
+                .map(serde_json::from_str)
+                .collect::<serde_json::Result<Vec<ExportedComment>>>()
+                .context("parsing the comments of the commenter export")?;
+            ExportDocument { header, comments }
+        }
+    };
+
+    if export_document.header.version > EXPORT_FORMAT_VERSION {
+        anyhow::bail!(
+            "commenter export version {} is newer than this version of commenter",
+            export_document.header.version
+        );
+    }
+
+    let resource_id = thread_mapping
+        .get(&export_document.header.resource_id.to_string())
+        .copied()
+        .unwrap_or(export_document.header.resource_id);
+
+    let comments = export_document
+        .comments
+        .into_iter()
+        .map(|exported_comment| ImportedComment {
+            entry_id: format!("comment {}", exported_comment.comment_id),
+            comment_id: exported_comment.comment_id,
+            resource_id,
+            parent_comment_id: exported_comment.parent_comment_id,
+            commenter: exported_comment.commenter,
+            commented_timestamp: exported_comment.commented_timestamp,
+            comment_text: exported_comment.comment_text,
+            deleted_timestamp: exported_comment
+                .deleted_timestamp
+                .filter(|_| exported_comment.deleted),
+            reactions: exported_comment.reactions,
+        })
+        .collect();
+
+    Ok((comments, ImportReport::default()))
+}
+
+/// Reads the posts of a Disqus export. A thread is mapped by its identifier, its link or its
+/// Disqus id, whichever is found in the mapping first.
+pub fn parse_disqus_export(
+    export: &str,
+    thread_mapping: &HashMap<String, Uuid>,
+) -> Result<(Vec<ImportedComment>, ImportReport)> {
+    let document = Document::parse(export).context("parsing the Disqus export")?;
+    let namespace = Uuid::new_v5(&Uuid::NAMESPACE_URL, DISQUS_URL.as_bytes());
+
+    let thread_resource_ids: HashMap<&str, Uuid> = document
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("thread"))
+        .filter_map(|thread| {
+            let thread_id = disqus_id(thread)?;
+            [
+                child_text(thread, "id"),
+                child_text(thread, "link"),
+                Some(thread_id),
+            ]
+            .into_iter()
+            .flatten()
+            .find_map(|key| thread_mapping.get(key))
+            .map(|resource_id| (thread_id, *resource_id))
+        })
+        .collect();
+
+    let mut comments = vec![];
+    let mut report = ImportReport::default();
+    for post in document
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("post"))
+    {
+        let post_id = disqus_id(post).context("post without a Disqus id")?;
+
+        let entry_id = format!("post {}", post_id);
+
+        if child_text(post, "isSpam") == Some("true") {
+            report.skip(entry_id, SkipReason::Spam);
+            continue;
+        }
+
+        let Some(resource_id) = child(post, "thread")
+            .and_then(disqus_id)
+            .and_then(|thread_id| thread_resource_ids.get(thread_id))
+        else {
+            report.skip(entry_id, SkipReason::UnmappedThread);
+            continue;
+        };
+
+        let author = child(post, "author");
+        let author_text = |name| author.and_then(|author| child_text(author, name));
+        let name = author_text("name").filter(|name| !name.is_empty());
+        let username = author_text("username").filter(|username| !username.is_empty());
+        // anonymous authors have no username, and are told apart by their email or their name
+        let account_key = username
+            .or(author_text("email").filter(|email| !email.is_empty()))
+            .or(name)
+            .unwrap_or_default();
+
+        let created_at = child_text(post, "createdAt")
+            .with_context(|| format!("post {} without a creation time", post_id))?;
+        let commented_timestamp = DateTime::parse_from_rfc3339(created_at)
+            .with_context(|| format!("post {} with an invalid creation time", post_id))?
+            .with_timezone(&Utc);
+
+        comments.push(ImportedComment {
+            entry_id,
+            comment_id: Uuid::new_v5(&namespace, format!("post:{}", post_id).as_bytes()),
+            resource_id: *resource_id,
+            parent_comment_id: child(post, "parent").and_then(disqus_id).map(|parent_id| {
+                Uuid::new_v5(&namespace, format!("post:{}", parent_id).as_bytes())
+            }),
+            commenter: Commenter {
+                account_id: Uuid::new_v5(&namespace, format!("author:{}", account_key).as_bytes()),
+                username: name.or(username).unwrap_or_default().to_string(),
+            },
+            commented_timestamp,
+            comment_text: child_text(post, "message")
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            // exports don't tell when a post was deleted
+            deleted_timestamp: (child_text(post, "isDeleted") == Some("true"))
+                .then_some(commented_timestamp),
+            reactions: vec![],
+        });
+    }
+
+    Ok((comments, report))
+}
+
+/// Reads the comments of the posts and pages of a WordPress export. A post is mapped by its id,
+/// its slug or its link, whichever is found in the mapping first. Comments in the trash are
+/// treated as deleted.
+pub fn parse_wordpress_export(
+    export: &str,
+    thread_mapping: &HashMap<String, Uuid>,
+) -> Result<(Vec<ImportedComment>, ImportReport)> {
+    let document = Document::parse(export).context("parsing the WordPress export")?;
+    let channel =
+        child(document.root_element(), "channel").context("WordPress export without a channel")?;
+    // comment ids are only unique within a site
+    let site_url = child_text(channel, "base_site_url")
+        .or(child_text(channel, "link"))
+        .unwrap_or_default();
+    let namespace = Uuid::new_v5(&Uuid::NAMESPACE_URL, site_url.as_bytes());
+    let wordpress_comment_id =
+        |comment_id| Uuid::new_v5(&namespace, format!("comment:{}", comment_id).as_bytes());
+
+    let mut comments = vec![];
+    let mut report = ImportReport::default();
+    for item in channel.children().filter(|node| node.has_tag_name("item")) {
+        let resource_id = [
+            child_text(item, "post_id"),
+            child_text(item, "post_name"),
+            child_text(item, "link"),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|key| !key.is_empty())
+        .find_map(|key| thread_mapping.get(key));
+
+        for wordpress_comment in item.children().filter(|node| node.has_tag_name("comment")) {
+            let comment_id =
+                child_text(wordpress_comment, "comment_id").context("comment without an id")?;
+            let entry_id = format!("comment {}", comment_id);
+
+            let approved = child_text(wordpress_comment, "comment_approved").unwrap_or_default();
+            let comment_type = child_text(wordpress_comment, "comment_type").unwrap_or_default();
+            let skip_reason = match (approved, comment_type) {
+                ("spam", _) => Some(SkipReason::Spam),
+                (_, "pingback" | "trackback") => Some(SkipReason::NotAComment),
+                ("1" | "trash", _) => None,
+                _ => Some(SkipReason::Unapproved),
+            };
+            if let Some(skip_reason) = skip_reason {
+                report.skip(entry_id, skip_reason);
+                continue;
+            }
+
+            let Some(resource_id) = resource_id else {
+                report.skip(entry_id, SkipReason::UnmappedThread);
+                continue;
+            };
+
+            let author = child_text(wordpress_comment, "comment_author").unwrap_or_default();
+            // guests have no user id, and are told apart by their email or their name
+            let account_key = match child_text(wordpress_comment, "comment_user_id") {
+                Some(user_id) if !user_id.is_empty() && user_id != "0" => {
+                    format!("user:{}", user_id)
+                }
+                _ => format!(
+                    "author:{}",
+                    child_text(wordpress_comment, "comment_author_email")
+                        .filter(|email| !email.is_empty())
+                        .unwrap_or(author)
+                ),
+            };
+
+            // the GMT date is zero for comments that were never published, the local date is
+            // the best there is then
+            let commented_timestamp = ["comment_date_gmt", "comment_date"]
+                .into_iter()
+                .filter_map(|name| child_text(wordpress_comment, name))
+                .find_map(|date| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok())
+                .with_context(|| format!("comment {} without a valid date", comment_id))?
+                .and_utc();
+
+            comments.push(ImportedComment {
+                entry_id,
+                comment_id: wordpress_comment_id(comment_id),
+                resource_id: *resource_id,
+                parent_comment_id: child_text(wordpress_comment, "comment_parent")
+                    .filter(|parent_id| !parent_id.is_empty() && *parent_id != "0")
+                    .map(wordpress_comment_id),
+                commenter: Commenter {
+                    account_id: Uuid::new_v5(&namespace, account_key.as_bytes()),
+                    username: author.to_string(),
+                },
+                commented_timestamp,
+                comment_text: child_text(wordpress_comment, "comment_content")
+                    .unwrap_or_default()
+                    .to_string(),
+                // exports don't tell when a comment was put in the trash
+                deleted_timestamp: (approved == "trash").then_some(commented_timestamp),
+                reactions: vec![],
+            });
+        }
+    }
+
+    Ok((comments, report))
+}
+
+fn disqus_id<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.attribute((DISQUS_INTERNALS_NAMESPACE, "id"))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).map(|child| child.text().unwrap_or_default().trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        models::{CommentReactor, CommentSort},
+        persistent::InMemoryCommentStore,
+        service::export::{export_thread, ExportFormat, EXPORT_PAGE_SIZE},
+    };
+
+    const DISQUS_EXPORT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<disqus xmlns="http://disqus.com" xmlns:dsq="http://disqus.com/disqus-internals">
+  <thread dsq:id="100">
+    <id>article-1</id>
+    <link>https://example.com/article-1</link>
+  </thread>
+  <thread dsq:id="200">
+    <id>article-2</id>
+    <link>https://example.com/article-2</link>
+  </thread>
+  <post dsq:id="1">
+    <message><![CDATA[<p>First</p>]]></message>
+    <createdAt>2020-01-01T10:00:00Z</createdAt>
+    <isDeleted>false</isDeleted>
+    <isSpam>false</isSpam>
+    <author><name>Ann</name><username>ann</username></author>
+    <thread dsq:id="100"/>
+  </post>
+  <post dsq:id="2">
+    <message><![CDATA[<p>Gone</p>]]></message>
+    <createdAt>2020-01-01T11:00:00Z</createdAt>
+    <isDeleted>true</isDeleted>
+    <isSpam>false</isSpam>
+    <author><name>Bob</name><email>bob@example.com</email></author>
+    <thread dsq:id="100"/>
+    <parent dsq:id="1"/>
+  </post>
+  <post dsq:id="3">
+    <message><![CDATA[<p>Reply to the gone one</p>]]></message>
+    <createdAt>2020-01-01T12:00:00Z</createdAt>
+    <isDeleted>false</isDeleted>
+    <isSpam>false</isSpam>
+    <author><name>Ann</name><username>ann</username></author>
+    <thread dsq:id="100"/>
+    <parent dsq:id="2"/>
+  </post>
+  <post dsq:id="4">
+    <message><![CDATA[<p>Buy now</p>]]></message>
+    <createdAt>2020-01-01T13:00:00Z</createdAt>
+    <isDeleted>false</isDeleted>
+    <isSpam>true</isSpam>
+    <author><name>Spammer</name></author>
+    <thread dsq:id="100"/>
+  </post>
+  <post dsq:id="5">
+    <message><![CDATA[<p>Elsewhere</p>]]></message>
+    <createdAt>2020-01-01T14:00:00Z</createdAt>
+    <isDeleted>false</isDeleted>
+    <isSpam>false</isSpam>
+    <author><name>Ann</name><username>ann</username></author>
+    <thread dsq:id="200"/>
+  </post>
+  <post dsq:id="6">
+    <message><![CDATA[<p>Regretted</p>]]></message>
+    <createdAt>2020-01-01T15:00:00Z</createdAt>
+    <isDeleted>true</isDeleted>
+    <isSpam>false</isSpam>
+    <author><name>Bob</name><email>bob@example.com</email></author>
+    <thread dsq:id="100"/>
+  </post>
+</disqus>
+"#;
+
+    const WORDPRESS_EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:wp="http://wordpress.org/export/1.2/"
+  xmlns:content="http://purl.org/rss/1.0/modules/content/">
+  <channel>
+    <link>https://blog.example.com</link>
+    <wp:base_site_url>https://blog.example.com</wp:base_site_url>
+    <item>
+      <link>https://blog.example.com/hello-world/</link>
+      <wp:post_id>1</wp:post_id>
+      <wp:post_name>hello-world</wp:post_name>
+      <wp:comment>
+        <wp:comment_id>10</wp:comment_id>
+        <wp:comment_author>Ann</wp:comment_author>
+        <wp:comment_date>2020-01-01 11:00:00</wp:comment_date>
+        <wp:comment_date_gmt>2020-01-01 10:00:00</wp:comment_date_gmt>
+        <wp:comment_content><![CDATA[First]]></wp:comment_content>
+        <wp:comment_approved>1</wp:comment_approved>
+        <wp:comment_type>comment</wp:comment_type>
+        <wp:comment_parent>0</wp:comment_parent>
+        <wp:comment_user_id>3</wp:comment_user_id>
+      </wp:comment>
+      <wp:comment>
+        <wp:comment_id>11</wp:comment_id>
+        <wp:comment_author>Guest</wp:comment_author>
+        <wp:comment_author_email>guest@example.com</wp:comment_author_email>
+        <wp:comment_date>2020-01-01 12:00:00</wp:comment_date>
+        <wp:comment_date_gmt>0000-00-00 00:00:00</wp:comment_date_gmt>
+        <wp:comment_content><![CDATA[Reply]]></wp:comment_content>
+        <wp:comment_approved>1</wp:comment_approved>
+        <wp:comment_type></wp:comment_type>
+        <wp:comment_parent>10</wp:comment_parent>
+        <wp:comment_user_id>0</wp:comment_user_id>
+      </wp:comment>
+      <wp:comment>
+        <wp:comment_id>12</wp:comment_id>
+        <wp:comment_author>Spammer</wp:comment_author>
+        <wp:comment_date_gmt>2020-01-01 13:00:00</wp:comment_date_gmt>
+        <wp:comment_approved>spam</wp:comment_approved>
+      </wp:comment>
+      <wp:comment>
+        <wp:comment_id>13</wp:comment_id>
+        <wp:comment_author>Other blog</wp:comment_author>
+        <wp:comment_date_gmt>2020-01-01 13:00:00</wp:comment_date_gmt>
+        <wp:comment_approved>1</wp:comment_approved>
+        <wp:comment_type>pingback</wp:comment_type>
+      </wp:comment>
+      <wp:comment>
+        <wp:comment_id>14</wp:comment_id>
+        <wp:comment_author>Waiting</wp:comment_author>
+        <wp:comment_date_gmt>2020-01-01 13:00:00</wp:comment_date_gmt>
+        <wp:comment_approved>0</wp:comment_approved>
+      </wp:comment>
+      <wp:comment>
+        <wp:comment_id>15</wp:comment_id>
+        <wp:comment_author>Ann</wp:comment_author>
+        <wp:comment_date_gmt>2020-01-01 14:00:00</wp:comment_date_gmt>
+        <wp:comment_content><![CDATA[Trashed]]></wp:comment_content>
+        <wp:comment_approved>trash</wp:comment_approved>
+        <wp:comment_parent>10</wp:comment_parent>
+        <wp:comment_user_id>3</wp:comment_user_id>
+      </wp:comment>
+      <wp:comment>
+        <wp:comment_id>16</wp:comment_id>
+        <wp:comment_author>Ann</wp:comment_author>
+        <wp:comment_date_gmt>2020-01-01 15:00:00</wp:comment_date_gmt>
+        <wp:comment_content><![CDATA[Reply to the waiting one]]></wp:comment_content>
+        <wp:comment_approved>1</wp:comment_approved>
+        <wp:comment_parent>14</wp:comment_parent>
+        <wp:comment_user_id>3</wp:comment_user_id>
+      </wp:comment>
+    </item>
+    <item>
+      <link>https://blog.example.com/about/</link>
+      <wp:post_id>2</wp:post_id>
+      <wp:post_name>about</wp:post_name>
+      <wp:comment>
+        <wp:comment_id>20</wp:comment_id>
+        <wp:comment_author>Ann</wp:comment_author>
+        <wp:comment_date_gmt>2020-01-02 10:00:00</wp:comment_date_gmt>
+        <wp:comment_approved>1</wp:comment_approved>
+      </wp:comment>
+    </item>
+  </channel>
+</rss>
+"#;
+
+    fn imported_comment(comment_id: Uuid, parent_comment_id: Option<Uuid>) -> ImportedComment {
+        ImportedComment {
+            entry_id: comment_id.to_string(),
+            comment_id,
+            resource_id: Uuid::nil(),
+            parent_comment_id,
+            commenter: Commenter {
+                account_id: Uuid::nil(),
+                username: "commenter".to_string(),
+            },
+            commented_timestamp: Utc::now(),
+            comment_text: "text".to_string(),
+            deleted_timestamp: None,
+            reactions: vec![],
+        }
+    }
+
+    fn skip_reasons(report: &ImportReport) -> Vec<(&str, SkipReason)> {
+        report
+            .skipped_entries
+            .iter()
+            .map(|skipped_entry| (skipped_entry.entry_id.as_str(), skipped_entry.reason))
+            .collect()
+    }
+
+    #[test]
+    fn thread_depths_leave_out_broken_chains() {
+        let ids: Vec<Uuid> = (0..6).map(|_| Uuid::new_v4()).collect();
+        // replies come before their parents, one chain ends in a missing comment and one loops
+        let comments = [
+            imported_comment(ids[2], Some(ids[1])),
+            imported_comment(ids[1], Some(ids[0])),
+            imported_comment(ids[0], None),
+            imported_comment(ids[3], Some(Uuid::new_v4())),
+            imported_comment(ids[4], Some(ids[5])),
+            imported_comment(ids[5], Some(ids[4])),
+        ];
+
+        let depths = thread_depths(&comments);
+
+        assert_eq!(depths[&ids[0]], Some(1));
+        assert_eq!(depths[&ids[1]], Some(2));
+        assert_eq!(depths[&ids[2]], Some(3));
+        assert_eq!(depths[&ids[3]], None);
+        assert_eq!(depths[&ids[4]], None);
+        assert_eq!(depths[&ids[5]], None);
+    }
+
+    #[tokio::test]
+    async fn disqus_posts_are_imported_once() {
+        let resource_id = Uuid::new_v4();
+        let thread_mapping =
+            HashMap::from([("https://example.com/article-1".to_string(), resource_id)]);
+
+        let (comments, report) = parse_disqus_export(DISQUS_EXPORT, &thread_mapping).unwrap();
+        assert_eq!(
+            comments
+                .iter()
+                .map(|comment| comment.entry_id.as_str())
+                .collect::<Vec<_>>(),
+            ["post 1", "post 2", "post 3", "post 6"]
+        );
+        assert_eq!(
+            skip_reasons(&report),
+            [
+                ("post 4", SkipReason::Spam),
+                ("post 5", SkipReason::UnmappedThread)
+            ]
+        );
+        assert_eq!(comments[0].commenter.username, "Ann");
+        assert_eq!(comments[0].comment_text, "<p>First</p>");
+        assert_eq!(comments[1].parent_comment_id, Some(comments[0].comment_id));
+        assert_eq!(
+            comments[2].commenter.account_id,
+            comments[0].commenter.account_id
+        );
+        assert!(comments[1].deleted_timestamp.is_some());
+
+        let comment_store = InMemoryCommentStore::new();
+        let report = import_comments(&comment_store, comments.clone(), report)
+            .await
+            .unwrap();
+        assert_eq!(report.imported_count, 3);
+        assert_eq!(report.spam_count(), 1);
+        assert_eq!(
+            skip_reasons(&report)[2..],
+            [("post 6", SkipReason::DeletedWithoutReplies)]
+        );
+
+        // the deleted post stays as a tombstone for the reply it has
+        let tombstone = comment_store
+            .find_comment(comments[1].comment_id)
+            .await
+            .unwrap();
+        assert!(tombstone.deleted);
+        assert_eq!(tombstone.comment_text, DELETED_COMMENT_TEXT);
+        let reply = comment_store
+            .find_comment(comments[2].comment_id)
+            .await
+            .unwrap();
+        assert_eq!(reply.resource_id, resource_id);
+        assert_eq!(reply.depth, 3);
+
+        let report = import_comments(&comment_store, comments, ImportReport::default())
+            .await
+            .unwrap();
+        assert_eq!(report.imported_count, 0);
+        assert_eq!(report.existing_count, 3);
+    }
+
+    #[tokio::test]
+    async fn imports_run_again_add_the_reactions_comments_are_missing() {
+        let resource_id = Uuid::new_v4();
+        let comment_id = Uuid::new_v4();
+        let reactions: Vec<CommentReaction> = ["1f44d", "2764-fe0f"]
+            .into_iter()
+            .map(|emoji_unified_code| CommentReaction {
+                reactor: CommentReactor {
+                    account_id: Uuid::new_v4(),
+                    username: "reactor".to_string(),
+                },
+                emoji_unified_code: emoji_unified_code.to_string(),
+            })
+            .collect();
+
+        // an earlier import stopped after adding the comment and its first reaction
+        let comment_store = InMemoryCommentStore::new();
+        let comment = ImportedComment {
+            resource_id,
+            reactions: reactions[..1].to_vec(),
+            ..imported_comment(comment_id, None)
+        };
+        import_comments(&comment_store, vec![comment], ImportReport::default())
+            .await
+            .unwrap();
+
+        let comment = ImportedComment {
+            resource_id,
+            reactions: reactions.clone(),
+            ..imported_comment(comment_id, None)
+        };
+        let report = import_comments(&comment_store, vec![comment], ImportReport::default())
+            .await
+            .unwrap();
+        assert_eq!(report.imported_count, 0);
+        assert_eq!(report.existing_count, 1);
+
+        let thread = thread_of(&comment_store, resource_id).await;
+        let (comment, comment_reactions) = &thread[0];
+        assert_eq!(comment_reactions.len(), 2);
+        assert_eq!(comment.reaction_counts["1f44d"], 1);
+        assert_eq!(comment.reaction_counts["2764-fe0f"], 1);
+    }
+
+    #[tokio::test]
+    async fn wordpress_comments_are_imported_with_the_entries_left_out() {
+        let resource_id = Uuid::new_v4();
+        let thread_mapping = HashMap::from([("hello-world".to_string(), resource_id)]);
+
+        let (comments, report) = parse_wordpress_export(WORDPRESS_EXPORT, &thread_mapping).unwrap();
+        assert_eq!(
+            comments
+                .iter()
+                .map(|comment| comment.entry_id.as_str())
+                .collect::<Vec<_>>(),
+            ["comment 10", "comment 11", "comment 15", "comment 16"]
+        );
+        assert_eq!(comments[1].parent_comment_id, Some(comments[0].comment_id));
+        // the local date stands in for a zero GMT date
+        assert_eq!(
+            comments[1].commented_timestamp,
+            "2020-01-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            comments[2].commenter.account_id,
+            comments[0].commenter.account_id
+        );
+        assert_ne!(
+            comments[1].commenter.account_id,
+            comments[0].commenter.account_id
+        );
+        assert!(comments[2].deleted_timestamp.is_some());
+
+        let comment_store = InMemoryCommentStore::new();
+        let report = import_comments(&comment_store, comments.clone(), report)
+            .await
+            .unwrap();
+        assert_eq!(report.imported_count, 2);
+        assert_eq!(report.spam_count(), 1);
+        assert_eq!(report.skipped_count(), 5);
+        assert_eq!(
+            skip_reasons(&report),
+            [
+                ("comment 12", SkipReason::Spam),
+                ("comment 13", SkipReason::NotAComment),
+                ("comment 14", SkipReason::Unapproved),
+                ("comment 20", SkipReason::UnmappedThread),
+                ("comment 16", SkipReason::ParentLeftOut),
+                ("comment 15", SkipReason::DeletedWithoutReplies),
+            ]
+        );
+
+        let reply = comment_store
+            .find_comment(comments[1].comment_id)
+            .await
+            .unwrap();
+        assert_eq!(reply.resource_id, resource_id);
+        assert_eq!(reply.comment_text, "Reply");
+    }
+
+    // what an import has to carry over of every comment of the thread
+    async fn thread_of(
+        comment_store: &InMemoryCommentStore,
+        resource_id: Uuid,
+    ) -> Vec<(Comment, Vec<CommentReaction>)> {
+        let comments = comment_store
+            .find_all_comments(resource_id.to_string(), CommentSort::Oldest, None)
+            .await
+            .unwrap();
+        let mut reactions = comment_store
+            .find_reactions_of_comments(comments.iter().map(|comment| comment.comment_id).collect())
+            .await
+            .unwrap();
+
+        comments
+            .into_iter()
+            .map(|comment| {
+                let comment_reactions = reactions.remove(&comment.comment_id).unwrap_or_default();
+                (comment, comment_reactions)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn commenter_exports_import_back_as_they_were() {
+        let resource_id = Uuid::new_v4();
+        let thread_mapping =
+            HashMap::from([("https://example.com/article-1".to_string(), resource_id)]);
+
+        // a thread with a tombstone, reactions and more comments than an export page holds
+        let comment_store = Arc::new(InMemoryCommentStore::new());
+        let (mut comments, report) = parse_disqus_export(DISQUS_EXPORT, &thread_mapping).unwrap();
+        comments[0].reactions = vec![CommentReaction {
+            reactor: CommentReactor {
+                account_id: Uuid::new_v4(),
+                username: "reactor".to_string(),
+            },
+            emoji_unified_code: "1f44d".to_string(),
+        }];
+        comments.extend((0..EXPORT_PAGE_SIZE).map(|_| ImportedComment {
+            resource_id,
+            ..imported_comment(Uuid::new_v4(), None)
+        }));
+        import_comments(comment_store.as_ref(), comments, report)
+            .await
+            .unwrap();
+        let thread = thread_of(&comment_store, resource_id).await;
+        assert_eq!(thread.len(), EXPORT_PAGE_SIZE as usize + 3);
+
+        for format in [ExportFormat::Json, ExportFormat::Ndjson] {
+            let export: String = export_thread(comment_store.clone(), resource_id, format)
+                .await
+                .unwrap()
+                .try_collect::<Vec<String>>()
+                .await
+                .unwrap()
+                .concat();
+
+            let (imported_comments, report) =
+                parse_commenter_export(&export, &HashMap::new()).unwrap();
+            assert_eq!(imported_comments.len(), thread.len());
+
+            let imported_comment_store = InMemoryCommentStore::new();
+            let report = import_comments(&imported_comment_store, imported_comments, report)
+                .await
+                .unwrap();
+            assert_eq!(report.imported_count, thread.len());
+            assert!(report.skipped_entries.is_empty());
+
+            let imported_thread = thread_of(&imported_comment_store, resource_id).await;
+            for ((comment, reactions), (imported_comment, imported_reactions)) in
+                thread.iter().zip(&imported_thread)
+            {
+                assert_eq!(imported_comment.comment_id, comment.comment_id);
+                assert_eq!(
+                    imported_comment.materialized_path,
+                    comment.materialized_path
+                );
+                assert_eq!(imported_comment.comment_text, comment.comment_text);
+                assert_eq!(imported_comment.deleted, comment.deleted);
+                assert_eq!(
+                    imported_comment.direct_reply_count,
+                    comment.direct_reply_count
+                );
+                assert_eq!(imported_comment.reaction_counts, comment.reaction_counts);
+                assert_eq!(imported_reactions.len(), reactions.len());
+            }
+        }
+    }
+
+    #[test]
+    fn commenter_exports_map_their_resource_and_refuse_newer_versions() {
+        let resource_id = Uuid::new_v4();
+        let mapped_resource_id = Uuid::new_v4();
+        let comment_id = Uuid::new_v4();
+        let export = format!(
+            "{}\n{}\n",
+            json!({ "version": EXPORT_FORMAT_VERSION, "resource_id": resource_id }),
+            json!({
+                "comment_id": comment_id,
+                "parent_comment_id": null,
+                "materialized_path": format!("{}->{}", resource_id, comment_id),
+                "commenter": { "account_id": Uuid::new_v4(), "username": "commenter" },
+                "commented_timestamp": Utc::now(),
+                "comment_text": "text",
+            })
+        );
+
+        let (comments, _) = parse_commenter_export(
+            &export,
+            &HashMap::from([(resource_id.to_string(), mapped_resource_id)]),
+        )
+        .unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].comment_id, comment_id);
+        assert_eq!(comments[0].resource_id, mapped_resource_id);
+        assert_eq!(comments[0].deleted_timestamp, None);
+
+        let export = json!({
+            "version": EXPORT_FORMAT_VERSION + 1,
+            "resource_id": resource_id,
+            "comments": [],
+        })
+        .to_string();
+        assert!(parse_commenter_export(&export, &HashMap::new()).is_err());
+    }
+}
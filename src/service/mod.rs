@@ -1 +1,5 @@
+pub mod cli;
+pub mod export;
+pub mod import;
+pub mod purge;
 pub mod server;
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use std::{cmp::Reverse, sync::Arc};
+use tracing::{error, info};
+
+use crate::persistent::CommentStore;
+
+/// How long after a comment was replaced with a tombstone it can still be restored.
+#[derive(Clone, Copy, Debug)]
+pub struct RestoreWindow(pub Duration);
+
+/// Purges the tombstones whose restore window has expired: the ones without replies are removed
+/// for good, and the text and author kept for the others are discarded. Returns how many
+/// tombstones were removed.
+pub async fn purge_expired_comments(
+    comment_store: &dyn CommentStore,
+    restore_window: RestoreWindow,
+) -> Result<usize> {
+    let deleted_before = Utc::now() - restore_window.0;
+
+    let mut tombstones = comment_store
+        .find_expired_tombstones(deleted_before)
+        .await?;
+
+    // replies go before the comments they reply to, so that a tombstone whose replies were all
+    // expired tombstones goes in the same run
+    tombstones.sort_by_key(|tombstone| Reverse(tombstone.depth));
+
+    let mut pruned_count = 0;
+    for tombstone in tombstones {
+        // its replies may have been removed since, and another instance may have purged it
+        let Ok(comment) = comment_store.find_comment(tombstone.comment_id).await else {
+            continue;
+        };
+
+        // replies to tombstones are refused, so a tombstone without replies stays so
+        if comment.direct_reply_count == 0 {
+            comment_store
+                .prune_comments(comment.materialized_path)
+                .await?;
+            pruned_count += 1;
+        } else {
+            comment_store
+                .discard_deleted_comment_content(comment.comment_id)
+                .await?;
+        }
+    }
+
+    Ok(pruned_count)
+}
+
+/// Runs [`purge_expired_comments`] in the background every `purge_interval`.
+pub fn spawn_purge_job(
+    comment_store: Arc<dyn CommentStore>,
+    restore_window: RestoreWindow,
+    purge_interval: std::time::Duration,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(purge_interval);
+        loop {
+            interval.tick().await;
+
+            match purge_expired_comments(comment_store.as_ref(), restore_window).await {
+                Ok(0) => {}
+                Ok(purged_count) => info!("Purged {} deleted comments", purged_count),
+                Err(e) => error!("Failed to purge deleted comments: {}", e),
+            }
+        }
+    });
+}
@@ -4,86 +4,258 @@ use axum::{
 };
 use mongodb::options::ClientOptions;
 use serde::{Deserialize, Serialize};
-use std::{env, net::SocketAddr, str::FromStr, sync::Arc};
+use std::{env, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
 use tracing::info;
 
-use crate::persistent::MongoDbConfig;
 use crate::{
-    common::handlers::health_check,
+    common::{auth::AdminApiKey, handlers::health_check},
     handlers::{
-        create_branch_comment, create_root_comment, delete_comment, get_all_comments,
-        get_branch_comments_next, get_branch_comments_rest, get_root_comments, react_to_comment,
-        undo_react_to_comment, update_comment_text,
+        approve_comment, create_branch_comment, create_resource, create_resource_alias,
+        create_root_comment, delete_comment, delete_resource_alias, export_resource_comments,
+        get_all_comments, get_branch_comments_levels, get_branch_comments_next,
+        get_branch_comments_rest, get_pending_comments, get_reactions, get_resource,
+        get_resource_aliases, get_resource_settings, get_root_comments, merge_resources,
+        move_comment, prune_comment, react_to_comment, rebuild_branch_comment_ids, reject_comment,
+        restore_comment, undo_react_to_comment, update_comment_text, update_resource,
+        update_resource_settings,
     },
-    persistent::{init_mongo_connection, PersistentLayer},
+    persistent::{
+        init_mongo_connection, init_postgres_connection, init_sqlite_connection, CommentStore,
+        InMemoryCommentStore, MongoCommentStore, MongoDbConfig, PostgresCommentStore,
+        SqliteCommentStore, StorageBackend,
+    },
+    service::purge::{spawn_purge_job, RestoreWindow},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-struct Config {
+pub struct Config {
     pub server_host: String,
     pub server_port: u16,
-    pub mongodb_connection_string: String,
+    pub storage_backend: StorageBackend,
+    pub run_migrations_on_startup: bool,
+    pub mongodb_connection_string: Option<String>,
     pub mongodb_max_pool_size: Option<u32>,
+    pub postgres_connection_string: Option<String>,
+    pub postgres_max_pool_size: Option<u32>,
+    pub sqlite_database_path: Option<String>,
+    pub admin_api_key: Option<String>,
+    pub restore_window_hours: u32,
+    pub purge_interval_minutes: u32,
 }
 
-pub async fn init_server() {
-    tracing_subscriber::fmt::init();
+impl Config {
+    pub fn from_env() -> Config {
+        Config {
+            server_host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
+            server_port: env::var("SERVER_PORT")
+                .unwrap_or_else(|_| "7000".to_string())
+                .parse()
+                .expect("SERVER_PORT must be a number"),
+            storage_backend: env::var("STORAGE_BACKEND")
+                .unwrap_or_else(|_| "mongo".to_string())
+                .parse()
+                .expect("STORAGE_BACKEND must be one of mongo, postgres, sqlite or memory"),
+            run_migrations_on_startup: env::var("RUN_MIGRATIONS_ON_STARTUP")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .expect("RUN_MIGRATIONS_ON_STARTUP must be either true or false"),
+            mongodb_connection_string: env::var("MONGODB_CONNECTION_STRING").ok(),
+            mongodb_max_pool_size: env::var("MONGODB_MAX_POOL_SIZE")
+                .ok()
+                .map(|s| s.parse().expect("MONGODB_MAX_POOL_SIZE must be a number")),
+            postgres_connection_string: env::var("POSTGRES_CONNECTION_STRING").ok(),
+            postgres_max_pool_size: env::var("POSTGRES_MAX_POOL_SIZE")
+                .ok()
+                .map(|s| s.parse().expect("POSTGRES_MAX_POOL_SIZE must be a number")),
+            sqlite_database_path: env::var("SQLITE_DATABASE_PATH").ok(),
+            admin_api_key: env::var("ADMIN_API_KEY").ok().filter(|key| !key.is_empty()),
+            restore_window_hours: env::var("RESTORE_WINDOW_HOURS")
+                .unwrap_or_else(|_| "168".to_string())
+                .parse()
+                .expect("RESTORE_WINDOW_HOURS must be a number"),
+            purge_interval_minutes: env::var("PURGE_INTERVAL_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("PURGE_INTERVAL_MINUTES must be a number"),
+        }
+    }
+
+    pub fn restore_window(&self) -> RestoreWindow {
+        RestoreWindow(chrono::Duration::hours(i64::from(
+            self.restore_window_hours,
+        )))
+    }
+}
 
+pub async fn init_server() {
     // Configure server
-    let config = Config {
-        server_host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
-        server_port: env::var("SERVER_PORT")
-            .unwrap_or_else(|_| "7000".to_string())
-            .parse()
-            .expect("SERVER_PORT must be a number"),
-        mongodb_connection_string: env::var("MONGODB_CONNECTION_STRING")
-            .expect("MONGODB_CONNECTION_STRING must be set"),
-        mongodb_max_pool_size: env::var("MONGODB_MAX_POOL_SIZE")
-            .ok()
-            .map(|s| s.parse().expect("MONGODB_MAX_POOL_SIZE must be a number")),
-    };
+    let config = Config::from_env();
+
+    let comment_store = init_comment_store(&config).await;
+
+    if config.run_migrations_on_startup {
+        apply_migrations(comment_store.as_ref()).await;
+    }
+
+    if config.admin_api_key.is_none() {
+        info!("ADMIN_API_KEY is not set, privileged endpoints are disabled");
+    }
+
+    // purging is idempotent, so it is fine for every instance to run the job. an interval of 0
+    // leaves purging to `commenter purge`, e.g. run by cron
+    if config.purge_interval_minutes > 0 {
+        spawn_purge_job(
+            comment_store.clone(),
+            config.restore_window(),
+            Duration::from_secs(u64::from(config.purge_interval_minutes) * 60),
+        );
+    }
+
+    let api_routes = api_routes(
+        comment_store,
+        AdminApiKey(config.admin_api_key.clone()),
+        config.restore_window(),
+    );
+
+    let health_probe_routes: Router = Router::new().route("/healthz", get(health_check));
+    let app = Router::new().merge(api_routes).merge(health_probe_routes);
+
+    let addr_str = format!("{}:{}", &config.server_host, &config.server_port);
+    let addr = SocketAddr::from_str(&addr_str).expect("invalid server address in config");
+    info!("Config server listening on {}", addr);
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+pub async fn apply_migrations(comment_store: &dyn CommentStore) {
+    let applied_migration_ids = comment_store.migrate().await.unwrap();
+    if applied_migration_ids.is_empty() {
+        info!("Storage schema is up to date");
+    } else {
+        info!("Applied migrations: {}", applied_migration_ids.join(", "));
+    }
+}
+
+pub async fn init_comment_store(config: &Config) -> Arc<dyn CommentStore> {
+    match config.storage_backend {
+        StorageBackend::Mongo => Arc::new(init_mongo_comment_store(config).await),
+        StorageBackend::Postgres => Arc::new(init_postgres_comment_store(config).await),
+        StorageBackend::Sqlite => Arc::new(init_sqlite_comment_store(config).await),
+        StorageBackend::Memory => {
+            info!("Using the in-memory storage backend, comments will not survive a restart");
+            Arc::new(InMemoryCommentStore::new())
+        }
+    }
+}
+
+async fn init_mongo_comment_store(config: &Config) -> MongoCommentStore {
+    let mongodb_connection_string = config
+        .mongodb_connection_string
+        .as_deref()
+        .expect("MONGODB_CONNECTION_STRING must be set");
 
-    let mongo_client = init_mongo_connection(
-        &config.mongodb_connection_string,
-        config.mongodb_max_pool_size,
-    )
-    .await
-    .unwrap();
+    let mongo_client =
+        init_mongo_connection(mongodb_connection_string, config.mongodb_max_pool_size)
+            .await
+            .unwrap();
 
     // extract the database name from the connection string
-    let options = ClientOptions::parse(&config.mongodb_connection_string)
+    let options = ClientOptions::parse(mongodb_connection_string)
         .await
         .unwrap();
     let mongo_db_name = options.default_database.clone().unwrap_or_default();
 
     let mongo_config = MongoDbConfig { mongo_db_name };
 
-    let persistent_layer = PersistentLayer {
+    let comment_store = MongoCommentStore {
         mongo_client,
         mongo_config,
     };
 
-    let api_routes = Router::new()
-        .route("/root-comment/new", post(create_root_comment))
-        .route("/root-comments", get(get_root_comments))
-        .route("/branch-comment/new", post(create_branch_comment))
-        .route("/branch-comments/next", get(get_branch_comments_next))
-        .route("/branch-comments/rest", get(get_branch_comments_rest))
-        .route("/comments/all", get(get_all_comments))
-        .route("/comment/update", post(update_comment_text))
-        .route("/comment/delete", post(delete_comment))
-        .route("/reaction/new", post(react_to_comment))
-        .route("/reaction/undo", post(undo_react_to_comment))
-        .layer(Extension(Arc::new(persistent_layer)));
+    let created_index_names = comment_store.ensure_indexes().await.unwrap();
+    if created_index_names.is_empty() {
+        info!("MongoDB indexes are up to date");
+    } else {
+        info!(
+            "Created MongoDB indexes: {}",
+            created_index_names.join(", ")
+        );
+    }
 
-    let health_probe_routes: Router = Router::new().route("/healthz", get(health_check));
-    let app = Router::new().merge(api_routes).merge(health_probe_routes);
+    comment_store
+}
 
-    let addr_str = format!("{}:{}", &config.server_host, &config.server_port);
-    let addr = SocketAddr::from_str(&addr_str).expect("invalid server address in config");
-    info!("Config server listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+async fn init_postgres_comment_store(config: &Config) -> PostgresCommentStore {
+    let postgres_connection_string = config
+        .postgres_connection_string
+        .as_deref()
+        .expect("POSTGRES_CONNECTION_STRING must be set");
+
+    let pool = init_postgres_connection(postgres_connection_string, config.postgres_max_pool_size)
         .await
         .unwrap();
+
+    PostgresCommentStore { pool }
+}
+
+async fn init_sqlite_comment_store(config: &Config) -> SqliteCommentStore {
+    let sqlite_database_path = config
+        .sqlite_database_path
+        .as_deref()
+        .expect("SQLITE_DATABASE_PATH must be set");
+
+    let pool = init_sqlite_connection(sqlite_database_path).await.unwrap();
+
+    SqliteCommentStore { pool }
+}
+
+fn api_routes<S: CommentStore + ?Sized>(
+    persistent_layer: Arc<S>,
+    admin_api_key: AdminApiKey,
+    restore_window: RestoreWindow,
+) -> Router {
+    Router::new()
+        .route("/root-comment/new", post(create_root_comment::<S>))
+        .route("/root-comments", get(get_root_comments::<S>))
+        .route("/branch-comment/new", post(create_branch_comment::<S>))
+        .route("/branch-comments/next", get(get_branch_comments_next::<S>))
+        .route("/branch-comments/rest", get(get_branch_comments_rest::<S>))
+        .route(
+            "/branch-comments/levels",
+            get(get_branch_comments_levels::<S>),
+        )
+        .route("/comments/all", get(get_all_comments::<S>))
+        .route("/comment/update", post(update_comment_text::<S>))
+        .route("/comment/delete", post(delete_comment::<S>))
+        .route("/comment/restore", post(restore_comment::<S>))
+        .route("/comment/prune", post(prune_comment::<S>))
+        .route("/comment/move", post(move_comment::<S>))
+        .route("/comments/pending", get(get_pending_comments::<S>))
+        .route("/comment/approve", post(approve_comment::<S>))
+        .route("/comment/reject", post(reject_comment::<S>))
+        .route("/reaction/new", post(react_to_comment::<S>))
+        .route("/reaction/undo", post(undo_react_to_comment::<S>))
+        .route("/reactions", get(get_reactions::<S>))
+        .route(
+            "/branch-comment-ids/rebuild",
+            post(rebuild_branch_comment_ids::<S>),
+        )
+        .route("/resource/new", post(create_resource::<S>))
+        .route("/resource", get(get_resource::<S>))
+        .route("/resource/update", post(update_resource::<S>))
+        .route("/resource/merge", post(merge_resources::<S>))
+        .route("/resource/alias/new", post(create_resource_alias::<S>))
+        .route("/resource/alias/delete", post(delete_resource_alias::<S>))
+        .route("/resource/aliases", get(get_resource_aliases::<S>))
+        .route("/resource/export", get(export_resource_comments::<S>))
+        .route("/resource/settings", get(get_resource_settings::<S>))
+        .route(
+            "/resource/settings/update",
+            post(update_resource_settings::<S>),
+        )
+        .layer(Extension(persistent_layer))
+        .layer(Extension(admin_api_key))
+        .layer(Extension(restore_window))
 }